@@ -1,10 +1,138 @@
 extern crate vfh;
 
+use std::process::ExitCode;
 use vfh::music::*;
 
-fn main() {
-    let config: Config = Config::new().unwrap();
-    let mut tree: Node = Node::new();
-    let _ = populate(&config.base, &mut tree);
-    dbg!(tree);
+const USAGE: &str =
+    "Usage: music [--config <path>] [--base <path>] [--format tree|json|debug] [--pretty] \
+     [--dirs-first]";
+
+/// Output format of the Node tree.
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Tree,
+    Json,
+    Debug,
+}
+
+/// Command-line arguments.
+struct Args {
+    config: Option<PathBuf>,
+    overrides: ConfigOverrides,
+    format: Format,
+    pretty: bool,
+    order: KindOrder,
+}
+
+/// Helper function that parses the command-line arguments, returning a message on failure.
+fn parse_args(mut args: impl Iterator<Item = String>) -> std::result::Result<Args, String> {
+    let mut parsed: Args = Args {
+        config: None,
+        overrides: ConfigOverrides::default(),
+        format: Format::Tree,
+        pretty: false,
+        order: KindOrder::Mixed,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or(format!("{} expects a value", flag));
+        match arg.as_str() {
+            "--config" => parsed.config = Some(PathBuf::from(value("--config")?)),
+            "--base" => parsed.overrides.base = Some(PathBuf::from(value("--base")?)),
+            "--format" => {
+                parsed.format = match value("--format")?.as_str() {
+                    "tree" => Format::Tree,
+                    "json" => Format::Json,
+                    "debug" => Format::Debug,
+                    other => return Err(format!("unknown format {}", other)),
+                }
+            }
+            "--pretty" => parsed.pretty = true,
+            "--dirs-first" => parsed.order = KindOrder::DirectoriesFirst,
+            other => return Err(format!("unknown argument {}", other)),
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn main() -> ExitCode {
+    let args: Args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let loaded = match &args.config {
+        Some(path) => Config::from_path(path),
+        None => Config::new(),
+    };
+    let mut config: Config = match loaded {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Invalid configuration: {}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+    config.merge_overrides(args.overrides);
+    if let Err(error) = config.validate() {
+        eprintln!("Invalid music library: {}", error);
+        return ExitCode::FAILURE;
+    }
+
+    let scanned: BTreeMap<String, (Node, ScanStats)> = match populate_libraries_with_stats(&config)
+    {
+        Ok(scanned) => scanned,
+        Err(error) => {
+            // The base may have vanished since it was validated
+            match error
+                .get_ref()
+                .and_then(|e| e.downcast_ref::<ConfigError>())
+            {
+                Some(base) => eprintln!("Invalid music library: {}", base),
+                None => eprintln!("Could not read music library: {}", error),
+            }
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut libraries: BTreeMap<String, Node> = BTreeMap::new();
+    for (name, (tree, stats)) in scanned {
+        eprintln!("Scanned library {}: {}", name, stats);
+        libraries.insert(name, tree);
+    }
+    match args.format {
+        Format::Tree => {
+            for (name, tree) in &libraries {
+                println!("Library {}:", name);
+                let options: RenderOptions = RenderOptions {
+                    order: args.order,
+                    ..RenderOptions::default()
+                };
+                print!("{}", render_tree(tree, &options));
+            }
+        }
+        Format::Json => {
+            let json: BTreeMap<&str, NodeJson> = libraries
+                .iter()
+                .map(|(name, tree)| (name.as_str(), node_json(tree, args.order)))
+                .collect();
+            match args.pretty {
+                true => println!("{}", serde_json::to_string_pretty(&json).unwrap()),
+                false => println!("{}", serde_json::to_string(&json).unwrap()),
+            }
+        }
+        Format::Debug => {
+            for (name, tree) in &libraries {
+                println!("Library {}:", name);
+                match args.pretty {
+                    true => println!("{:#?}", tree),
+                    false => println!("{:?}", tree),
+                }
+            }
+        }
+    }
+
+    ExitCode::SUCCESS
 }
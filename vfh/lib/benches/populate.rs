@@ -0,0 +1,68 @@
+//! Compares `populate()` with `populate_parallel()` on a generated library.
+//!
+//! Run with `cargo bench -p vfh --features rayon`.
+
+use std::fs::{create_dir_all, remove_dir_all, write};
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+use vfh::music::*;
+
+const ARTISTS: usize = 40;
+const ALBUMS: usize = 10;
+const TRACKS: usize = 12;
+const RUNS: u32 = 5;
+
+/// Helper function that writes a library of `ARTISTS` artists with `ALBUMS` albums of `TRACKS`
+/// tracks each below `base`, with cover art for every album.
+fn generate(base: &Path) -> std::io::Result<()> {
+    let _ = remove_dir_all(base);
+    for artist in 0..ARTISTS {
+        for album in 0..ALBUMS {
+            let dir: PathBuf = base
+                .join(format!("Artist {}", artist))
+                .join(format!("Album {}", album));
+            create_dir_all(&dir)?;
+            write(dir.join(format!("Album {}.jpg", album)), "")?;
+            for track in 0..TRACKS {
+                write(dir.join(format!("{:02} Track.mp3", track)), "")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Helper function that returns the fastest of `RUNS` runs of `scan`, along with its tree.
+fn time(scan: impl Fn() -> Node) -> (Duration, Node) {
+    let mut best: Duration = Duration::MAX;
+    let mut tree: Node = Node::new();
+    for _ in 0..RUNS {
+        let start: Instant = Instant::now();
+        tree = scan();
+        best = best.min(start.elapsed());
+    }
+    (best, tree)
+}
+
+fn main() -> std::io::Result<()> {
+    let base: PathBuf = env::temp_dir().join("vfh-bench-populate");
+    generate(&base)?;
+    let config: Config = toml::from_str("base = ''").unwrap();
+
+    let (serial, expected) = time(|| {
+        let mut tree: Node = Node::new();
+        populate(&base, &mut tree, &config).unwrap();
+        tree
+    });
+    let (parallel, tree) =
+        time(|| populate_parallel(&base, &config, &AtomicBool::new(false)).unwrap());
+    assert_eq!(tree, expected);
+
+    println!(
+        "{} files: populate {:?}, populate_parallel {:?} ({:.1}x)",
+        ARTISTS * ALBUMS * (TRACKS + 1),
+        serial,
+        parallel,
+        serial.as_secs_f64() / parallel.as_secs_f64()
+    );
+    remove_dir_all(&base)
+}
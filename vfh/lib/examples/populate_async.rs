@@ -0,0 +1,46 @@
+//! Builds the Node tree of the configured base directory from an async main, while another task
+//! keeps running on the same runtime.
+//!
+//! Run with `cargo run -p vfh --example populate_async --features tokio [-- <dir>]`.
+
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+use vfh::music::*;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let config: Config = match Config::new() {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    let dir: PathBuf = env::args()
+        .nth(1)
+        .map_or(config.base.clone(), PathBuf::from);
+
+    // Stands in for the other commands of an async backend, which keep being served
+    let heartbeat = tokio::spawn(async {
+        let mut ticks: u64 = 0;
+        loop {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            ticks += 1;
+            eprintln!("still responsive after {} ticks", ticks);
+        }
+    });
+
+    // Would be set by another command, for instance when the user picks another directory
+    let cancel: AtomicBool = AtomicBool::new(false);
+    let tree: Node = populate_async(&dir, &config, &cancel).await?;
+    heartbeat.abort();
+
+    let counts: TreeCounts = count_tree(&tree);
+    println!(
+        "{}: {} songs in {} directories",
+        dir.display(),
+        counts.songs,
+        counts.dirs
+    );
+    Ok(())
+}
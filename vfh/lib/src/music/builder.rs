@@ -0,0 +1,236 @@
+use super::config::{Config, ConfigError};
+use std::path::PathBuf;
+
+/// Fluent builder for constructing a `Config` programmatically, for instance from UI state or in
+/// test fixtures, without touching the filesystem or environment variables.
+///
+/// A base path is required by `ConfigBuilder::new()`, so a `Config` can never be built without
+/// one. Every other field starts out at its `Config::default()` value.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let config: Config = ConfigBuilder::new("/mnt/music")
+///     .extensions(["flac", "mp3"])
+///     .exclude(["Recordings/"])
+///     .follow_symlinks(true)
+///     .max_depth(Some(2))
+///     .build()
+///     .unwrap();
+/// assert_eq!(config.base, PathBuf::from("/mnt/music"));
+/// assert_eq!(config.extensions, ["flac", "mp3"]);
+/// assert!(config.follow_symlinks);
+///
+/// // Round trip through the builder
+/// let rebuilt: Config = ConfigBuilder::from(config.clone()).build().unwrap();
+/// assert_eq!(rebuilt, config);
+///
+/// // Relative roots are rejected
+/// assert_eq!(
+///     ConfigBuilder::new("Music").build(),
+///     Err(ConfigError::NotAbsoluteAfterExpansion(PathBuf::from("Music")))
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    /// Helper function that returns a builder for a library rooted at `base`.
+    pub fn new(base: impl Into<PathBuf>) -> ConfigBuilder {
+        let mut config: Config = Config::default();
+        config.base = base.into();
+        ConfigBuilder { config }
+    }
+
+    /// Sets `Config.base`.
+    pub fn base(mut self, base: impl Into<PathBuf>) -> ConfigBuilder {
+        self.config.base = base.into();
+        self
+    }
+
+    /// Sets `Config.bases`.
+    pub fn bases<P: Into<PathBuf>>(mut self, bases: impl IntoIterator<Item = P>) -> ConfigBuilder {
+        self.config.bases = bases.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets `Config.extensions`.
+    pub fn extensions<S: Into<String>>(
+        mut self,
+        extensions: impl IntoIterator<Item = S>,
+    ) -> ConfigBuilder {
+        self.config.extensions = extensions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets `Config.art_extensions`.
+    pub fn art_extensions<S: Into<String>>(
+        mut self,
+        art_extensions: impl IntoIterator<Item = S>,
+    ) -> ConfigBuilder {
+        self.config.art_extensions = art_extensions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets `Config.case_insensitive_extensions`.
+    pub fn case_insensitive_extensions(
+        mut self,
+        case_insensitive_extensions: bool,
+    ) -> ConfigBuilder {
+        self.config.case_insensitive_extensions = case_insensitive_extensions;
+        self
+    }
+
+    /// Sets `Config.sniff_content`.
+    pub fn sniff_content(mut self, sniff_content: bool) -> ConfigBuilder {
+        self.config.sniff_content = sniff_content;
+        self
+    }
+
+    /// Sets `Config.normalize_keys`.
+    pub fn normalize_keys(mut self, normalize_keys: bool) -> ConfigBuilder {
+        self.config.normalize_keys = normalize_keys;
+        self
+    }
+
+    /// Sets `Config.fold_case`.
+    pub fn fold_case(mut self, fold_case: bool) -> ConfigBuilder {
+        self.config.fold_case = fold_case;
+        self
+    }
+
+    /// Sets `Config.default_art`.
+    pub fn default_art(mut self, default_art: Option<PathBuf>) -> ConfigBuilder {
+        self.config.default_art = default_art;
+        self
+    }
+
+    /// Sets `Config.exclude`.
+    pub fn exclude<S: Into<String>>(
+        mut self,
+        exclude: impl IntoIterator<Item = S>,
+    ) -> ConfigBuilder {
+        self.config.exclude = exclude.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets `Config.include_hidden`.
+    pub fn include_hidden(mut self, include_hidden: bool) -> ConfigBuilder {
+        self.config.include_hidden = include_hidden;
+        self
+    }
+
+    /// Sets `Config.follow_symlinks`.
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> ConfigBuilder {
+        self.config.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Sets `Config.canonicalize_base`.
+    pub fn canonicalize_base(mut self, canonicalize_base: bool) -> ConfigBuilder {
+        self.config.canonicalize_base = canonicalize_base;
+        self
+    }
+
+    /// Sets `Config.max_depth`.
+    pub fn max_depth(mut self, max_depth: Option<usize>) -> ConfigBuilder {
+        self.config.max_depth = max_depth;
+        self
+    }
+
+    /// Sets `Config.max_entries`.
+    pub fn max_entries(mut self, max_entries: Option<usize>) -> ConfigBuilder {
+        self.config.max_entries = max_entries;
+        self
+    }
+
+    /// Sets `Config.prune_empty_dirs`.
+    pub fn prune_empty_dirs(mut self, prune_empty_dirs: bool) -> ConfigBuilder {
+        self.config.prune_empty_dirs = prune_empty_dirs;
+        self
+    }
+
+    /// Sets `Config.abort_on_error`.
+    pub fn abort_on_error(mut self, abort_on_error: bool) -> ConfigBuilder {
+        self.config.abort_on_error = abort_on_error;
+        self
+    }
+
+    /// Sets `Config.read_timeout_secs`.
+    pub fn read_timeout_secs(mut self, read_timeout_secs: Option<u64>) -> ConfigBuilder {
+        self.config.read_timeout_secs = read_timeout_secs;
+        self
+    }
+
+    /// Sets `Config.max_open_dirs`.
+    pub fn max_open_dirs(mut self, max_open_dirs: usize) -> ConfigBuilder {
+        self.config.max_open_dirs = max_open_dirs;
+        self
+    }
+
+    /// Sets `Config.skip_empty_files`.
+    pub fn skip_empty_files(mut self, skip_empty_files: bool) -> ConfigBuilder {
+        self.config.skip_empty_files = skip_empty_files;
+        self
+    }
+
+    /// Sets `Config.skip_junk`.
+    pub fn skip_junk(mut self, skip_junk: bool) -> ConfigBuilder {
+        self.config.skip_junk = skip_junk;
+        self
+    }
+
+    /// Sets `Config.junk`.
+    pub fn junk<S: Into<String>>(mut self, junk: impl IntoIterator<Item = S>) -> ConfigBuilder {
+        self.config.junk = junk.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets `Config.articles`.
+    pub fn articles<S: Into<String>>(
+        mut self,
+        articles: impl IntoIterator<Item = S>,
+    ) -> ConfigBuilder {
+        self.config.articles = articles.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets `Config.rescan_interval_secs`.
+    pub fn rescan_interval_secs(mut self, rescan_interval_secs: Option<u64>) -> ConfigBuilder {
+        self.config.rescan_interval_secs = rescan_interval_secs;
+        self
+    }
+
+    /// Helper function that returns the built `Config`, checking that every entry point returned
+    /// by `Config::roots()` is an absolute path.
+    ///
+    /// Unlike `Config::validate()`, the filesystem is never accessed.
+    pub fn build(self) -> Result<Config, ConfigError> {
+        if let Some(root) = self
+            .config
+            .roots()
+            .into_iter()
+            .find(|root| !root.is_absolute())
+        {
+            return Err(ConfigError::NotAbsoluteAfterExpansion(root));
+        }
+
+        Ok(self.config)
+    }
+}
+
+impl From<Config> for ConfigBuilder {
+    fn from(config: Config) -> ConfigBuilder {
+        ConfigBuilder { config }
+    }
+}
+
+impl TryFrom<ConfigBuilder> for Config {
+    type Error = ConfigError;
+
+    fn try_from(builder: ConfigBuilder) -> Result<Config, ConfigError> {
+        builder.build()
+    }
+}
@@ -0,0 +1,149 @@
+use super::node::{deserialize_hex, serialize_hex, Fnv1a, Node};
+use super::platform::{to_long_path, write_atomic};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs::{metadata, read_dir, read_to_string, DirEntry};
+use std::hash::Hasher;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// Cache file written by `save_cache()`.
+///
+/// # Fields
+/// `fingerprint`: `u64` -> `fingerprint()` of the base directory when the tree was built.
+/// `tree`: `Node` -> The cached Node tree.
+#[derive(Deserialize, Serialize)]
+struct CacheFile {
+    #[serde(serialize_with = "serialize_hex", deserialize_with = "deserialize_hex")]
+    fingerprint: u64,
+    tree: Node,
+}
+
+/// Helper function that returns a hash of the file hierarchy below `base`, which changes whenever
+/// a file or directory is added, removed or renamed, or a directory is modified, without reading
+/// the contents of any file.
+///
+/// The names of every file and directory and the modification times of every directory are
+/// hashed, in file name order so the result is the same for the same hierarchy. Symbolic links
+/// are hashed by name but not followed, and directories that cannot be read hash differently
+/// from readable ones.
+///
+/// Only compare fingerprints for equality. Modification times come from the clock of the machine
+/// writing the files and may go backwards, and filesystems such as FAT only record them to a
+/// couple of seconds, so a later fingerprint being smaller or larger means nothing. Names are
+/// hashed to still tell apart most changes made within such a coarse time window.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-fingerprint");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Album")).unwrap();
+/// write(base.join("Album").join("1.mp3"), "").unwrap();
+///
+/// let before: u64 = fingerprint(&base);
+/// assert_eq!(fingerprint(&base), before);
+///
+/// write(base.join("Album").join("2.mp3"), "").unwrap();
+/// assert_ne!(fingerprint(&base), before);
+/// ```
+pub fn fingerprint(base: &Path) -> u64 {
+    let mut hasher: Fnv1a = Fnv1a::default();
+    hash_dir(&mut hasher, base, Path::new(""));
+    hasher.finish()
+}
+
+/// Helper function that hashes the directory `dir`, found at `relative` below the base
+/// directory, and its descendants into `hasher`, as described in `fingerprint()`.
+fn hash_dir(hasher: &mut Fnv1a, dir: &Path, relative: &Path) {
+    hasher.write(relative.as_os_str().as_encoded_bytes());
+    hasher.write(&[0]);
+
+    match metadata(to_long_path(dir))
+        .and_then(|m| m.modified())
+        .map(|modified| modified.duration_since(UNIX_EPOCH))
+    {
+        Ok(Ok(after)) => {
+            hasher.write(&[1]);
+            hasher.write(&after.as_secs().to_le_bytes());
+            hasher.write(&after.subsec_nanos().to_le_bytes());
+        }
+        Ok(Err(before)) => {
+            hasher.write(&[2]);
+            hasher.write(&before.duration().as_secs().to_le_bytes());
+            hasher.write(&before.duration().subsec_nanos().to_le_bytes());
+        }
+        Err(_) => hasher.write(&[3]),
+    }
+
+    let mut entries: Vec<DirEntry> =
+        match read_dir(to_long_path(dir)).and_then(|entries| entries.collect()) {
+            Ok(entries) => entries,
+            Err(_) => {
+                hasher.write(b"\0unreadable");
+                return;
+            }
+        };
+    entries.sort_by_key(DirEntry::file_name);
+
+    let mut subdirs: Vec<OsString> = Vec::new();
+    for entry in &entries {
+        let is_dir: bool = entry.file_type().is_ok_and(|kind| kind.is_dir());
+        hasher.write(entry.file_name().as_encoded_bytes());
+        hasher.write(&[0, u8::from(is_dir)]);
+        if is_dir {
+            subdirs.push(entry.file_name());
+        }
+    }
+
+    for name in subdirs {
+        hash_dir(hasher, &dir.join(&name), &relative.join(&name));
+    }
+}
+
+/// Helper function that writes `node`, built from `base`, to the cache file at `cache_path`
+/// together with the `fingerprint()` of `base`, creating any missing parent directories. The file
+/// is replaced atomically, so a crash never leaves a truncated cache behind.
+pub fn save_cache(cache_path: &Path, base: &Path, node: &Node) -> Result<()> {
+    let cache: CacheFile = CacheFile {
+        fingerprint: fingerprint(base),
+        tree: node.clone(),
+    };
+    let contents: String =
+        toml::to_string(&cache).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    write_atomic(cache_path, contents.as_bytes())
+}
+
+/// Helper function that returns the Node tree cached at `cache_path` by `save_cache()` if the
+/// `fingerprint()` of `base` has not changed since, or `None` if it has or the cache file is
+/// missing or cannot be read, in which case `base` should be scanned again.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let dir: PathBuf = env::temp_dir().join("vfh-doc-load-cache-if-fresh");
+/// # let _ = remove_dir_all(&dir);
+/// let base: PathBuf = dir.join("music");
+/// let cache: PathBuf = dir.join("cache").join("tree.toml");
+/// create_dir_all(base.join("Album")).unwrap();
+/// write(base.join("Album").join("1.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// assert_eq!(load_cache_if_fresh(&cache, &base), None);
+/// save_cache(&cache, &base, &tree).unwrap();
+/// assert_eq!(load_cache_if_fresh(&cache, &base), Some(tree));
+///
+/// write(base.join("Album").join("2.mp3"), "").unwrap();
+/// assert_eq!(load_cache_if_fresh(&cache, &base), None);
+/// ```
+pub fn load_cache_if_fresh(cache_path: &Path, base: &Path) -> Option<Node> {
+    let contents: String = read_to_string(to_long_path(cache_path)).ok()?;
+    let cache: CacheFile = toml::from_str(&contents).ok()?;
+    (cache.fingerprint == fingerprint(base)).then_some(cache.tree)
+}
@@ -0,0 +1,357 @@
+use super::config::Config;
+use super::node::{whole_seconds, Entry, EntryKind, Node};
+use super::platform::{to_long_path, write_atomic};
+use super::traverse::{get_nested_mut, iter_depth_first};
+use super::utils::{check_base, latest_modified, prune_empty_dirs, read_one_dir, scan_base};
+use serde::{Deserialize, Serialize};
+use std::fs::{metadata, read_to_string, remove_file};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Version of the format of the checkpoint files written by `populate_resumable()`. Checkpoints
+/// of any other version are not resumed.
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// Checkpoint file written by `populate_resumable()`.
+///
+/// # Fields
+/// `version`: `u32` -> `CHECKPOINT_VERSION` of the program that wrote the checkpoint.
+/// `base`: `PathBuf` -> The directory being scanned, canonicalized unless
+///                      `Config.canonicalize_base` is unset.
+/// `frontier`: `Vec<Vec<String>>` -> Keys of the directories of `tree` inserted but not read yet,
+///                                   the next one to read last.
+/// `interval`: `Duration` -> Time between two checkpoints.
+/// `config`: `Config` -> The configuration driving the scan.
+/// `tree`: `Node` -> The Node tree built so far, whose directories in `frontier` have no children
+///                   yet and whose directories do not have their `size` and `modified` fields
+///                   set yet.
+#[derive(Deserialize, Serialize)]
+struct Checkpoint {
+    version: u32,
+    base: PathBuf,
+    frontier: Vec<Vec<String>>,
+    interval: Duration,
+    config: Config,
+    tree: Node,
+}
+
+/// Version of a checkpoint file, read before the rest of it, which may not follow the current
+/// format.
+#[derive(Deserialize)]
+struct CheckpointVersion {
+    version: u32,
+}
+
+impl Checkpoint {
+    /// Helper function that returns the checkpoint saved at `path`.
+    ///
+    /// # Errors
+    /// - Any error returned when reading `path`, such as `ErrorKind::NotFound`.
+    /// - `ErrorKind::InvalidData` if `path` holds no checkpoint of the current
+    ///   `CHECKPOINT_VERSION`.
+    fn load(path: &Path) -> Result<Checkpoint> {
+        let invalid = |error: toml::de::Error| Error::new(ErrorKind::InvalidData, error);
+        let contents: String = read_to_string(to_long_path(path))?;
+        let version: u32 = toml::from_str::<CheckpointVersion>(&contents)
+            .map_err(invalid)?
+            .version;
+        if version != CHECKPOINT_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "{} is a checkpoint of version {}, not {}",
+                    path.display(),
+                    version,
+                    CHECKPOINT_VERSION
+                ),
+            ));
+        }
+        toml::from_str(&contents).map_err(invalid)
+    }
+
+    /// Helper function that saves the checkpoint to `path`, replacing it atomically so an
+    /// interrupted write leaves the previous checkpoint in place.
+    fn save(&self, path: &Path) -> Result<()> {
+        let contents: String =
+            toml::to_string(self).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        write_atomic(path, contents.as_bytes())
+    }
+}
+
+/// Helper function that builds the Node tree of `dir` like `populate()` does, saving the progress
+/// of the scan to `checkpoint_path` every `interval`, so a scan that is interrupted can go on
+/// where it stopped instead of starting over.
+///
+/// A checkpoint holds the tree built so far and the directories still to read. When
+/// `checkpoint_path` already holds a checkpoint of the current `CHECKPOINT_VERSION` for the same
+/// `dir` and `config`, the scan resumes from it. Otherwise it starts from scratch, replacing the
+/// checkpoint. Checkpoints are replaced atomically, so a scan interrupted while writing one still
+/// resumes from the previous one. The checkpoint is removed once the scan is done.
+///
+/// The scan checks `cancel` before every file and directory it reads. Once it is set, a last
+/// checkpoint is saved and an error of kind `ErrorKind::Interrupted` is returned, so an
+/// application being closed can stop the scan to resume it with `resume()` on the next run. Any
+/// other error saves a checkpoint as well before it is returned.
+///
+/// Directories are read one at a time, and a checkpoint is only saved between two directories,
+/// so at most `interval` plus the time taken to read a single directory is lost when the process
+/// is killed. The directory being read when the scan is interrupted is read again from scratch.
+/// The returned tree is identical to the one `populate()` builds, except that once
+/// `Config.max_entries` is reached, the directories inserted so far are kept without reading
+/// their contents, so which entries are kept may differ. The `ScanStats` are not collected.
+///
+/// Returns an error wrapping a `ConfigError` when `dir` is not a usable directory, as
+/// `populate()` does.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// # use std::io::ErrorKind;
+/// # use std::sync::atomic::{AtomicBool, Ordering};
+/// # use std::time::Duration;
+/// let dir: PathBuf = env::temp_dir().join("vfh-doc-populate-resumable");
+/// # let _ = remove_dir_all(&dir);
+/// let base: PathBuf = dir.join("music");
+/// let checkpoint: PathBuf = dir.join("scan.checkpoint.toml");
+/// for album in ["Album", "Other"] {
+///     create_dir_all(base.join(album).join("Disc 1")).unwrap();
+///     write(base.join(album).join("Disc 1").join("1.mp3"), "").unwrap();
+///     write(base.join(album).join("intro.mp3"), "").unwrap();
+/// }
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let scanned: Node = populate_from(&base, &config).unwrap();
+///
+/// // A scan stopped as the application closes leaves a checkpoint behind
+/// let cancel: AtomicBool = AtomicBool::new(true);
+/// let stopped = populate_resumable(&base, &checkpoint, Duration::ZERO, &config, &cancel);
+/// assert_eq!(stopped.unwrap_err().kind(), ErrorKind::Interrupted);
+/// assert!(checkpoint.exists());
+///
+/// // The next run goes on from it
+/// cancel.store(false, Ordering::Relaxed);
+/// assert_eq!(resume(&checkpoint, &cancel).unwrap(), scanned);
+/// assert!(!checkpoint.exists());
+///
+/// // A checkpoint of another directory is not resumed
+/// cancel.store(true, Ordering::Relaxed);
+/// let other: PathBuf = base.join("Other");
+/// let stopped = populate_resumable(&other, &checkpoint, Duration::ZERO, &config, &cancel);
+/// assert_eq!(stopped.unwrap_err().kind(), ErrorKind::Interrupted);
+/// cancel.store(false, Ordering::Relaxed);
+/// let tree: Node =
+///     populate_resumable(&base, &checkpoint, Duration::ZERO, &config, &cancel).unwrap();
+/// assert_eq!(tree, scanned);
+/// ```
+///
+/// A configuration loaded from its file resumes the scan as well, as long as its settings did not
+/// change:
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, remove_file, write};
+/// # use std::sync::atomic::AtomicBool;
+/// # use std::time::Duration;
+/// # #[cfg(unix)]
+/// # {
+/// let dir: PathBuf = env::temp_dir().join("vfh-doc-populate-resumable-config");
+/// # let _ = remove_dir_all(&dir);
+/// let base: PathBuf = dir.join("music");
+/// let checkpoint: PathBuf = dir.join("scan.checkpoint.toml");
+/// create_dir_all(base.join("Album")).unwrap();
+/// create_dir_all(base.join("Broken")).unwrap();
+/// write(base.join("Album").join("1.mp3"), "").unwrap();
+/// // A link to itself, which cannot be read
+/// std::os::unix::fs::symlink("loop.mp3", base.join("Broken").join("loop.mp3")).unwrap();
+/// write(
+///     dir.join("config.toml"),
+///     format!(
+///         "base = '{}'\nfollow_symlinks = true\nabort_on_error = true\n",
+///         base.display()
+///     ),
+/// )
+/// .unwrap();
+///
+/// // The scan fails once `Album` was read
+/// let config: Config = Config::from_path(&dir.join("config.toml")).unwrap();
+/// let cancel: AtomicBool = AtomicBool::new(false);
+/// assert!(populate_resumable(&base, &checkpoint, Duration::ZERO, &config, &cancel).is_err());
+/// assert!(checkpoint.exists());
+///
+/// // The next run, with the configuration loaded again, does not read `Album` again
+/// remove_file(base.join("Broken").join("loop.mp3")).unwrap();
+/// write(base.join("Album").join("2.mp3"), "").unwrap();
+/// let config: Config = Config::from_path(&dir.join("config.toml")).unwrap();
+/// let tree: Node =
+///     populate_resumable(&base, &checkpoint, Duration::ZERO, &config, &cancel).unwrap();
+/// assert_eq!(tree["Album"].child.keys().collect::<Vec<_>>(), ["1"]);
+/// assert!(!checkpoint.exists());
+/// assert_eq!(populate_from(&base, &config).unwrap()["Album"].child.len(), 2);
+/// # }
+/// ```
+pub fn populate_resumable(
+    dir: &Path,
+    checkpoint_path: &Path,
+    interval: Duration,
+    config: &Config,
+    cancel: &AtomicBool,
+) -> Result<Node> {
+    let base: PathBuf = scan_base(dir, config);
+    check_base(&base)?;
+
+    let checkpoint: Checkpoint = match Checkpoint::load(checkpoint_path) {
+        Ok(checkpoint) if checkpoint.base == base && checkpoint.config.same_settings(config) => {
+            Checkpoint {
+                interval,
+                ..checkpoint
+            }
+        }
+        _ => Checkpoint {
+            version: CHECKPOINT_VERSION,
+            base,
+            frontier: vec![Vec::new()],
+            interval,
+            config: config.clone(),
+            tree: Node::new(),
+        },
+    };
+    scan_checkpointed(checkpoint, checkpoint_path, cancel)
+}
+
+/// Helper function that goes on with the scan saved at `checkpoint_path` by
+/// `populate_resumable()`, with the directory, configuration and interval it was started with,
+/// and returns the Node tree once it is done.
+///
+/// # Errors
+/// - Any error returned when reading `checkpoint_path`, such as `ErrorKind::NotFound` when there
+///   is no checkpoint to resume.
+/// - `ErrorKind::InvalidData` if `checkpoint_path` holds no checkpoint of the current
+///   `CHECKPOINT_VERSION`. See `CHECKPOINT_VERSION`.
+/// - An error wrapping a `ConfigError` when the directory of the checkpoint is no longer a usable
+///   directory.
+/// - Any error returned by the scan, after which the checkpoint is kept, as described in
+///   `populate_resumable()`.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// # use std::io::ErrorKind;
+/// # use std::sync::atomic::AtomicBool;
+/// let dir: PathBuf = env::temp_dir().join("vfh-doc-resume");
+/// # let _ = remove_dir_all(&dir);
+/// create_dir_all(&dir).unwrap();
+/// let cancel: AtomicBool = AtomicBool::new(false);
+///
+/// // No checkpoint, or one written by another version
+/// let missing = resume(&dir.join("missing.toml"), &cancel);
+/// assert_eq!(missing.unwrap_err().kind(), ErrorKind::NotFound);
+/// write(dir.join("old.toml"), "version = 0\n").unwrap();
+/// let old = resume(&dir.join("old.toml"), &cancel);
+/// assert_eq!(old.unwrap_err().kind(), ErrorKind::InvalidData);
+/// ```
+pub fn resume(checkpoint_path: &Path, cancel: &AtomicBool) -> Result<Node> {
+    let checkpoint: Checkpoint = Checkpoint::load(checkpoint_path)?;
+    check_base(&checkpoint.base)?;
+    scan_checkpointed(checkpoint, checkpoint_path, cancel)
+}
+
+/// Helper function that reads the directories of the frontier of `checkpoint` until there are
+/// none left, saving it to `path` as described in `populate_resumable()`, and returns the
+/// finished Node tree.
+fn scan_checkpointed(mut checkpoint: Checkpoint, path: &Path, cancel: &AtomicBool) -> Result<Node> {
+    let config: Config = checkpoint.config.clone();
+    let entries: Arc<AtomicUsize> =
+        Arc::new(AtomicUsize::new(iter_depth_first(&checkpoint.tree).count()));
+    let mut saved: Instant = Instant::now();
+
+    while let Some(keys) = checkpoint.frontier.last().cloned() {
+        match read_frontier(&mut checkpoint, &keys, &config, &entries, cancel) {
+            Ok((unread, limit_reached)) => {
+                checkpoint.frontier.pop();
+                match limit_reached {
+                    true => checkpoint.frontier.clear(),
+                    // Read the subdirectories next, first one first, as `populate()` does
+                    false => checkpoint.frontier.extend(
+                        unread
+                            .into_iter()
+                            .rev()
+                            .map(|key| keys.iter().cloned().chain([key]).collect::<Vec<String>>()),
+                    ),
+                }
+            }
+            Err(error) => {
+                checkpoint.save(path)?;
+                return Err(error);
+            }
+        }
+        if saved.elapsed() >= checkpoint.interval && !checkpoint.frontier.is_empty() {
+            checkpoint.save(path)?;
+            saved = Instant::now();
+        }
+    }
+
+    let mut tree: Node = checkpoint.tree;
+    aggregate(&mut tree);
+    if config.prune_empty_dirs {
+        prune_empty_dirs(&mut tree);
+    }
+    match remove_file(to_long_path(path)) {
+        Err(error) if error.kind() != ErrorKind::NotFound => Err(error),
+        _ => Ok(tree),
+    }
+}
+
+/// Helper function that reads the directory of the tree of `checkpoint` reached by following
+/// `keys`, or its base when `keys` is empty, with `read_one_dir()`.
+fn read_frontier(
+    checkpoint: &mut Checkpoint,
+    keys: &[String],
+    config: &Config,
+    entries: &Arc<AtomicUsize>,
+    cancel: &AtomicBool,
+) -> Result<(Vec<String>, bool)> {
+    let base: &Path = &checkpoint.base;
+    if keys.is_empty() {
+        let mut root: Entry = Entry::new(
+            base.to_path_buf(),
+            EntryKind::Directory,
+            None,
+            PathBuf::new(),
+        );
+        root.child = std::mem::take(&mut checkpoint.tree);
+        let read: Result<(Vec<String>, bool)> =
+            read_one_dir(base, keys, &mut root, config, entries, cancel);
+        checkpoint.tree = root.child;
+        return read;
+    }
+
+    let path: Vec<&str> = keys.iter().map(String::as_str).collect();
+    let entry: &mut Entry =
+        get_nested_mut(&mut checkpoint.tree, &path, false).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{} is not in the checkpointed tree", path.join("/")),
+            )
+        })?;
+    read_one_dir(base, keys, entry, config, entries, cancel)
+}
+
+/// Helper function that sets the `size` and `modified` fields of every directory of `node` from
+/// its descendants, the way `populate()` does once a directory is read.
+fn aggregate(node: &mut Node) {
+    for entry in node.values_mut().filter(|entry| entry.is_dir()) {
+        aggregate(&mut entry.child);
+        entry.size = entry.child.values().map(|child| child.size).sum();
+        entry.modified = latest_modified(&entry.child)
+            .or_else(|| {
+                metadata(to_long_path(&entry.src))
+                    .and_then(|m| m.modified())
+                    .ok()
+            })
+            .map(whole_seconds);
+    }
+}
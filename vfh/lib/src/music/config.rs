@@ -1,44 +1,1870 @@
-use serde::{Deserialize, Serialize};
+use super::builder::ConfigBuilder;
+use super::glob::{expand_dirs, is_glob_path};
+use super::platform::{strip_long_path, to_long_path, write_atomic, Platform};
+use super::sort::DEFAULT_ARTICLES;
+use serde::de::DeserializeOwned;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
 use std::env;
-use std::fs::read_to_string;
-use std::io::Result;
-use std::path::PathBuf;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt::{Display, Formatter};
+use std::fs::{metadata, read, read_dir};
+use std::io;
+use std::path::{Component, Path, PathBuf};
 use toml;
 
 /// TOML configuration file for Tauri program.
 ///
 /// # Fields
+/// `version`: `u32` -> Version of the configuration file format. See `ConfigFile` for how older
+///                     formats are upgraded.
 /// `base`: `PathBuf` -> Entry point from which the Node tree will be built.
-#[derive(Deserialize, Serialize)]
+/// `bases`: `Vec<PathBuf>` -> Entry points for libraries split across several directories. When
+///                            set, used instead of `base`. Entry points, including `base`, may be
+///                            glob patterns such as `/mnt/music/*/FLAC`, expanded at scan time.
+///                            See `Config::roots()` and `expand_dirs()`.
+/// `extensions`: `Vec<String>` -> Audio file extensions, without the leading `.`, that are
+///                                inserted into the Node tree in addition to `AUDIO_EXTENSIONS`.
+/// `art_extensions`: `Vec<String>` -> Image file extensions, without the leading `.`, searched for
+///                                    cover art in order of precedence.
+/// `case_insensitive_extensions`: `bool` -> Whether `extensions` and `art_extensions` match
+///                                          regardless of case, so `.MP3` and `.Flac` are picked
+///                                          up. Enabled by default.
+/// `sniff_content`: `bool` -> Whether files without an extension, or with one that is not known
+///                           (see `mime_for_extension()`), are read to detect audio containers by
+///                           their first bytes, so they are inserted with the detected MIME type.
+///                           See `sniff_audio()`. Disabled by default, so scans never read the
+///                           contents of files.
+/// `normalize_keys`: `bool` -> Whether the keys of the Node tree are normalized to Unicode NFC,
+///                            so names written in NFD, as on macOS, get the same keys as the same
+///                            names in NFC. Enabled by default. See `normalize_key()`.
+/// `fold_case`: `bool` -> Whether names that only differ in case are the same name, as on the
+///                       case-insensitive file systems of macOS and Windows. Only the first of
+///                       them in file name order is inserted, keeping its casing, and lookups
+///                       find it in any case. Disabled by default. See `fold_key()`.
+/// `default_art`: `Option<PathBuf>` -> Image used as the cover art of entries for which no art
+///                                     was found. Must exist when the configuration is loaded.
+/// `exclude`: `Vec<String>` -> Glob patterns, relative to `base`, of files and directories left
+///                             out of the Node tree. See `path_matches()` for the syntax.
+///                             Excluded directories are not descended into, and images they
+///                             match are never used as cover art.
+/// `include_hidden`: `bool` -> Whether files and directories whose name starts with a `.` are
+///                            inserted into the Node tree and used as cover art. Hidden
+///                            directories are not descended into otherwise.
+/// `follow_symlinks`: `bool` -> Whether symbolic links are followed while building the Node tree.
+///                             When unset, symbolic links are skipped entirely. When set, links
+///                             whose target does not exist are skipped and listed in
+///                             `ScanStats.broken_symlinks`. Either way, a directory that is one
+///                             of its own ancestors, through a link or a bind mount, is skipped
+///                             to break the cycle.
+/// `canonicalize_base`: `bool` -> Whether the directory a Node tree is built from is canonicalized
+///                               first, so that when it is a symbolic link the `src` of every
+///                               entry starts with the directory it points to. Links below it
+///                               are kept as they are. Enabled by default.
+/// `max_depth`: `Option<usize>` -> Depth below `base` past which directories are neither inserted
+///                                into the Node tree nor descended into, while files are inserted
+///                                at every depth that is read. The entries directly under `base`
+///                                are at depth 0, so 0 only inserts the files directly under
+///                                `base` and 2 builds the Artist, Album and Track levels.
+/// `max_entries`: `Option<usize>` -> Number of directories and audio files past which a scan stops
+///                                  early, returning the entries inserted so far with
+///                                  `ScanStats.limit_reached` set, so that a `base` pointing at a
+///                                  whole disk by mistake does not exhaust memory. Entries count
+///                                  in the order they are visited, and the `size` and `modified`
+///                                  of directories only cover the entries they got.
+/// `prune_empty_dirs`: `bool` -> Whether directories without any audio file among their
+///                              descendants are left out of the Node tree. They are left out
+///                              while scanning, which gives the same tree as calling
+///                              `prune_empty_dirs()` after it. See `prune_empty_dirs()`.
+///                              Directories flagged with `Entry.error` are kept.
+/// `abort_on_error`: `bool` -> Whether building the Node tree stops at the first file system error
+///                            and returns it, leaving the Node tree untouched. Otherwise, files
+///                            that cannot be read are left out, directories that cannot be
+///                            listed are kept empty with `Entry.error` set, and both are
+///                            reported in `ScanStats.errors`.
+/// `read_timeout_secs`: `Option<u64>` -> Seconds after which reading the entries of a single
+///                                       directory is given up, for network mounts that may hang.
+///                                       The directory is then kept without its contents and
+///                                       reported as an error of kind `ErrorKind::TimedOut`,
+///                                       which the scan returns when the directory is its base.
+///                                       The read itself keeps running on a thread of its own
+///                                       until the file system answers. Unset by default, so
+///                                       reads are never timed.
+/// `max_open_dirs`: `usize` -> Maximum number of directories `populate_parallel()` and
+///                             `populate_async()` list at the same time, which bounds the file
+///                             descriptors they hold open. 64 by default, and at least 1.
+/// `skip_empty_files`: `bool` -> Whether audio files whose size is 0 bytes, such as those left by
+///                              an interrupted rip, are left out of the Node tree and listed in
+///                              `ScanStats.empty_files`.
+/// `skip_junk`: `bool` -> Whether the files and directories left by operating systems and NAS
+///                       software, listed in `JUNK_FILES`, `JUNK_DIRS` and `junk`, are left out of
+///                       the Node tree. Enabled by default. See `is_junk()`.
+/// `junk`: `Vec<String>` -> Glob patterns, matched against the name of files and directories,
+///                          skipped as junk in addition to `JUNK_FILES` and `JUNK_DIRS`.
+/// `articles`: `Vec<String>` -> Leading articles ignored when sorting entries for presentation,
+///                              in addition to `DEFAULT_ARTICLES`. See `display_sort_key()`.
+/// `libraries`: `BTreeMap<String, LibraryConfig>` -> Named libraries, each built into its own
+///                                                  Node tree by `populate_libraries()`. When
+///                                                  set, used instead of `base` and `bases`.
+/// `rescan_interval_secs`: `Option<u64>` -> Seconds between background rescans of the library.
+///                                          See `spawn_rescanner()`.
+///
+/// Every field is optional and falls back to the value in `Config::default()`, so configuration
+/// files written for older versions keep working as fields are added. Unknown fields are rejected
+/// so that typos do not silently do nothing.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let config: Config = toml::from_str("base = '/mnt/music'").unwrap();
+/// assert_eq!(config.base, PathBuf::from("/mnt/music"));
+/// assert_eq!(config.extensions, Config::default().extensions);
+/// ```
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
+    pub version: u32,
     pub base: PathBuf,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bases: Vec<PathBuf>,
+    pub extensions: Vec<String>,
+    pub art_extensions: Vec<String>,
+    pub case_insensitive_extensions: bool,
+    pub sniff_content: bool,
+    pub normalize_keys: bool,
+    pub fold_case: bool,
+    pub default_art: Option<PathBuf>,
+    pub exclude: Vec<String>,
+    pub include_hidden: bool,
+    pub follow_symlinks: bool,
+    pub canonicalize_base: bool,
+    pub max_depth: Option<usize>,
+    pub max_entries: Option<usize>,
+    pub prune_empty_dirs: bool,
+    pub abort_on_error: bool,
+    pub read_timeout_secs: Option<u64>,
+    pub max_open_dirs: usize,
+    pub skip_empty_files: bool,
+    pub skip_junk: bool,
+    pub junk: Vec<String>,
+    pub articles: Vec<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub libraries: BTreeMap<String, LibraryConfig>,
+    pub rescan_interval_secs: Option<u64>,
+    #[serde(skip)]
+    source: PathBuf,
+    #[serde(skip)]
+    raw_paths: RawPaths,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            version: CONFIG_VERSION,
+            base: default_base(),
+            bases: Vec::new(),
+            extensions: Vec::new(),
+            art_extensions: default_art_extensions(),
+            case_insensitive_extensions: true,
+            sniff_content: false,
+            normalize_keys: true,
+            fold_case: false,
+            default_art: None,
+            exclude: Vec::new(),
+            include_hidden: false,
+            follow_symlinks: false,
+            canonicalize_base: true,
+            max_depth: None,
+            max_entries: None,
+            prune_empty_dirs: false,
+            abort_on_error: false,
+            read_timeout_secs: None,
+            max_open_dirs: 64,
+            skip_empty_files: false,
+            skip_junk: true,
+            junk: Vec::new(),
+            articles: Vec::new(),
+            libraries: BTreeMap::new(),
+            rescan_interval_secs: None,
+            source: PathBuf::new(),
+            raw_paths: RawPaths::default(),
+        }
+    }
+}
+
+/// Path-valued fields of a `Config` as written in its configuration file, each paired with the
+/// path `resolve_path()` resolved it to, so that saving the configuration writes back the `~`,
+/// `$VAR` and relative paths rather than the machine-specific paths they resolve to.
+///
+/// Configurations compare equal whatever their `RawPaths`, which only record how their paths were
+/// written.
+#[derive(Clone, Debug, Default)]
+struct RawPaths(Vec<(PathBuf, PathBuf)>);
+
+impl PartialEq for RawPaths {
+    fn eq(&self, _: &RawPaths) -> bool {
+        true
+    }
+}
+
+impl RawPaths {
+    /// Helper function that resolves `path` with `resolve_path()`, recording how it was written.
+    fn resolve(&mut self, path: &Path, relative_to: &Path) -> Result<PathBuf, ConfigError> {
+        let resolved: PathBuf = resolve_path(path, relative_to)?;
+        self.0.push((resolved.clone(), path.to_path_buf()));
+        Ok(resolved)
+    }
+
+    /// Helper function that returns `path` as it was written in the configuration file when it is
+    /// a resolved path, or `path` itself when it was set some other way.
+    fn unresolve(&self, path: &Path) -> PathBuf {
+        self.0
+            .iter()
+            .find(|(resolved, _)| resolved == path)
+            .map_or_else(|| path.to_path_buf(), |(_, raw)| raw.clone())
+    }
+}
+
+/// Comments written above each field by `Config::to_commented_toml()`.
+const FIELD_COMMENTS: &[(&str, &str)] = &[
+    (
+        "version",
+        "Version of the configuration file format, upgraded automatically by mpb.",
+    ),
+    ("base", "Entry point from which the library tree is built."),
+    (
+        "bases",
+        "Entry points merged into a single library tree, used instead of base when set.",
+    ),
+    (
+        "extensions",
+        "Audio file extensions inserted into the library tree, in addition to the built-in ones.",
+    ),
+    (
+        "art_extensions",
+        "Image file extensions searched for cover art, in order of precedence.",
+    ),
+    (
+        "case_insensitive_extensions",
+        "Whether extensions and art_extensions match regardless of case.",
+    ),
+    (
+        "sniff_content",
+        "Whether files without a known extension are read to detect audio by their contents.",
+    ),
+    (
+        "normalize_keys",
+        "Whether names are normalized, so accented names match whichever platform wrote them.",
+    ),
+    (
+        "fold_case",
+        "Whether names that only differ in case are the same name, as on macOS and Windows.",
+    ),
+    (
+        "default_art",
+        "Image used as cover art when no art is found for an entry.",
+    ),
+    (
+        "exclude",
+        "Glob patterns, relative to base, of files and directories left out of the library tree.",
+    ),
+    (
+        "include_hidden",
+        "Whether files and directories whose name starts with a '.' are included.",
+    ),
+    (
+        "follow_symlinks",
+        "Whether symbolic links are followed. When false, they are skipped entirely.",
+    ),
+    (
+        "canonicalize_base",
+        "Whether a base that is a symbolic link is resolved, so every path starts the same way.",
+    ),
+    (
+        "max_depth",
+        "Depth below base past which directories are left out of the library tree.\n0 only \
+         includes the files directly under base.",
+    ),
+    (
+        "max_entries",
+        "Number of directories and audio files past which a scan stops early.",
+    ),
+    (
+        "prune_empty_dirs",
+        "Whether directories without any audio file below them are left out of the library tree.",
+    ),
+    (
+        "abort_on_error",
+        "Whether building the library tree stops at the first unreadable file or directory.",
+    ),
+    (
+        "read_timeout_secs",
+        "Seconds after which a directory that takes too long to read, such as on a hanging\n\
+         network mount, is kept without its contents. Unset never gives up.",
+    ),
+    (
+        "max_open_dirs",
+        "Maximum number of directories listed at the same time by parallel scans.",
+    ),
+    (
+        "skip_empty_files",
+        "Whether audio files of 0 bytes, such as those of an interrupted rip, are left out.",
+    ),
+    (
+        "skip_junk",
+        "Whether files and directories left by operating systems, such as .DS_Store and @eaDir,\n\
+         are left out.",
+    ),
+    (
+        "junk",
+        "Name patterns of files and directories left out as junk, in addition to the built-in \
+         ones.",
+    ),
+    (
+        "articles",
+        "Leading articles ignored when sorting, in addition to The, A and An.",
+    ),
+    (
+        "libraries",
+        "Named libraries, each built into its own tree, used instead of base and bases when set.\n\
+         Either a path, or a table with a base and optional extensions.",
+    ),
+    (
+        "rescan_interval_secs",
+        "Seconds between background rescans of the library. Unset disables rescanning.",
+    ),
+];
+
+/// Examples written, commented out, by `Config::to_commented_toml()` for the fields that are left
+/// out of the configuration file while they are unset or empty, so the file still documents them.
+const FIELD_EXAMPLES: &[(&str, &str)] = &[
+    ("bases", "bases = [\"~/Music\", \"/mnt/music\"]"),
+    ("default_art", "default_art = \"~/Music/cover.png\""),
+    ("max_depth", "max_depth = 2"),
+    ("max_entries", "max_entries = 100000"),
+    ("read_timeout_secs", "read_timeout_secs = 30"),
+    (
+        "libraries",
+        "[libraries]\naudiobooks = { base = \"~/Audiobooks\", extensions = [\"m4b\"] }",
+    ),
+    ("rescan_interval_secs", "rescan_interval_secs = 3600"),
+];
+
+/// Version of the configuration file format written by this build of the program.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Configuration file as found on disk, keyed on its `version` field.
+///
+/// Older formats are upgraded in memory by `ConfigFile::migrate()`, one version at a time, so
+/// renaming or restructuring fields never makes existing configuration files fail to parse. Call
+/// `Config::save()` after loading to rewrite the file in the current format.
+///
+/// # Variants
+/// `V0`: `Config` -> Versionless configuration file, written before `version` was introduced.
+///                   Apart from the missing `version`, the layout is the same as `V1`.
+/// `V1`: `Config` -> Current configuration file format.
+/// `Newer`: `u32` -> Configuration file written by a newer version of the program, which this
+///                   build does not know how to read.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let file: ConfigFile = ConfigFile::from_toml("base = '/mnt/music'").unwrap();
+/// assert!(matches!(file, ConfigFile::V0(_)));
+/// let config: Config = file.migrate(Path::new("config.toml")).unwrap();
+/// assert_eq!(config.version, CONFIG_VERSION);
+/// assert_eq!(config.base, PathBuf::from("/mnt/music"));
+///
+/// let file: ConfigFile = ConfigFile::from_toml("version = 1\nbase = '/mnt/music'").unwrap();
+/// assert_eq!(file.migrate(Path::new("config.toml")).unwrap(), config);
+///
+/// // Newer files may contain fields this build does not know about
+/// let file: ConfigFile = ConfigFile::from_toml("version = 7\nshuffle = true").unwrap();
+/// assert_eq!(file, ConfigFile::Newer(7));
+/// assert_eq!(
+///     file.migrate(Path::new("config.toml")),
+///     Err(ConfigError::NewerVersion {
+///         path: PathBuf::from("config.toml"),
+///         version: 7,
+///     })
+/// );
+///
+/// assert!(ConfigFile::from_toml("version = 'one'").is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigFile {
+    V0(Config),
+    V1(Config),
+    Newer(u32),
+}
+
+/// The only field read before the format of a configuration file is known.
+#[derive(Deserialize)]
+struct VersionProbe {
+    version: Option<u32>,
+}
+
+impl ConfigFile {
+    /// Helper function that parses a configuration file, dispatching on its `version` field.
+    pub fn from_toml(contents: &str) -> Result<ConfigFile, toml::de::Error> {
+        let probe: VersionProbe = toml::from_str(contents)?;
+
+        Ok(match probe.version {
+            None | Some(0) => ConfigFile::V0(toml::from_str(contents)?),
+            Some(1) => ConfigFile::V1(toml::from_str(contents)?),
+            Some(version) => ConfigFile::Newer(version),
+        })
+    }
+
+    /// Helper function that upgrades the configuration file to `CONFIG_VERSION`.
+    ///
+    /// `path` is only used to report `ConfigError::NewerVersion` for files this build cannot read.
+    pub fn migrate(self, path: &Path) -> Result<Config, ConfigError> {
+        match self {
+            ConfigFile::V0(config) => Ok(migrate_v0(config)),
+            ConfigFile::V1(config) => Ok(config),
+            ConfigFile::Newer(version) => Err(ConfigError::NewerVersion {
+                path: path.to_path_buf(),
+                version,
+            }),
+        }
+    }
+}
+
+/// Helper function that upgrades a versionless configuration file to version 1.
+fn migrate_v0(config: Config) -> Config {
+    Config {
+        version: 1,
+        ..config
+    }
+}
+
+/// Helper function that returns the default value of `Config.base`.
+fn default_base() -> PathBuf {
+    Platform::current().default_base()
+}
+
+/// Helper function that returns the default value of `Config.art_extensions`.
+fn default_art_extensions() -> Vec<String> {
+    ["webp", "apng", "gif", "avif", "svg", "png", "jpeg", "jpg"]
+        .map(String::from)
+        .to_vec()
 }
 
 impl Config {
     /// Helper function that returns or creates the program configuration file.
     ///
-    /// The program expects the `HOME` environment variable to be set. If it is not, the program will
-    /// assume that the user's home directory is placed one level above the current working directory
-    /// to look for its configuration file.
+    /// The configuration file is resolved in the following order of precedence:
+    /// 1. The file named by the `MPB_CONFIG` environment variable. If it is set but the file does
+    ///    not exist, `ConfigError::ConfigNotFound` is returned instead of falling back.
+    /// 2. The first existing location returned by `Platform::config_candidates()` for the
+    ///    platform the program is running on.
+    /// 3. A built-in default configuration pointing at the user's `Music` directory.
+    ///
+    /// After the configuration is loaded, the `MPB_BASE` environment variable, if set, overrides
+    /// `base`, `bases` and `libraries` regardless of what the configuration file says. A leading
+    /// `~` is expanded to the home directory and the result is canonicalized, returning
+    /// `ConfigError::NotFound` or `ConfigError::NotADirectory` if it does not point at an existing
+    /// directory.
+    ///
+    /// The program expects the `HOME` (or `USERPROFILE` on Windows) environment variable to be set.
+    /// If it is not, the program will assume that the user's home directory is placed one level
+    /// above the current working directory to look for its configuration file.
     ///
     /// # Examples
     /// On Linux: [~/.config/mpb/config.toml](file://~/.config/mpb/config.toml)
+    /// On macOS: `~/Library/Application Support/mpb/config.toml`
+    /// On Windows: `%APPDATA%\mpb\config.toml`
     /// Default: `../.config/mpb/config.toml`
-    pub fn new() -> Result<Config> {
-        let mut home = env::var_os("HOME").unwrap_or("..".into());
-        home.push("/.config/mpb/config.toml");
-        let path: PathBuf = PathBuf::from(&home);
-
-        if path.try_exists()? {
-            let config: Config = toml::from_str(read_to_string(path)?.as_str())
-                .expect("An error occurred during deserialization of the config file.");
-            return Ok(config);
-        } else {
-            home = env::var_os("HOME").unwrap_or("..".into());
-            home.push("/Music/");
-            return Ok(Config {
-                base: PathBuf::from(home),
+    ///
+    /// ```
+    /// # use vfh::music::*;
+    /// match Config::new() {
+    ///     Ok(config) => println!("scanning {}", config.base.display()),
+    ///     Err(error) => eprintln!("{}", error),
+    /// }
+    /// ```
+    pub fn new() -> Result<Config, ConfigError> {
+        Config::locate()?.with_env_overrides()
+    }
+
+    /// Helper function that behaves like `Config::new()`, but writes a commented default
+    /// configuration file to `Config::source_path()` when no configuration file exists yet, so
+    /// the user has a file to edit after the first run.
+    ///
+    /// The environment overrides are applied after the file is written and are never persisted.
+    pub fn new_or_init() -> Result<Config, ConfigError> {
+        let config: Config = Config::locate()?;
+
+        if !config
+            .source
+            .try_exists()
+            .map_err(|e| ConfigError::io(&config.source, e))?
+        {
+            write_atomic(&config.source, config.to_commented_toml()?.as_bytes())
+                .map_err(|e| ConfigError::io(&config.source, e))?;
+        }
+
+        config.with_env_overrides()
+    }
+
+    /// Helper function that applies the environment overrides described in `Config::new()`.
+    pub(super) fn with_env_overrides(mut self) -> Result<Config, ConfigError> {
+        if let Some(base) = env::var_os("MPB_BASE").filter(|base| !base.is_empty()) {
+            self.base = canonical_dir(&expand_tilde(Path::new(&base)))?;
+            self.bases.clear();
+            self.libraries.clear();
+        }
+
+        Ok(self)
+    }
+
+    /// Helper function that loads the configuration file according to the precedence described
+    /// in `Config::new()`, without applying any environment overrides.
+    fn locate() -> Result<Config, ConfigError> {
+        if let Some(explicit) = env::var_os("MPB_CONFIG").filter(|explicit| !explicit.is_empty()) {
+            return Config::from_path(Path::new(&explicit));
+        }
+
+        let platform: Platform = Platform::current();
+        let candidates: Vec<PathBuf> = platform.config_candidates();
+
+        for path in &candidates {
+            if path.try_exists().map_err(|e| ConfigError::io(path, e))? {
+                return Config::from_path(path);
+            }
+        }
+
+        Ok(Config {
+            source: candidates.into_iter().next().unwrap_or_default(),
+            ..Config::default()
+        })
+    }
+
+    /// Helper function that reads and deserializes the configuration file at `path`.
+    ///
+    /// Unlike `Config::new()`, no fallback or environment override is applied: a missing file
+    /// returns `ConfigError::ConfigNotFound` and malformed TOML returns `ConfigError::Parse`.
+    ///
+    /// Path-valued fields are resolved with `resolve_path()` relative to the directory containing
+    /// the configuration file, so `base = "~/Music"` and `base = "./music"` work regardless of
+    /// the working directory the program was launched from.
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// # use std::fs::{create_dir_all, remove_dir_all, write};
+    /// let dir: PathBuf = env::temp_dir().join("vfh-doc-config-from-path");
+    /// # let _ = remove_dir_all(&dir);
+    /// create_dir_all(&dir).unwrap();
+    ///
+    /// write(dir.join("config.toml"), "base = \"/srv/music\"").unwrap();
+    /// let config: Config = Config::from_path(&dir.join("config.toml")).unwrap();
+    /// assert_eq!(config.base, PathBuf::from("/srv/music"));
+    /// assert_eq!(config.source_path(), dir.join("config.toml"));
+    /// ```
+    pub fn from_path(path: &Path) -> Result<Config, ConfigError> {
+        let contents: String = read_text(path)?;
+        let mut config: Config = ConfigFile::from_toml(&contents)
+            .map_err(|e| ConfigError::parse(path, &contents, e))?
+            .migrate(path)?;
+        config.source = path.to_path_buf();
+        config.resolve_paths()?;
+
+        if let Some(art) = config
+            .default_art
+            .as_ref()
+            .filter(|art| !to_long_path(art).is_file())
+        {
+            return Err(ConfigError::ArtNotFound(art.clone()));
+        }
+        Ok(config)
+    }
+
+    /// Helper function that resolves every path-valued field with `resolve_path()`, relative to
+    /// the directory of `Config::source_path()`.
+    fn resolve_paths(&mut self) -> Result<(), ConfigError> {
+        let dir: PathBuf = self
+            .source
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        let raw: &mut RawPaths = &mut self.raw_paths;
+        self.base = raw.resolve(&self.base, &dir)?;
+        for base in &mut self.bases {
+            *base = raw.resolve(base, &dir)?;
+        }
+        if let Some(art) = &self.default_art {
+            self.default_art = Some(raw.resolve(art, &dir)?);
+        }
+        for library in self.libraries.values_mut() {
+            library.base = raw.resolve(&library.base, &dir)?;
+        }
+        Ok(())
+    }
+
+    /// Helper function that returns a copy of the configuration whose path-valued fields are
+    /// written the way they were in the configuration file, for as long as they were not changed
+    /// since it was loaded. See `Config::save_to()`.
+    fn unresolved(&self) -> Config {
+        let raw: &RawPaths = &self.raw_paths;
+        let mut config: Config = self.clone();
+        config.base = raw.unresolve(&self.base);
+        for base in &mut config.bases {
+            *base = raw.unresolve(base);
+        }
+        config.default_art = self.default_art.as_deref().map(|art| raw.unresolve(art));
+        for library in config.libraries.values_mut() {
+            library.base = raw.unresolve(&library.base);
+        }
+        config
+    }
+
+    /// Helper function that writes the configuration back to `Config::source_path()`.
+    ///
+    /// See `Config::save_to()` for details.
+    pub fn save(&self) -> Result<(), ConfigError> {
+        self.save_to(&self.source)
+    }
+
+    /// Helper function that serializes the configuration to TOML and writes it to `path`.
+    ///
+    /// Missing parent directories (such as the `mpb` configuration directory) are created. The
+    /// file is first written to a temporary file next to `path` and then renamed over it, so a
+    /// crash mid-write never leaves a truncated configuration file behind.
+    ///
+    /// Paths loaded from a configuration file are written back as they were written there, such
+    /// as `~/Music`, `$MUSIC_ROOT/library` or `./music`, rather than as the absolute paths they
+    /// were resolved to, so the saved file keeps working on other machines. Paths changed since
+    /// the configuration was loaded are written as they are.
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// # use std::fs::{create_dir_all, remove_dir_all, write};
+    /// let dir: PathBuf = env::temp_dir().join("vfh-doc-config-save");
+    /// # let _ = remove_dir_all(&dir);
+    /// create_dir_all(&dir).unwrap();
+    /// write(dir.join("config.toml"), "base = \"/srv/music\"").unwrap();
+    ///
+    /// let mut config: Config = Config::from_path(&dir.join("config.toml")).unwrap();
+    /// config.base = PathBuf::from("/mnt/music");
+    /// config.save().unwrap();
+    /// assert_eq!(Config::from_path(&dir.join("config.toml")).unwrap(), config);
+    ///
+    /// // The versionless file was upgraded when it was loaded and is now saved as version 1
+    /// let contents: String = read_to_string(dir.join("config.toml")).unwrap();
+    /// assert!(contents.contains("version = 1"));
+    ///
+    /// config.save_to(&dir.join("mpb").join("config.toml")).unwrap();
+    /// let copy: Config = Config::from_path(&dir.join("mpb").join("config.toml")).unwrap();
+    /// assert_eq!(copy.base, config.base);
+    /// ```
+    pub fn save_to(&self, path: &Path) -> Result<(), ConfigError> {
+        let contents: String =
+            toml::to_string(&self.unresolved()).map_err(|e| ConfigError::Io {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            })?;
+        write_atomic(path, contents.as_bytes()).map_err(|e| ConfigError::io(path, e))
+    }
+
+    /// Helper function that serializes the configuration to TOML, preceding every field with a
+    /// comment describing it. Paths are written the way `Config::save_to()` writes them.
+    ///
+    /// Fields that are unset or empty, and so not serialized, are written as commented-out
+    /// examples under their comment, so every field is documented in the file.
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// # use std::fs::{create_dir_all, remove_dir_all, write};
+    /// let dir: PathBuf = env::temp_dir().join("vfh-doc-config-commented");
+    /// # let _ = remove_dir_all(&dir);
+    /// create_dir_all(&dir).unwrap();
+    /// write(
+    ///     dir.join("config.toml"),
+    ///     "base = \"/srv/music\"\n[libraries]\nbooks = \"/srv/books\"",
+    /// )
+    /// .unwrap();
+    ///
+    /// let config: Config = Config::from_path(&dir.join("config.toml")).unwrap();
+    /// let toml: String = config.to_commented_toml().unwrap();
+    /// assert!(toml.contains("# Entry point from which the library tree is built.\n"));
+    /// assert!(toml.contains("is built.\nbase = \"/srv/music\"\n"));
+    /// assert_eq!(toml::from_str::<Config>(&toml).unwrap().libraries, config.libraries);
+    /// ```
+    pub fn to_commented_toml(&self) -> Result<String, ConfigError> {
+        let table: toml::Table =
+            toml::Table::try_from(self.unresolved()).map_err(|e| ConfigError::Io {
+                path: self.source.clone(),
+                message: e.to_string(),
+            })?;
+        let mut contents: String = String::from("# Configuration file for mpb.\n");
+
+        // Unset fields are written as examples, in place of their value
+        let mut fields: Vec<(String, Result<toml::Value, &str>)> = FIELD_EXAMPLES
+            .iter()
+            .filter(|(field, _)| !table.contains_key(*field))
+            .map(|(field, example)| (field.to_string(), Err(*example)))
+            .collect();
+        fields.extend(table.into_iter().map(|(key, value)| (key, Ok(value))));
+        fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        // Tables must come after every plain value, or the values would end up inside them
+        let (tables, values): (Vec<_>, Vec<_>) =
+            fields.into_iter().partition(|(_, value)| match value {
+                Ok(value) => value.is_table(),
+                Err(example) => example.starts_with('['),
             });
+        for (key, value) in values.into_iter().chain(tables) {
+            let comment: &str = FIELD_COMMENTS
+                .iter()
+                .find(|(field, _)| *field == key)
+                .map(|(_, comment)| *comment)
+                .unwrap_or("");
+
+            contents.push('\n');
+            for comment_line in comment.lines() {
+                contents.push_str("# ");
+                contents.push_str(comment_line);
+                contents.push('\n');
+            }
+            match value {
+                Ok(value) => {
+                    let mut line: toml::Table = toml::Table::new();
+                    line.insert(key, value);
+                    contents.push_str(&line.to_string());
+                }
+                Err(example) => {
+                    for example_line in example.lines() {
+                        contents.push_str("# ");
+                        contents.push_str(example_line);
+                        contents.push('\n');
+                    }
+                }
+            }
+        }
+
+        Ok(contents)
+    }
+
+    /// Helper function that returns the leading articles ignored when sorting: `DEFAULT_ARTICLES`
+    /// followed by `articles`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// let config: Config = toml::from_str("articles = ['Les', 'Die']").unwrap();
+    /// assert_eq!(config.sort_articles(), ["The", "A", "An", "Les", "Die"]);
+    /// ```
+    pub fn sort_articles(&self) -> Vec<&str> {
+        DEFAULT_ARTICLES
+            .iter()
+            .copied()
+            .chain(self.articles.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Helper function that returns every entry point of the library: `bases` when it is set, or
+    /// `base` otherwise. Duplicates are skipped.
+    ///
+    /// Glob patterns (see `is_glob_path()`) are returned as they are, and expanded by
+    /// `populate_all()` when the library is scanned, so directories created in the meantime are
+    /// picked up.
+    ///
+    /// Both of the following configuration files are accepted:
+    /// ```toml
+    /// base = "/mnt/ssd/music"
+    /// ```
+    /// ```toml
+    /// bases = ["/mnt/ssd/music", "/mnt/external/music"]
+    /// ```
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// let config: Config = toml::from_str("base = '/mnt/ssd/music'").unwrap();
+    /// assert_eq!(config.roots(), [PathBuf::from("/mnt/ssd/music")]);
+    ///
+    /// let config: Config =
+    ///     toml::from_str("bases = ['/mnt/ssd/music', '/mnt/external/music']").unwrap();
+    /// assert_eq!(
+    ///     config.roots(),
+    ///     [PathBuf::from("/mnt/ssd/music"), PathBuf::from("/mnt/external/music")]
+    /// );
+    /// ```
+    pub fn roots(&self) -> Vec<PathBuf> {
+        if self.bases.is_empty() {
+            return vec![self.base.clone()];
+        }
+
+        let mut roots: Vec<PathBuf> = Vec::new();
+        for root in &self.bases {
+            if !roots.contains(root) {
+                roots.push(root.clone());
+            }
+        }
+        roots
+    }
+
+    /// Helper function that checks that every entry point returned by `Config::roots()`, or the
+    /// base of every library when `Config.libraries` is set, is an absolute path to an existing,
+    /// readable directory. Glob patterns in `base` and `bases` must be absolute and match at least
+    /// one directory, and every directory they match is checked. The bases of libraries are never
+    /// expanded.
+    ///
+    /// `populate()` reports an unusable base with the same `ConfigError`, but only for the
+    /// directory it scans and once the scan starts, so this is meant to be called before scanning
+    /// to check every entry point at once, and that they are absolute.
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// # use std::fs::{create_dir_all, remove_dir_all, write};
+    /// let dir: PathBuf = env::temp_dir().join("vfh-doc-config-validate");
+    /// # let _ = remove_dir_all(&dir);
+    /// create_dir_all(dir.join("Music")).unwrap();
+    /// write(dir.join("song.mp3"), "").unwrap();
+    ///
+    /// let mut config: Config = Config::default();
+    /// config.base = dir.join("Music");
+    /// assert_eq!(config.validate(), Ok(()));
+    ///
+    /// config.base = dir.join("Missing");
+    /// assert_eq!(config.validate(), Err(ConfigError::NotFound(dir.join("Missing"))));
+    ///
+    /// config.base = dir.join("song.mp3");
+    /// assert_eq!(config.validate(), Err(ConfigError::NotADirectory(dir.join("song.mp3"))));
+    ///
+    /// config.base = PathBuf::from("Music");
+    /// let error: ConfigError = config.validate().unwrap_err();
+    /// assert_eq!(error, ConfigError::NotAbsoluteAfterExpansion(PathBuf::from("Music")));
+    /// assert_eq!(
+    ///     toml::to_string(&error).unwrap(),
+    ///     "kind = \"not_absolute_after_expansion\"\npath = \"Music\"\n\
+    ///      message = \"base Music is not an absolute path\"\n"
+    /// );
+    ///
+    /// config.base = dir.join("Mus*");
+    /// assert_eq!(config.validate(), Ok(()));
+    ///
+    /// config.base = dir.join("Missing*");
+    /// assert_eq!(config.validate(), Err(ConfigError::NoMatch(dir.join("Missing*"))));
+    /// ```
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let roots: Vec<PathBuf> = match self.libraries.is_empty() {
+            true => self.roots(),
+            false => self
+                .libraries
+                .values()
+                .map(|library| library.base.clone())
+                .collect(),
+        };
+
+        let mut expanded: Vec<PathBuf> = Vec::new();
+        for root in roots {
+            if !root.is_absolute() {
+                return Err(ConfigError::NotAbsoluteAfterExpansion(root));
+            }
+            match self.libraries.is_empty() && is_glob_path(&root) {
+                true => {
+                    let dirs: Vec<PathBuf> = expand_dirs(&root);
+                    if dirs.is_empty() {
+                        return Err(ConfigError::NoMatch(root));
+                    }
+                    expanded.extend(dirs);
+                }
+                false => expanded.push(root),
+            }
+        }
+
+        for root in expanded {
+            let is_dir: bool = metadata(to_long_path(&root))
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::NotFound => ConfigError::NotFound(root.clone()),
+                    io::ErrorKind::PermissionDenied => ConfigError::PermissionDenied(root.clone()),
+                    _ => ConfigError::io(&root, e),
+                })?
+                .is_dir();
+            if !is_dir {
+                return Err(ConfigError::NotADirectory(root));
+            }
+
+            read_dir(to_long_path(&root)).map_err(|e| match e.kind() {
+                io::ErrorKind::PermissionDenied => ConfigError::PermissionDenied(root.clone()),
+                _ => ConfigError::io(&root, e),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Helper function that returns a `ConfigBuilder` for a library rooted at `base`.
+    pub fn builder(base: impl Into<PathBuf>) -> ConfigBuilder {
+        ConfigBuilder::new(base)
+    }
+
+    /// Helper function that applies every value set in `overrides` over the configuration.
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// let mut config: Config =
+    ///     toml::from_str("bases = ['/mnt/a', '/mnt/b']\ninclude_hidden = true").unwrap();
+    /// config.merge_overrides(ConfigOverrides {
+    ///     base: Some(PathBuf::from("/tmp/music")),
+    ///     max_depth: Some(1),
+    ///     ..ConfigOverrides::default()
+    /// });
+    ///
+    /// assert_eq!(config.roots(), [PathBuf::from("/tmp/music")]);
+    /// assert_eq!(config.max_depth, Some(1));
+    /// assert!(config.include_hidden);
+    /// ```
+    pub fn merge_overrides(&mut self, overrides: ConfigOverrides) {
+        if let Some(base) = overrides.base {
+            self.base = base;
+            self.bases.clear();
+            self.libraries.clear();
+        }
+        if let Some(extensions) = overrides.extensions {
+            self.extensions = extensions;
+        }
+        if let Some(exclude) = overrides.exclude {
+            self.exclude = exclude;
+        }
+        if let Some(include_hidden) = overrides.include_hidden {
+            self.include_hidden = include_hidden;
+        }
+        if let Some(follow_symlinks) = overrides.follow_symlinks {
+            self.follow_symlinks = follow_symlinks;
+        }
+        if let Some(max_depth) = overrides.max_depth {
+            self.max_depth = Some(max_depth);
+        }
+    }
+
+    /// Returns the path of the configuration file this `Config` was loaded from.
+    ///
+    /// If no configuration file was found, this is the location where the program expects the
+    /// file to live, so the frontend can still tell the user where to create it.
+    pub fn source_path(&self) -> &Path {
+        &self.source
+    }
+
+    /// Helper function that returns whether `self` and `other` hold the same settings, wherever
+    /// they were loaded from. Unlike `==`, this ignores `Config::source_path()`, which is not
+    /// serialized, so a `Config` read back from a file saved by the program compares equal to
+    /// the one it was saved from.
+    pub(super) fn same_settings(&self, other: &Config) -> bool {
+        Config {
+            source: PathBuf::new(),
+            ..self.clone()
+        } == Config {
+            source: PathBuf::new(),
+            ..other.clone()
+        }
+    }
+}
+
+/// Helper function that expands a leading `~` in `path` to the user's home directory.
+///
+/// Only `~` on its own or followed by a path separator is expanded; every other path is returned
+/// unchanged.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let home: PathBuf = Platform::current().home_dir();
+/// assert_eq!(expand_tilde(Path::new("~/Music")), home.join("Music"));
+/// assert_eq!(expand_tilde(Path::new("/srv/~music")), PathBuf::from("/srv/~music"));
+/// ```
+pub fn expand_tilde(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => Platform::current().home_dir().join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Name under which `populate_libraries()` returns the anonymous library built from `Config.base`
+/// or `Config.bases` when `Config.libraries` is empty.
+pub const DEFAULT_LIBRARY: &str = "default";
+
+/// Named library declared in the `[libraries]` section of the configuration file.
+///
+/// A library is either written as a bare path or as a table:
+/// ```toml
+/// [libraries]
+/// music = "/mnt/music"
+/// audiobooks = { base = "/mnt/books", extensions = ["m4b", "mp3"] }
+/// ```
+///
+/// # Fields
+/// `base`: `PathBuf` -> Entry point from which the library's Node tree is built, resolved like
+///                      `Config.base`.
+/// `extensions`: `Option<Vec<String>>` -> Audio file extensions replacing `AUDIO_EXTENSIONS` and
+///                                        `Config.extensions` for this library.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let config: Config = toml::from_str(
+///     "[libraries]
+///      music = '/mnt/music'
+///      audiobooks = { base = '/mnt/books', extensions = ['m4b'] }",
+/// )
+/// .unwrap();
+/// assert_eq!(config.libraries["music"].base, PathBuf::from("/mnt/music"));
+/// assert_eq!(config.libraries["music"].extensions, None);
+/// assert_eq!(config.libraries["audiobooks"].extensions, Some(vec![String::from("m4b")]));
+///
+/// // Round trip
+/// let saved: String = toml::to_string(&config).unwrap();
+/// assert_eq!(toml::from_str::<Config>(&saved).unwrap(), config);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "LibrarySpec")]
+pub struct LibraryConfig {
+    pub base: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<Vec<String>>,
+}
+
+/// Either way of writing a `LibraryConfig` in the configuration file.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LibrarySpec {
+    Base(PathBuf),
+    Table(LibraryTable),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LibraryTable {
+    base: PathBuf,
+    #[serde(default)]
+    extensions: Option<Vec<String>>,
+}
+
+impl From<LibrarySpec> for LibraryConfig {
+    fn from(spec: LibrarySpec) -> LibraryConfig {
+        match spec {
+            LibrarySpec::Base(base) => LibraryConfig {
+                base,
+                extensions: None,
+            },
+            LibrarySpec::Table(table) => LibraryConfig {
+                base: table.base,
+                extensions: table.extensions,
+            },
         }
     }
 }
+
+/// Values supplied by a frontend, such as command-line flags, that take precedence over the
+/// configuration file. Applied with `Config::merge_overrides()`.
+///
+/// # Fields
+/// `base`: `Option<PathBuf>` -> Replaces `Config.base` and clears `Config.bases` and
+///                              `Config.libraries`, so the library is rooted at this path only.
+/// `extensions`: `Option<Vec<String>>` -> Replaces `Config.extensions`.
+/// `exclude`: `Option<Vec<String>>` -> Replaces `Config.exclude`.
+/// `include_hidden`: `Option<bool>` -> Replaces `Config.include_hidden`.
+/// `follow_symlinks`: `Option<bool>` -> Replaces `Config.follow_symlinks`.
+/// `max_depth`: `Option<usize>` -> Replaces `Config.max_depth`.
+///
+/// Unset fields leave the configuration untouched.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigOverrides {
+    pub base: Option<PathBuf>,
+    pub extensions: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    pub include_hidden: Option<bool>,
+    pub follow_symlinks: Option<bool>,
+    pub max_depth: Option<usize>,
+}
+
+/// Name of the per-directory override file read by `populate()`.
+pub const OVERRIDES_FILE_NAME: &str = ".mpb.toml";
+
+/// Name of the per-directory ignore file read by `populate()`. See `parse_ignore_file()`.
+pub const IGNORE_FILE_NAME: &str = ".mpbignore";
+
+/// Per-directory overrides read from a `.mpb.toml` file inside any directory visited by
+/// `populate()`. Overrides apply to the directory's whole subtree and are merged over the global
+/// `Config`, with overrides of nested directories taking precedence over their ancestors.
+///
+/// # Fields
+/// `exclude`: `Vec<String>` -> Glob patterns, relative to the directory containing the file, left
+///                             out of the Node tree in addition to `Config.exclude`.
+/// `art`: `Option<PathBuf>` -> Image used as the cover art of the directory and every entry in
+///                             its subtree, resolved with `resolve_path()` relative to the
+///                             directory containing the file.
+/// `extensions`: `Option<Vec<String>>` -> Audio file extensions replacing `AUDIO_EXTENSIONS` and
+///                                        `Config.extensions` within the subtree.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let album: PathBuf = env::temp_dir().join("vfh-doc-dir-overrides");
+/// # let _ = remove_dir_all(&album);
+/// create_dir_all(&album).unwrap();
+/// write(
+///     album.join(OVERRIDES_FILE_NAME),
+///     "exclude = ['Bonus/']\nart = 'scans/front.png'\nextensions = ['flac']",
+/// )
+/// .unwrap();
+///
+/// let overrides: DirOverrides =
+///     DirOverrides::from_path(&album.join(OVERRIDES_FILE_NAME)).unwrap();
+/// assert_eq!(overrides.exclude, ["Bonus/"]);
+/// assert_eq!(overrides.art, Some(album.join("scans").join("front.png")));
+/// assert_eq!(overrides.extensions, Some(vec![String::from("flac")]));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DirOverrides {
+    pub exclude: Vec<String>,
+    pub art: Option<PathBuf>,
+    pub extensions: Option<Vec<String>>,
+}
+
+impl DirOverrides {
+    /// Helper function that reads and deserializes the override file at `path`.
+    pub fn from_path(path: &Path) -> Result<DirOverrides, ConfigError> {
+        let mut overrides: DirOverrides = read_toml(path)?;
+        if let Some(art) = &overrides.art {
+            let dir: &Path = path.parent().unwrap_or(Path::new(""));
+            overrides.art = Some(resolve_path(art, dir)?);
+        }
+        Ok(overrides)
+    }
+}
+
+/// Helper function that reads the TOML file at `path` and deserializes it into `T`.
+fn read_toml<T: DeserializeOwned>(path: &Path) -> Result<T, ConfigError> {
+    let contents: String = read_text(path)?;
+    toml::from_str(contents.as_str()).map_err(|e| ConfigError::parse(path, &contents, e))
+}
+
+/// Helper function that reads the file at `path` as UTF-8 text.
+fn read_text(path: &Path) -> Result<String, ConfigError> {
+    let bytes: Vec<u8> = read(to_long_path(path)).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => ConfigError::ConfigNotFound(path.to_path_buf()),
+        _ => ConfigError::io(path, e),
+    })?;
+    String::from_utf8(bytes).map_err(|e| ConfigError::Parse {
+        path: path.to_path_buf(),
+        message: format!("invalid UTF-8 at byte {}", e.utf8_error().valid_up_to()),
+    })
+}
+
+/// Helper function that resolves a path read from the configuration file.
+///
+/// - `$VAR` and `${VAR}` references are expanded with `expand_vars()`.
+/// - A leading `~` is expanded to the user's home directory with `expand_tilde()`.
+/// - `~user` paths are not supported and return `ConfigError::UnsupportedTilde`.
+/// - Relative paths are resolved against `relative_to`, dropping any `.` components.
+/// - Absolute and empty paths are returned unchanged.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let dir: &Path = Path::new("/home/user/.config/mpb");
+/// assert_eq!(resolve_path(Path::new("./music"), dir), Ok(dir.join("music")));
+/// assert_eq!(resolve_path(Path::new("/mnt/music"), dir), Ok(PathBuf::from("/mnt/music")));
+/// ```
+pub fn resolve_path(path: &Path, relative_to: &Path) -> Result<PathBuf, ConfigError> {
+    let expanded: PathBuf = expand_vars(path)?;
+    let path: &Path = expanded.as_path();
+
+    if path.as_os_str().as_encoded_bytes().starts_with(b"~") {
+        return match path.strip_prefix("~") {
+            Ok(_) => Ok(expand_tilde(path)),
+            Err(_) => Err(ConfigError::UnsupportedTilde(path.to_path_buf())),
+        };
+    }
+
+    if path.as_os_str().is_empty() || path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+
+    Ok(relative_to
+        .components()
+        .chain(path.components())
+        .filter(|component| *component != Component::CurDir)
+        .collect())
+}
+
+/// Helper function that expands `$VAR` and `${VAR}` environment variable references in `path`.
+///
+/// Variable names are made of ASCII letters, digits and `_`, and cannot start with a digit. A `$`
+/// that does not start a reference is kept as-is. Referencing a variable that is not set returns
+/// `ConfigError::UndefinedVariable` instead of passing the reference through. Paths that are not
+/// valid UTF-8 are returned unchanged.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// assert_eq!(expand_vars(Path::new("/mnt/$5 albums")), Ok(PathBuf::from("/mnt/$5 albums")));
+/// assert_eq!(
+///     expand_vars(Path::new("$VFH_DOC_UNSET/library")).unwrap_err().kind(),
+///     "undefined_variable"
+/// );
+/// ```
+pub fn expand_vars(path: &Path) -> Result<PathBuf, ConfigError> {
+    let Some(text) = path.to_str().filter(|text| text.contains('$')) else {
+        return Ok(path.to_path_buf());
+    };
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut expanded: OsString = OsString::new();
+    let mut rest: &str = text;
+
+    while let Some(start) = rest.find('$') {
+        expanded.push(&rest[..start]);
+        let after: &str = &rest[start + 1..];
+
+        let (name, remainder): (&str, &str) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", after),
+            },
+            None => {
+                let end: usize = after.find(|c: char| !is_name(c)).unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            }
+        };
+
+        if name.is_empty()
+            || name.starts_with(|c: char| c.is_ascii_digit())
+            || !name.chars().all(is_name)
+        {
+            expanded.push("$");
+            rest = after;
+            continue;
+        }
+
+        let value: OsString = env::var_os(name).ok_or_else(|| ConfigError::UndefinedVariable {
+            path: path.to_path_buf(),
+            variable: name.to_string(),
+        })?;
+        expanded.push(value);
+        rest = remainder;
+    }
+    expanded.push(rest);
+
+    Ok(PathBuf::from(expanded))
+}
+
+/// Helper function that canonicalizes `path` and checks that it points at a directory.
+fn canonical_dir(path: &Path) -> Result<PathBuf, ConfigError> {
+    let canonical: PathBuf = to_long_path(path)
+        .canonicalize()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => ConfigError::NotFound(path.to_path_buf()),
+            _ => ConfigError::io(path, e),
+        })?;
+
+    if !canonical.is_dir() {
+        return Err(ConfigError::NotADirectory(path.to_path_buf()));
+    }
+    Ok(strip_long_path(&canonical))
+}
+
+/// Errors that can occur while locating, reading or validating the program configuration file.
+///
+/// `ConfigError` serializes as a `{ kind, path, message }` record, where `kind` is the snake_case
+/// variant name and `message` is the human-readable description, so the frontend can both branch
+/// on the failure and display it.
+///
+/// # Variants
+/// `ConfigNotFound`: `PathBuf` -> A configuration file was requested explicitly through
+///                                `MPB_CONFIG` or `Config::from_path()` but does not exist.
+/// `Io`: `PathBuf`, `String` -> A file or directory exists but could not be read.
+/// `Parse`: `PathBuf`, `String` -> The configuration file is not valid TOML or does not match
+///                                the `Config` schema.
+/// `NotFound`: `PathBuf` -> The configured base directory does not exist.
+/// `NotADirectory`: `PathBuf` -> The configured base exists but is not a directory.
+/// `ArtNotFound`: `PathBuf` -> The configured default art is not an existing file.
+/// `PermissionDenied`: `PathBuf` -> The configured base exists but its contents cannot be listed.
+/// `NotAbsoluteAfterExpansion`: `PathBuf` -> The configured base is still relative after `~` and
+///                                           relative path resolution.
+/// `UnsupportedTilde`: `PathBuf` -> A configured path starts with `~user`, which is not expanded.
+/// `UndefinedVariable`: `PathBuf`, `String` -> A configured path references an environment
+///                                           variable that is not set.
+/// `NewerVersion`: `PathBuf`, `u32` -> The configuration file was written by a newer version of
+///                                    the program. See `ConfigFile`.
+/// `NoMatch`: `PathBuf` -> A configured base is a glob pattern matching no directory. See
+///                         `expand_dirs()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    ConfigNotFound(PathBuf),
+    Io { path: PathBuf, message: String },
+    Parse { path: PathBuf, message: String },
+    NotFound(PathBuf),
+    NotADirectory(PathBuf),
+    ArtNotFound(PathBuf),
+    PermissionDenied(PathBuf),
+    NotAbsoluteAfterExpansion(PathBuf),
+    UnsupportedTilde(PathBuf),
+    UndefinedVariable { path: PathBuf, variable: String },
+    NewerVersion { path: PathBuf, version: u32 },
+    NoMatch(PathBuf),
+}
+
+impl ConfigError {
+    /// Returns the snake_case name of the variant, as used in the serialized representation.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ConfigError::ConfigNotFound(_) => "config_not_found",
+            ConfigError::Io { .. } => "io",
+            ConfigError::Parse { .. } => "parse",
+            ConfigError::NotFound(_) => "not_found",
+            ConfigError::NotADirectory(_) => "not_a_directory",
+            ConfigError::ArtNotFound(_) => "art_not_found",
+            ConfigError::PermissionDenied(_) => "permission_denied",
+            ConfigError::NotAbsoluteAfterExpansion(_) => "not_absolute_after_expansion",
+            ConfigError::UnsupportedTilde(_) => "unsupported_tilde",
+            ConfigError::UndefinedVariable { .. } => "undefined_variable",
+            ConfigError::NewerVersion { .. } => "newer_version",
+            ConfigError::NoMatch(_) => "no_match",
+        }
+    }
+
+    /// Returns the path of the file or directory the error is about.
+    pub fn path(&self) -> &Path {
+        match self {
+            ConfigError::ConfigNotFound(path)
+            | ConfigError::Io { path, .. }
+            | ConfigError::Parse { path, .. }
+            | ConfigError::NotFound(path)
+            | ConfigError::NotADirectory(path)
+            | ConfigError::ArtNotFound(path)
+            | ConfigError::PermissionDenied(path)
+            | ConfigError::NotAbsoluteAfterExpansion(path)
+            | ConfigError::UnsupportedTilde(path)
+            | ConfigError::UndefinedVariable { path, .. }
+            | ConfigError::NewerVersion { path, .. }
+            | ConfigError::NoMatch(path) => path,
+        }
+    }
+
+    /// Helper function that converts a TOML error into `ConfigError::Parse`, prefixing the message
+    /// with the line and column of the offending text in `contents`.
+    fn parse(path: &Path, contents: &str, error: toml::de::Error) -> ConfigError {
+        let message: String = match error.span() {
+            Some(span) => {
+                let before: &str = &contents[..span.start.min(contents.len())];
+                let line: usize = before.matches('\n').count() + 1;
+                let column: usize = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+                format!("line {}, column {}: {}", line, column, error.message())
+            }
+            None => error.message().to_string(),
+        };
+
+        ConfigError::Parse {
+            path: path.to_path_buf(),
+            message,
+        }
+    }
+
+    fn io(path: &Path, error: io::Error) -> ConfigError {
+        ConfigError::Io {
+            path: path.to_path_buf(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::ConfigNotFound(path) => {
+                write!(f, "config file {} does not exist", path.display())
+            }
+            ConfigError::Io { path, message } => {
+                write!(f, "could not read {}: {}", path.display(), message)
+            }
+            ConfigError::Parse { path, message } => {
+                write!(
+                    f,
+                    "could not parse config file {}: {}",
+                    path.display(),
+                    message
+                )
+            }
+            ConfigError::NotFound(path) => {
+                write!(f, "base directory {} does not exist", path.display())
+            }
+            ConfigError::NotADirectory(path) => {
+                write!(f, "base {} is not a directory", path.display())
+            }
+            ConfigError::ArtNotFound(path) => {
+                write!(f, "default art {} is not an existing file", path.display())
+            }
+            ConfigError::PermissionDenied(path) => {
+                write!(f, "permission denied reading base {}", path.display())
+            }
+            ConfigError::NotAbsoluteAfterExpansion(path) => {
+                write!(f, "base {} is not an absolute path", path.display())
+            }
+            ConfigError::UnsupportedTilde(path) => {
+                write!(
+                    f,
+                    "{} refers to another user's home directory, which is not supported",
+                    path.display()
+                )
+            }
+            ConfigError::UndefinedVariable { path, variable } => {
+                write!(
+                    f,
+                    "{} refers to the environment variable {}, which is not set",
+                    path.display(),
+                    variable
+                )
+            }
+            ConfigError::NewerVersion { path, version } => {
+                write!(
+                    f,
+                    "config file {} was written by a newer mpb (version {}, this build supports up \
+                     to {})",
+                    path.display(),
+                    version,
+                    CONFIG_VERSION
+                )
+            }
+            ConfigError::NoMatch(path) => {
+                write!(f, "no directory matches the base {}", path.display())
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl Serialize for ConfigError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ConfigError", 3)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("path", self.path())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{fixture, lock_env, Env};
+    use super::*;
+    use std::fs::{create_dir_all, read_to_string, remove_file, write};
+
+    /// Helper function that makes `Config::new()` look for its configuration file in `dir` alone,
+    /// on every platform.
+    fn isolate(env: &mut Env, dir: &Path) {
+        for var in ["HOME", "USERPROFILE", "APPDATA", "XDG_CONFIG_HOME"] {
+            env.set(var, dir);
+        }
+        env.remove("MPB_CONFIG");
+        env.remove("MPB_BASE");
+    }
+
+    #[test]
+    fn partial_configs_parse() {
+        let _env: Env = lock_env();
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config, Config::default());
+
+        let config: Config = toml::from_str(
+            "base = '/mnt/music'
+             bases = ['/mnt/a', '/mnt/b']
+             extensions = ['flac']
+             art_extensions = ['jpg']
+             exclude = ['Recordings/']
+             include_hidden = true
+             follow_symlinks = true
+             max_depth = 2",
+        )
+        .unwrap();
+        assert_eq!(config.bases, ["/mnt/a", "/mnt/b"].map(PathBuf::from));
+        assert_eq!(config.extensions, ["flac"]);
+        assert_eq!(config.art_extensions, ["jpg"]);
+        assert_eq!(config.exclude, ["Recordings/"]);
+        assert!(config.include_hidden && config.follow_symlinks);
+        assert_eq!(config.max_depth, Some(2));
+        assert_eq!(config.junk, Config::default().junk);
+
+        let error: toml::de::Error = toml::from_str::<Config>("bse = '/mnt/music'").unwrap_err();
+        assert!(error.message().starts_with("unknown field `bse`"));
+    }
+
+    #[test]
+    fn new_finds_the_config_file() {
+        let mut env: Env = lock_env();
+        let xdg: PathBuf = fixture("config-new");
+        isolate(&mut env, &xdg);
+        let source: PathBuf = xdg.join("mpb").join("config.toml");
+
+        // Without a configuration file
+        let config: Config = Config::new().unwrap();
+        assert_eq!(config.base, xdg.join("Music"));
+        assert_eq!(
+            config.source_path(),
+            Platform::current().config_candidates()[0]
+        );
+        assert!(!source.exists());
+
+        create_dir_all(xdg.join("mpb")).unwrap();
+        write(&source, "base = \"/srv/music\"").unwrap();
+        let config: Config = Config::new().unwrap();
+        assert_eq!(config.base, PathBuf::from("/srv/music"));
+        assert_eq!(config.source_path(), source);
+    }
+
+    #[test]
+    fn mpb_config_names_the_config_file() {
+        let mut env: Env = lock_env();
+        let dir: PathBuf = fixture("config-mpb-config");
+        isolate(&mut env, &dir);
+        write(dir.join("music.toml"), "base = \"/srv/music\"").unwrap();
+
+        env.set("MPB_CONFIG", dir.join("music.toml"));
+        let config: Config = Config::new().unwrap();
+        assert_eq!(config.base, PathBuf::from("/srv/music"));
+        assert_eq!(config.source_path(), dir.join("music.toml"));
+
+        // A missing file is an error rather than a fallback
+        env.set("MPB_CONFIG", dir.join("missing.toml"));
+        assert_eq!(
+            Config::new().err(),
+            Some(ConfigError::ConfigNotFound(dir.join("missing.toml")))
+        );
+
+        env.set("MPB_CONFIG", "");
+        assert_eq!(Config::new().unwrap().base, dir.join("Music"));
+    }
+
+    #[test]
+    fn mpb_base_overrides_every_base() {
+        let mut env: Env = lock_env();
+        let dir: PathBuf = fixture("config-mpb-base");
+        isolate(&mut env, &dir);
+        write(
+            dir.join("music.toml"),
+            "base = '/srv/music'\nbases = ['/mnt/a']\n[libraries]\nbooks = '/srv/books'",
+        )
+        .unwrap();
+        env.set("MPB_CONFIG", dir.join("music.toml"));
+
+        env.set("MPB_BASE", &dir);
+        let config: Config = Config::new().unwrap();
+        assert_eq!(config.base, dir);
+        assert!(config.bases.is_empty());
+        assert!(config.libraries.is_empty());
+
+        env.set("MPB_BASE", dir.join("missing"));
+        assert_eq!(
+            Config::new().err(),
+            Some(ConfigError::NotFound(dir.join("missing")))
+        );
+        env.set("MPB_BASE", dir.join("music.toml"));
+        assert_eq!(
+            Config::new().err(),
+            Some(ConfigError::NotADirectory(dir.join("music.toml")))
+        );
+
+        env.set("MPB_BASE", "");
+        assert_eq!(Config::new().unwrap().base, PathBuf::from("/srv/music"));
+    }
+
+    #[test]
+    fn new_or_init_writes_the_default_config_file() {
+        let mut env: Env = lock_env();
+        let xdg: PathBuf = fixture("config-new-or-init");
+        isolate(&mut env, &xdg);
+
+        env.set("MPB_BASE", &xdg);
+        let config: Config = Config::new_or_init().unwrap();
+        assert_eq!(config.base, xdg);
+        assert!(config.source_path().is_file());
+
+        // The override is not written to the file, and the file is not written again
+        let saved: Config = Config::from_path(config.source_path()).unwrap();
+        assert_eq!(saved.base, xdg.join("Music"));
+        write(config.source_path(), "base = '/srv/music'").unwrap();
+        env.remove("MPB_BASE");
+        assert_eq!(
+            Config::new_or_init().unwrap().base,
+            PathBuf::from("/srv/music")
+        );
+
+        remove_file(config.source_path()).unwrap();
+        assert_eq!(Config::new_or_init().unwrap(), saved);
+    }
+
+    #[test]
+    fn from_path_reports_missing_and_malformed_files() {
+        let dir: PathBuf = fixture("config-from-path");
+        assert_eq!(
+            Config::from_path(&dir.join("missing.toml")).err(),
+            Some(ConfigError::ConfigNotFound(dir.join("missing.toml")))
+        );
+
+        write(dir.join("broken.toml"), "base = ").unwrap();
+        assert!(matches!(
+            Config::from_path(&dir.join("broken.toml")),
+            Err(ConfigError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn default_art_must_exist() {
+        let dir: PathBuf = fixture("config-default-art");
+        write(dir.join("config.toml"), "default_art = 'placeholder.png'").unwrap();
+        assert_eq!(
+            Config::from_path(&dir.join("config.toml")).err(),
+            Some(ConfigError::ArtNotFound(dir.join("placeholder.png")))
+        );
+
+        write(dir.join("placeholder.png"), "").unwrap();
+        let config: Config = Config::from_path(&dir.join("config.toml")).unwrap();
+        assert_eq!(config.default_art, Some(dir.join("placeholder.png")));
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_problem() {
+        let dir: PathBuf = fixture("config-parse-errors");
+
+        write(dir.join("truncated.toml"), "base = \"/srv/mu").unwrap();
+        let error: ConfigError = Config::from_path(&dir.join("truncated.toml")).unwrap_err();
+        assert!(error.to_string().contains("line 1, column"));
+
+        write(dir.join("binary.toml"), b"base = \"/srv/\xff\"").unwrap();
+        let error: ConfigError = Config::from_path(&dir.join("binary.toml")).unwrap_err();
+        assert_eq!(
+            error,
+            ConfigError::Parse {
+                path: dir.join("binary.toml"),
+                message: String::from("invalid UTF-8 at byte 13"),
+            }
+        );
+
+        write(dir.join("newer.toml"), "version = 2").unwrap();
+        let error: ConfigError = Config::from_path(&dir.join("newer.toml")).unwrap_err();
+        assert_eq!(error.kind(), "newer_version");
+        assert!(error.to_string().contains("written by a newer mpb"));
+
+        write(dir.join("wrong-type.toml"), "base = 42").unwrap();
+        let error: ConfigError = Config::from_path(&dir.join("wrong-type.toml")).unwrap_err();
+        assert!(matches!(error, ConfigError::Parse { .. }));
+        assert!(error.to_string().contains("line 1, column 8"));
+
+        write(
+            dir.join("later-line.toml"),
+            "base = '/srv'\nmax_depth = 'two'",
+        )
+        .unwrap();
+        let error: ConfigError = Config::from_path(&dir.join("later-line.toml")).unwrap_err();
+        assert!(error.to_string().contains("line 2, column 13"));
+    }
+
+    #[test]
+    fn tilde_expands_to_the_home_directory() {
+        let mut env: Env = lock_env();
+        env.set("HOME", "/home/user");
+        env.set("USERPROFILE", "/home/user");
+        assert_eq!(
+            expand_tilde(Path::new("~/Music")),
+            PathBuf::from("/home/user/Music")
+        );
+        assert_eq!(expand_tilde(Path::new("~")), PathBuf::from("/home/user"));
+        assert_eq!(
+            expand_tilde(Path::new("~other/Music")),
+            PathBuf::from("~other/Music")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paths_resolve_home_and_variables() {
+        let mut env: Env = lock_env();
+        env.set("HOME", "/home/user");
+        let dir: &Path = Path::new("/home/user/.config/mpb");
+        assert_eq!(
+            resolve_path(Path::new("~/Music"), dir),
+            Ok(PathBuf::from("/home/user/Music"))
+        );
+        assert_eq!(resolve_path(Path::new(""), dir), Ok(PathBuf::new()));
+
+        env.set("MUSIC_ROOT", "/mnt/my music");
+        assert_eq!(
+            resolve_path(Path::new("$MUSIC_ROOT/library"), dir),
+            Ok(PathBuf::from("/mnt/my music/library"))
+        );
+        env.set("MUSIC_ROOT", "~/music");
+        assert_eq!(
+            resolve_path(Path::new("$MUSIC_ROOT/library"), dir),
+            Ok(PathBuf::from("/home/user/music/library"))
+        );
+        env.remove("MUSIC_ROOT");
+        assert_eq!(
+            resolve_path(Path::new("$MUSIC_ROOT/library"), dir)
+                .unwrap_err()
+                .kind(),
+            "undefined_variable"
+        );
+        assert_eq!(
+            resolve_path(Path::new("~other/Music"), dir),
+            Err(ConfigError::UnsupportedTilde(PathBuf::from("~other/Music")))
+        );
+    }
+
+    #[test]
+    fn variables_expand() {
+        let mut env: Env = lock_env();
+        env.set("MUSIC_ROOT", "/mnt/music");
+        env.set("SHARE", "My Shared Drive");
+        env.remove("VFH_TEST_UNSET");
+
+        assert_eq!(
+            expand_vars(Path::new("$MUSIC_ROOT/library")),
+            Ok(PathBuf::from("/mnt/music/library"))
+        );
+        assert_eq!(
+            expand_vars(Path::new("/media/${SHARE}/Music")),
+            Ok(PathBuf::from("/media/My Shared Drive/Music"))
+        );
+        assert_eq!(
+            expand_vars(Path::new("/mnt/${SHARE")),
+            Ok(PathBuf::from("/mnt/${SHARE"))
+        );
+        assert_eq!(
+            expand_vars(Path::new("$VFH_TEST_UNSET/library")),
+            Err(ConfigError::UndefinedVariable {
+                path: PathBuf::from("$VFH_TEST_UNSET/library"),
+                variable: String::from("VFH_TEST_UNSET"),
+            })
+        );
+    }
+
+    #[test]
+    fn commented_toml_documents_every_field() {
+        let config: Config = Config::default();
+        let contents: String = config.to_commented_toml().unwrap();
+        assert_eq!(toml::from_str::<Config>(&contents).unwrap(), config);
+
+        for (field, comment) in FIELD_COMMENTS {
+            let documented: String = comment
+                .lines()
+                .map(|line| format!("# {}\n", line))
+                .collect();
+            let written: bool = [format!("{} = ", field), format!("# {} = ", field)]
+                .iter()
+                .chain([&format!("# [{}]", field)])
+                .any(|line| contents.contains(&format!("{}{}", documented, line)));
+            assert!(written, "{} is not documented:\n{}", field, contents);
+        }
+
+        // Every field that can be written has a comment
+        let (tables, values): (Vec<&str>, Vec<&str>) = FIELD_EXAMPLES
+            .iter()
+            .map(|(_, example)| *example)
+            .partition(|example| example.starts_with('['));
+        let set: Config = toml::from_str(&[values, tables].concat().join("\n")).unwrap();
+        for field in toml::Table::try_from(set).unwrap().keys() {
+            assert!(FIELD_COMMENTS
+                .iter()
+                .any(|(commented, _)| commented == field));
+        }
+    }
+
+    #[test]
+    fn commented_toml_examples_take_effect() {
+        let contents: String = Config::default().to_commented_toml().unwrap();
+        let uncommented: String = contents
+            .lines()
+            .filter_map(|line| match line.strip_prefix("# ") {
+                Some(example) if example.starts_with('[') => Some(example),
+                Some(example) => toml::from_str::<toml::Table>(example)
+                    .is_ok_and(|example| !example.is_empty())
+                    .then_some(example),
+                None => Some(line),
+            })
+            .collect::<Vec<&str>>()
+            .join("\n");
+        let config: Config = toml::from_str(&uncommented).unwrap();
+        assert_eq!(config.max_depth, Some(2));
+        assert_eq!(config.read_timeout_secs, Some(30));
+        assert_eq!(config.rescan_interval_secs, Some(3600));
+        assert_eq!(config.default_art, Some(PathBuf::from("~/Music/cover.png")));
+        assert_eq!(
+            config.libraries["audiobooks"].base,
+            PathBuf::from("~/Audiobooks")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_keeps_unresolved_paths() {
+        let _env: Env = lock_env();
+        let dir: PathBuf = fixture("config-save-unresolved");
+        write(dir.join("cover.png"), "").unwrap();
+        write(
+            dir.join("config.toml"),
+            "version = 1\n\
+             base = '~/Music'\n\
+             bases = ['$HOME/library', './music']\n\
+             default_art = 'cover.png'\n\
+             [libraries]\n\
+             books = '${HOME}/Books'\n",
+        )
+        .unwrap();
+
+        let config: Config = Config::from_path(&dir.join("config.toml")).unwrap();
+        assert_eq!(config.base, expand_tilde(Path::new("~/Music")));
+        assert_eq!(config.bases[1], dir.join("music"));
+        config.save().unwrap();
+
+        let saved: Config =
+            toml::from_str(&read_to_string(dir.join("config.toml")).unwrap()).unwrap();
+        assert_eq!(saved.base, PathBuf::from("~/Music"));
+        assert_eq!(saved.bases, ["$HOME/library", "./music"].map(PathBuf::from));
+        assert_eq!(saved.default_art, Some(PathBuf::from("cover.png")));
+        assert_eq!(
+            saved.libraries["books"].base,
+            PathBuf::from("${HOME}/Books")
+        );
+        assert_eq!(Config::from_path(&dir.join("config.toml")).unwrap(), config);
+
+        let commented: Config = toml::from_str(&config.to_commented_toml().unwrap()).unwrap();
+        assert_eq!(commented.base, PathBuf::from("~/Music"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_writes_changed_paths() {
+        let _env: Env = lock_env();
+        let dir: PathBuf = fixture("config-save-changed");
+        write(
+            dir.join("config.toml"),
+            "base = '~/Music'\nbases = ['./a', './b']",
+        )
+        .unwrap();
+
+        let mut config: Config = Config::from_path(&dir.join("config.toml")).unwrap();
+        config.base = PathBuf::from("/mnt/music");
+        config.bases[1] = dir.join("c");
+        config.save().unwrap();
+
+        let saved: Config =
+            toml::from_str(&read_to_string(dir.join("config.toml")).unwrap()).unwrap();
+        assert_eq!(saved.base, PathBuf::from("/mnt/music"));
+        assert_eq!(saved.bases, [PathBuf::from("./a"), dir.join("c")]);
+        assert_eq!(Config::from_path(&dir.join("config.toml")).unwrap(), config);
+    }
+}
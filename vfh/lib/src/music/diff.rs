@@ -0,0 +1,156 @@
+use super::node::{Entry, EntryKind, Node};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::btree_map;
+use std::iter::Peekable;
+
+/// Change between two Node trees, returned by `diff()`.
+///
+/// Serializes as `{ "change": "added" | "removed" | "modified", "keys": [...] }` so it can be sent
+/// to the frontend as is.
+///
+/// # Variants
+/// `Added`: `Vec<String>` -> Keys of an entry only found in the new tree. Its descendants are
+///                           not reported separately.
+/// `Removed`: `Vec<String>` -> Keys of an entry only found in the old tree. Its descendants are
+///                             not reported separately.
+/// `Modified`: `Vec<String>` -> Keys of an entry found in both trees whose `src`, `art` or, for
+///                              audio files, `size` or `modified` changed. Changes of the sizes
+///                              and times directories aggregate from their descendants are not
+///                              reported, as the descendants themselves are.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "change", content = "keys", rename_all = "lowercase")]
+pub enum TreeChange {
+    Added(Vec<String>),
+    Removed(Vec<String>),
+    Modified(Vec<String>),
+}
+
+/// Helper function that returns the changes turning `old` into `new`, in the sorted order of
+/// their keys, parents before children.
+///
+/// Only subtrees that differ are descended into. An entry whose kind changed is reported as
+/// removed and added again, and a renamed file as the removal of the old key and the addition of
+/// the new one.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, remove_file, rename, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-diff");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Album")).unwrap();
+/// create_dir_all(base.join("Other")).unwrap();
+/// write(base.join("Album").join("Album.jpg"), "").unwrap();
+/// write(base.join("Album").join("intro.mp3"), "").unwrap();
+/// write(base.join("Other").join("song.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut old: Node = Node::new();
+/// populate(&base, &mut old, &config).unwrap();
+/// assert_eq!(diff(&old, &old), []);
+///
+/// // Renamed file
+/// rename(base.join("Other").join("song.mp3"), base.join("Other").join("track.mp3")).unwrap();
+/// let mut new: Node = Node::new();
+/// populate(&base, &mut new, &config).unwrap();
+/// assert_eq!(
+///     diff(&old, &new),
+///     [
+///         TreeChange::Removed(vec![String::from("Other"), String::from("song")]),
+///         TreeChange::Added(vec![String::from("Other"), String::from("track")]),
+///     ]
+/// );
+///
+/// // Swapped art file
+/// remove_file(base.join("Album").join("Album.jpg")).unwrap();
+/// write(base.join("Album").join("Album.png"), "").unwrap();
+/// let mut swapped: Node = Node::new();
+/// populate(&base, &mut swapped, &config).unwrap();
+/// assert_eq!(
+///     diff(&new, &swapped),
+///     [
+///         TreeChange::Modified(vec![String::from("Album")]),
+///         TreeChange::Modified(vec![String::from("Album"), String::from("intro")]),
+///     ]
+/// );
+///
+/// let serialized: String = toml::to_string(&diff(&new, &swapped)[0]).unwrap();
+/// assert_eq!(serialized, "change = \"modified\"\nkeys = [\"Album\"]\n");
+/// ```
+pub fn diff(old: &Node, new: &Node) -> Vec<TreeChange> {
+    let mut changes: Vec<TreeChange> = Vec::new();
+    diff_into(old, new, &mut Vec::new(), &mut changes);
+    changes
+}
+
+/// Helper function that appends the changes between the children `old` and `new` of the entry
+/// reached through `keys` to `changes`.
+fn diff_into<'a>(
+    old: &'a Node,
+    new: &'a Node,
+    keys: &mut Vec<&'a str>,
+    changes: &mut Vec<TreeChange>,
+) {
+    let mut old_entries: Peekable<btree_map::Iter<'a, String, Entry>> = old.iter().peekable();
+    let mut new_entries: Peekable<btree_map::Iter<'a, String, Entry>> = new.iter().peekable();
+    let owned = |keys: &[&str], key: &str| -> Vec<String> {
+        keys.iter()
+            .copied()
+            .chain([key])
+            .map(String::from)
+            .collect()
+    };
+
+    loop {
+        let order: Ordering = match (old_entries.peek(), new_entries.peek()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some((old_key, _)), Some((new_key, _))) => old_key.cmp(new_key),
+        };
+
+        match order {
+            Ordering::Less => {
+                let (key, _) = old_entries.next().unwrap_or_else(|| unreachable!());
+                changes.push(TreeChange::Removed(owned(keys, key)));
+            }
+            Ordering::Greater => {
+                let (key, _) = new_entries.next().unwrap_or_else(|| unreachable!());
+                changes.push(TreeChange::Added(owned(keys, key)));
+            }
+            Ordering::Equal => {
+                let (key, old_entry) = old_entries.next().unwrap_or_else(|| unreachable!());
+                let (_, new_entry) = new_entries.next().unwrap_or_else(|| unreachable!());
+                if old_entry == new_entry {
+                    continue;
+                }
+
+                if old_entry.kind != new_entry.kind {
+                    changes.push(TreeChange::Removed(owned(keys, key)));
+                    changes.push(TreeChange::Added(owned(keys, key)));
+                    continue;
+                }
+                if is_modified(old_entry, new_entry) {
+                    changes.push(TreeChange::Modified(owned(keys, key)));
+                }
+                if old_entry.child != new_entry.child {
+                    keys.push(key);
+                    diff_into(&old_entry.child, &new_entry.child, keys, changes);
+                    keys.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Helper function that returns whether the fields of an entry of the same kind in both trees,
+/// leaving aside its children, changed as described in `TreeChange::Modified`.
+fn is_modified(old: &Entry, new: &Entry) -> bool {
+    let own_metadata_changed: bool = match new.kind {
+        EntryKind::Audio => old.size != new.size || old.modified != new.modified,
+        EntryKind::Directory => false,
+    };
+
+    old.src != new.src || old.art != new.art || old.parent != new.parent || own_metadata_changed
+}
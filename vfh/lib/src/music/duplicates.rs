@@ -0,0 +1,155 @@
+use super::node::Node;
+use super::platform::to_long_path;
+use super::stats::HashProgress;
+use super::traverse::iter_depth_first;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Result};
+use std::path::{Path, PathBuf};
+
+/// Size of the chunks audio files are read in while they are hashed by `find_duplicates()`.
+pub const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Helper function that returns the groups of audio files of `node` whose contents are
+/// identical, such as the tracks of an album ripped twice into different folders.
+///
+/// Files are first grouped by their `Entry.size`, and only the files sharing their size with
+/// another one are read, so a file of a unique size is never opened. Their contents are then
+/// hashed with BLAKE3, reading `HASH_CHUNK_SIZE` bytes at a time, and files are grouped by hash.
+/// Empty files and files that cannot be read are never reported.
+///
+/// Every group holds at least two paths, sorted, and groups are sorted by their first path. The
+/// result serializes as a list of lists of paths.
+///
+/// Reads every file that may have a duplicate, which takes a while on a large library. Use
+/// `find_duplicates_with_progress()` to follow the progress.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-find-duplicates");
+/// # let _ = remove_dir_all(&base);
+/// for album in ["Album", "Album (rip 2)"] {
+///     create_dir_all(base.join(album)).unwrap();
+///     write(base.join(album).join("1.flac"), "first track").unwrap();
+///     write(base.join(album).join("2.flac"), "second track").unwrap();
+/// }
+/// // Same size as the copies of `1.flac`, different contents
+/// write(base.join("Album").join("3.flac"), "third track").unwrap();
+/// write(base.join("Album").join("4.flac"), "").unwrap();
+/// write(base.join("Album (rip 2)").join("4.flac"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// let duplicates: Vec<Vec<PathBuf>> = find_duplicates(&tree);
+/// assert_eq!(
+///     duplicates,
+///     [
+///         [base.join("Album/1.flac"), base.join("Album (rip 2)/1.flac")],
+///         [base.join("Album/2.flac"), base.join("Album (rip 2)/2.flac")],
+///     ]
+/// );
+/// ```
+pub fn find_duplicates(node: &Node) -> Vec<Vec<PathBuf>> {
+    find_duplicates_with_progress(node, |_| {})
+}
+
+/// Helper function that returns the groups of identical audio files of `node` like
+/// `find_duplicates()` does, calling `progress` along the way.
+///
+/// `progress` is called with a `HashProgress` once before the first file is hashed and after
+/// every file, whether it could be read or not, so the last call has `hashed_files` equal to
+/// `total_files`. The files eliminated by their size alone are not counted.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-find-duplicates-with-progress");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(&base).unwrap();
+/// for (name, contents) in [("a.mp3", "same"), ("b.mp3", "same"), ("c.mp3", "other")] {
+///     write(base.join(name), contents).unwrap();
+/// }
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// let mut reports: Vec<HashProgress> = Vec::new();
+/// let duplicates: Vec<Vec<PathBuf>> =
+///     find_duplicates_with_progress(&tree, |progress: HashProgress| reports.push(progress));
+/// assert_eq!(duplicates, [[base.join("a.mp3"), base.join("b.mp3")]]);
+///
+/// // `c.mp3` is the only file of its size, so it is never read
+/// assert_eq!(reports.len(), 3);
+/// assert_eq!(
+///     reports.last(),
+///     Some(&HashProgress { hashed_files: 2, total_files: 2, hashed_bytes: 8, total_bytes: 8 })
+/// );
+/// ```
+pub fn find_duplicates_with_progress(
+    node: &Node,
+    mut progress: impl FnMut(HashProgress),
+) -> Vec<Vec<PathBuf>> {
+    let mut sizes: BTreeMap<u64, Vec<&Path>> = BTreeMap::new();
+    for (_, entry) in iter_depth_first(node) {
+        if entry.is_leaf() && entry.size > 0 {
+            sizes.entry(entry.size).or_default().push(&entry.src);
+        }
+    }
+    sizes.retain(|_, paths| paths.len() > 1);
+
+    let mut state: HashProgress = HashProgress {
+        hashed_files: 0,
+        total_files: sizes.values().map(Vec::len).sum(),
+        hashed_bytes: 0,
+        total_bytes: sizes
+            .iter()
+            .map(|(size, paths)| size * paths.len() as u64)
+            .sum(),
+    };
+    progress(state);
+
+    let mut groups: Vec<Vec<PathBuf>> = Vec::new();
+    for (size, paths) in sizes {
+        let mut hashes: BTreeMap<[u8; 32], Vec<PathBuf>> = BTreeMap::new();
+        for path in paths {
+            if let Ok(hash) = hash_file(path) {
+                hashes.entry(hash).or_default().push(path.to_path_buf());
+            }
+            state.hashed_files += 1;
+            state.hashed_bytes += size;
+            progress(state);
+        }
+        groups.extend(hashes.into_values().filter(|paths| paths.len() > 1));
+    }
+
+    for group in &mut groups {
+        group.sort();
+    }
+    groups.sort();
+    groups
+}
+
+/// Helper function that returns the BLAKE3 hash of the contents of the file at `path`, read
+/// `HASH_CHUNK_SIZE` bytes at a time.
+fn hash_file(path: &Path) -> Result<[u8; 32]> {
+    let mut file: File = File::open(to_long_path(path))?;
+    let mut hasher: blake3::Hasher = blake3::Hasher::new();
+    let mut chunk: Vec<u8> = vec![0; HASH_CHUNK_SIZE];
+    loop {
+        match file.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => {
+                hasher.update(&chunk[..read]);
+            }
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(*hasher.finalize().as_bytes())
+}
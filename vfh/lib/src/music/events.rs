@@ -0,0 +1,383 @@
+use super::config::Config;
+use super::diff::{diff, TreeChange};
+use super::node::{Entry, Node};
+use super::platform::to_long_path;
+use super::traverse::{iter_depth_first, remove_by_path};
+use super::utils::{find_child, insert_dir, insert_path, populate_from, refresh_art, scan_base};
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Component, Path, PathBuf};
+
+/// Change of the file system below the base of a Node tree, as reported by a watcher such as the
+/// `notify` crate, to be applied to the tree with `apply_fs_event()`.
+///
+/// # Variants
+/// `Created`: `PathBuf` -> A file or directory was created, or moved in from outside the library.
+/// `Removed`: `PathBuf` -> A file or directory was removed, or moved out of the library.
+/// `Renamed`: `(PathBuf, PathBuf)` -> A file or directory was renamed or moved within the
+///                                    library, from the first path to the second.
+/// `Modified`: `PathBuf` -> The contents of a file changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FsChange {
+    Created(PathBuf),
+    Removed(PathBuf),
+    Renamed(PathBuf, PathBuf),
+    Modified(PathBuf),
+}
+
+/// Changes `apply_fs_event()` made to a Node tree, to be forwarded to the frontend.
+///
+/// Serializes as `{ "changes": [...] }`, with the changes serialized as described in
+/// `TreeChange`.
+///
+/// # Fields
+/// `changes`: `Vec<TreeChange>` -> The changes, as `diff()` reports them between the tree before
+///                                 and after the event. Empty when the event changed nothing,
+///                                 such as the creation of a file that is neither audio nor art.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct AppliedChange {
+    pub changes: Vec<TreeChange>,
+}
+
+impl AppliedChange {
+    /// Helper function that returns whether the event changed nothing.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Helper function that applies `event` to `node`, populated from `base` with `config`, the way
+/// scanning the library again would, and returns what changed.
+///
+/// - Audio files are inserted with `insert_path()` when created or modified, and removed with
+///   `remove_by_path()` when removed, pruning the directories left empty when
+///   `Config.prune_empty_dirs` is set. A created directory is inserted along with the directories
+///   and audio files below it, including empty ones unless `Config.prune_empty_dirs` is set.
+/// - Images of `Config.art_extensions` update the `art` of the directory they are in and of its
+///   children, which are the entries that may use them. Creating `Album/Album.webp` gives its art
+///   to `Album` and to the songs of `Album` without art of their own.
+/// - A renamed file or directory is removed from its old path and created at its new one.
+/// - Files that `populate()` would leave out, such as files of other types, excluded files or
+///   empty files when `Config.skip_empty_files` is set, are ignored, as are events for paths
+///   that no longer exist by the time they are applied.
+///
+/// Only the top-level entries the event is below are compared to report the changes, so
+/// applying an event is not slowed down by the size of the rest of the library, except for
+/// images directly in `base`, which may change the art of every top-level entry.
+///
+/// # Errors
+/// - `ErrorKind::InvalidInput` if a path of `event` is not below `base`.
+/// - Any error returned when reading the metadata of an audio file or listing a created
+///   directory, other than `ErrorKind::NotFound`.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, remove_file, rename, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-apply-fs-event");
+/// # let _ = remove_dir_all(&base);
+/// let album: PathBuf = base.join("Artist").join("Album");
+/// create_dir_all(&album).unwrap();
+/// write(album.join("1.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let base: PathBuf = base.canonicalize().unwrap();
+/// let album: PathBuf = base.join("Artist").join("Album");
+/// let mut tree: Node = populate_from(&base, &config).unwrap();
+/// let keys = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect::<Vec<String>>();
+///
+/// // New song
+/// write(album.join("2.mp3"), "").unwrap();
+/// let applied: AppliedChange =
+///     apply_fs_event(&mut tree, &base, FsChange::Created(album.join("2.mp3")), &config).unwrap();
+/// assert_eq!(applied.changes, [TreeChange::Added(keys(&["Artist", "Album", "2"]))]);
+///
+/// // New cover art for the album and its songs
+/// write(album.join("Album.webp"), "").unwrap();
+/// let event: FsChange = FsChange::Created(album.join("Album.webp"));
+/// let applied: AppliedChange = apply_fs_event(&mut tree, &base, event, &config).unwrap();
+/// assert_eq!(
+///     applied.changes,
+///     [
+///         TreeChange::Modified(keys(&["Artist", "Album"])),
+///         TreeChange::Modified(keys(&["Artist", "Album", "1"])),
+///         TreeChange::Modified(keys(&["Artist", "Album", "2"])),
+///     ]
+/// );
+/// assert_eq!(tree["Artist"].child["Album"].child["1"].art, Some(album.join("Album.webp")));
+///
+/// // Renamed song, and files that are not audio
+/// rename(album.join("2.mp3"), album.join("Two.mp3")).unwrap();
+/// let event: FsChange = FsChange::Renamed(album.join("2.mp3"), album.join("Two.mp3"));
+/// let applied: AppliedChange = apply_fs_event(&mut tree, &base, event, &config).unwrap();
+/// assert_eq!(
+///     applied.changes,
+///     [
+///         TreeChange::Removed(keys(&["Artist", "Album", "2"])),
+///         TreeChange::Added(keys(&["Artist", "Album", "Two"])),
+///     ]
+/// );
+/// write(album.join("notes.txt"), "").unwrap();
+/// let event: FsChange = FsChange::Created(album.join("notes.txt"));
+/// assert!(apply_fs_event(&mut tree, &base, event, &config).unwrap().is_empty());
+///
+/// // Removed art, then a whole new directory
+/// remove_file(album.join("Album.webp")).unwrap();
+/// let event: FsChange = FsChange::Removed(album.join("Album.webp"));
+/// assert_eq!(apply_fs_event(&mut tree, &base, event, &config).unwrap().changes.len(), 3);
+/// create_dir_all(base.join("Other").join("EP")).unwrap();
+/// write(base.join("Other").join("EP").join("song.mp3"), "").unwrap();
+/// let event: FsChange = FsChange::Created(base.join("Other"));
+/// let applied: AppliedChange = apply_fs_event(&mut tree, &base, event, &config).unwrap();
+/// assert_eq!(applied.changes, [TreeChange::Added(keys(&["Other"]))]);
+///
+/// // The same tree as a new scan
+/// assert_eq!(tree, populate_from(&base, &config).unwrap());
+/// ```
+pub fn apply_fs_event(
+    node: &mut Node,
+    base: &Path,
+    event: FsChange,
+    config: &Config,
+) -> Result<AppliedChange> {
+    // Work below the directory `populate()` scans when `base` is a symbolic link
+    let canonical: PathBuf = scan_base(base, config);
+    let rebase = |path: PathBuf| -> PathBuf {
+        match path.strip_prefix(base) {
+            Ok(relative) if canonical != base => canonical.join(relative),
+            _ => path,
+        }
+    };
+    let base: &Path = &canonical;
+    let event: FsChange = match event {
+        FsChange::Created(path) => FsChange::Created(rebase(path)),
+        FsChange::Removed(path) => FsChange::Removed(rebase(path)),
+        FsChange::Renamed(from, to) => FsChange::Renamed(rebase(from), rebase(to)),
+        FsChange::Modified(path) => FsChange::Modified(rebase(path)),
+    };
+
+    let paths: Vec<&Path> = match &event {
+        FsChange::Created(path) | FsChange::Removed(path) | FsChange::Modified(path) => {
+            vec![path]
+        }
+        FsChange::Renamed(from, to) => vec![from, to],
+    };
+    let mut tops: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        let top: PathBuf = top_level(base, path, config)?;
+        if !tops.contains(&top) {
+            tops.push(top);
+        }
+    }
+    if tops.iter().any(|top| top == base) {
+        tops = vec![base.to_path_buf()];
+    }
+    let before: Vec<Node> = tops.iter().map(|top| snapshot(node, base, top)).collect();
+
+    match &event {
+        FsChange::Created(path) | FsChange::Modified(path) => create(node, base, path, config)?,
+        FsChange::Removed(path) => remove(node, base, path, config),
+        FsChange::Renamed(from, to) => {
+            remove(node, base, from, config);
+            create(node, base, to, config)?;
+        }
+    }
+
+    let changes: Vec<TreeChange> = tops
+        .iter()
+        .zip(before)
+        .flat_map(|(top, before)| diff(&before, &snapshot(node, base, top)))
+        .collect();
+    Ok(AppliedChange { changes })
+}
+
+/// Helper function that returns whether `path` has one of the extensions of
+/// `Config.art_extensions`, regardless of case unless `Config.case_insensitive_extensions` is
+/// unset.
+fn is_art(path: &Path, config: &Config) -> bool {
+    let Some(extension) = path.extension().and_then(OsStr::to_str) else {
+        return false;
+    };
+    config
+        .art_extensions
+        .iter()
+        .any(|art| match config.case_insensitive_extensions {
+            true => art.eq_ignore_ascii_case(extension),
+            false => art == extension,
+        })
+}
+
+/// Helper function that returns the path of the top-level entry of the Node tree populated from
+/// `base` that an event for `path` may change, or `base` itself when it may change several.
+fn top_level(base: &Path, path: &Path, config: &Config) -> Result<PathBuf> {
+    let changed: &Path = match is_art(path, config) {
+        true => path.parent().unwrap_or(path),
+        false => path,
+    };
+    let relative: &Path = changed.strip_prefix(base).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} is not below {}", path.display(), base.display()),
+        )
+    })?;
+    match relative.components().next() {
+        Some(Component::Normal(name)) => Ok(base.join(name)),
+        None => Ok(base.to_path_buf()),
+        Some(_) => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} is not below {}", path.display(), base.display()),
+        )),
+    }
+}
+
+/// Helper function that returns a copy of the part of `node` that `top_level()` returned `top`
+/// for, which is the whole of `node` when `top` is `base`.
+fn snapshot(node: &Node, base: &Path, top: &Path) -> Node {
+    if top == base {
+        return node.clone();
+    }
+    find_child(node, top)
+        .map(|(key, entry)| Node::from([(key.clone(), entry.clone())]))
+        .unwrap_or_default()
+}
+
+/// Helper function that applies the creation or modification of the file or directory at `path`
+/// to `node`, as described in `apply_fs_event()`.
+fn create(node: &mut Node, base: &Path, path: &Path, config: &Config) -> Result<()> {
+    if is_art(path, config) {
+        refresh_art(node, base, path.parent().unwrap_or(base), config);
+        return Ok(());
+    }
+    if !to_long_path(path).exists() {
+        return Ok(());
+    }
+
+    if !to_long_path(path).is_dir() {
+        return skip_left_out(insert_path(node, base, path, config));
+    }
+
+    // Read the directory on its own, then insert it and its entries with the settings of `base`
+    let tree: Node = match populate_from(path, config) {
+        Ok(tree) => tree,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(error),
+    };
+    if tree.is_empty() && config.prune_empty_dirs {
+        return Ok(());
+    }
+    skip_left_out(insert_dir(node, base, path, None, config))?;
+
+    // Parents come before their children, and siblings in file name order, as in `populate()`
+    let mut entries: Vec<&Entry> = iter_depth_first(&tree).map(|(_, entry)| entry).collect();
+    entries.sort_by(|a, b| a.src.cmp(&b.src));
+    for entry in entries {
+        skip_left_out(match entry.is_dir() {
+            true => insert_dir(node, base, &entry.src, entry.error, config),
+            false => insert_path(node, base, &entry.src, config),
+        })?;
+    }
+    Ok(())
+}
+
+/// Helper function that ignores the error of inserting an entry that the configuration leaves
+/// out of the tree, or that is already gone again.
+fn skip_left_out(inserted: Result<()>) -> Result<()> {
+    match inserted {
+        Err(error)
+            if matches!(
+                error.kind(),
+                ErrorKind::Unsupported
+                    | ErrorKind::InvalidData
+                    | ErrorKind::AlreadyExists
+                    | ErrorKind::NotFound
+            ) =>
+        {
+            Ok(())
+        }
+        inserted => inserted,
+    }
+}
+
+/// Helper function that applies the removal of the file or directory at `path` to `node`, as
+/// described in `apply_fs_event()`.
+fn remove(node: &mut Node, base: &Path, path: &Path, config: &Config) {
+    match is_art(path, config) {
+        true => refresh_art(node, base, path.parent().unwrap_or(base), config),
+        false => {
+            remove_by_path(node, base, path, config.prune_empty_dirs, config.fold_case);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::node::EntryKind;
+    use super::super::testing::fixture;
+    use super::*;
+    use std::fs::{create_dir_all, write};
+
+    #[test]
+    fn created_empty_directories_are_inserted() {
+        let base: PathBuf = fixture("events-created-empty");
+        write(base.join("intro.mp3"), "").unwrap();
+        let config: Config = toml::from_str("base = ''").unwrap();
+        let mut tree: Node = populate_from(&base, &config).unwrap();
+
+        create_dir_all(base.join("Empty")).unwrap();
+        let event: FsChange = FsChange::Created(base.join("Empty"));
+        let applied: AppliedChange = apply_fs_event(&mut tree, &base, event, &config).unwrap();
+        assert_eq!(
+            applied.changes,
+            [TreeChange::Added(vec![String::from("Empty")])]
+        );
+        assert_eq!(tree, populate_from(&base, &config).unwrap());
+    }
+
+    #[test]
+    fn created_directories_are_inserted_whole() {
+        let base: PathBuf = fixture("events-created-whole");
+        let config: Config = toml::from_str("base = ''").unwrap();
+        let mut tree: Node = populate_from(&base, &config).unwrap();
+
+        let artist: PathBuf = base.join("Artist");
+        for dir in ["Album", "Album/Scans", "Album/Disc 1", "Empty"] {
+            create_dir_all(artist.join(dir)).unwrap();
+        }
+        for file in [
+            "Album.mp3",
+            "Album/Disc 1/1.flac",
+            "Album/Scans/back.txt",
+            "notes.txt",
+        ] {
+            write(artist.join(file), "").unwrap();
+        }
+        apply_fs_event(&mut tree, &base, FsChange::Created(artist.clone()), &config).unwrap();
+        assert_eq!(tree, populate_from(&base, &config).unwrap());
+        assert_eq!(tree["Artist"].child["Album"].kind, EntryKind::Directory);
+        assert_eq!(tree["Artist"].child["Album (mp3)"].kind, EntryKind::Audio);
+        assert!(tree["Artist"].child["Empty"].child.is_empty());
+    }
+
+    #[test]
+    fn created_empty_directories_are_pruned() {
+        let base: PathBuf = fixture("events-created-pruned");
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+        config.prune_empty_dirs = true;
+        let mut tree: Node = populate_from(&base, &config).unwrap();
+
+        create_dir_all(base.join("Empty").join("Scans")).unwrap();
+        write(base.join("Empty").join("Scans").join("back.txt"), "").unwrap();
+        let event: FsChange = FsChange::Created(base.join("Empty"));
+        assert!(apply_fs_event(&mut tree, &base, event, &config)
+            .unwrap()
+            .is_empty());
+
+        create_dir_all(base.join("Album").join("Scans")).unwrap();
+        write(base.join("Album").join("1.mp3"), "").unwrap();
+        let event: FsChange = FsChange::Created(base.join("Album"));
+        apply_fs_event(&mut tree, &base, event, &config).unwrap();
+        assert_eq!(tree, populate_from(&base, &config).unwrap());
+        assert_eq!(tree["Album"].child.keys().collect::<Vec<_>>(), ["1"]);
+    }
+}
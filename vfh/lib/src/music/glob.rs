@@ -0,0 +1,241 @@
+use super::platform::to_long_path;
+use std::ffi::OsString;
+use std::fs::read_dir;
+use std::path::{Component, Path, PathBuf};
+
+/// Helper function that matches `text` against a glob `pattern`.
+///
+/// Supported syntax:
+/// - `?` matches any single character except `/`.
+/// - `*` matches any sequence of characters except `/`.
+/// - `**` matches any sequence of characters, including `/`. `**/` also matches zero directories.
+/// - `[abc]`, `[a-z]` and `[!abc]` match a single character from (or not from) a set.
+///
+/// Every other character matches itself.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// assert!(glob_match("*.flac", "song.flac"));
+/// assert!(!glob_match("*.flac", "Album/song.flac"));
+/// assert!(glob_match("**/*.flac", "Album/song.flac"));
+/// assert!(glob_match("**/*.flac", "song.flac"));
+/// assert!(glob_match("Disc [0-9]", "Disc 2"));
+/// assert!(!glob_match("Disc [!0-9]", "Disc 2"));
+/// assert!(glob_match("track??", "track01"));
+/// ```
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    matches(&pattern, &text)
+}
+
+/// Helper function that matches a file or directory `path`, relative to the root of a scan,
+/// against an exclude `pattern`, following `.gitignore` conventions:
+/// - A pattern ending in `/` only matches directories.
+/// - A pattern without any other `/` matches the file or directory name at any depth.
+/// - A pattern containing a `/` is matched against the whole relative path. A leading `/` is
+///   ignored.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// assert!(path_matches("__MACOSX/", Path::new("Artist/__MACOSX"), true));
+/// assert!(!path_matches("__MACOSX/", Path::new("Artist/__MACOSX"), false));
+/// assert!(path_matches("*.cue", Path::new("Artist/Album/disc.cue"), false));
+/// assert!(path_matches("Artist/lossless-*", Path::new("Artist/lossless-masters"), true));
+/// assert!(!path_matches("Artist/lossless-*", Path::new("Other/Artist/lossless-masters"), true));
+/// ```
+pub fn path_matches(pattern: &str, path: &Path, is_dir: bool) -> bool {
+    let (pattern, dir_only): (&str, bool) = match pattern.strip_suffix('/') {
+        Some(stripped) => (stripped, true),
+        None => (pattern, false),
+    };
+
+    if pattern.is_empty() || (dir_only && !is_dir) {
+        return false;
+    }
+
+    let components: Vec<String> = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect();
+
+    match pattern.contains('/') {
+        true => glob_match(pattern.trim_start_matches('/'), &components.join("/")),
+        false => components
+            .last()
+            .is_some_and(|name| glob_match(pattern, name)),
+    }
+}
+
+/// Helper function that returns whether `path` is a glob pattern for `expand_dirs()`: one of its
+/// components contains `*`, `?` or `[`, and `path` does not exist as-is, so a directory whose
+/// name contains such characters, like `Live [2004]`, is still used as a plain path.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// assert!(is_glob_path(Path::new("/mnt/vfh-doc-missing/*/FLAC")));
+/// assert!(!is_glob_path(Path::new("/mnt/vfh-doc-missing/Music")));
+/// assert!(!is_glob_path(&env::temp_dir()));
+/// ```
+pub fn is_glob_path(path: &Path) -> bool {
+    !to_long_path(path).exists()
+        && path.components().any(|component| match component {
+            Component::Normal(name) => name
+                .to_str()
+                .is_some_and(|name| name.contains(['*', '?', '['])),
+            _ => false,
+        })
+}
+
+/// Helper function that returns the existing directories matching the glob pattern `pattern`,
+/// such as `/mnt/music/*/FLAC`, sorted by path.
+///
+/// Every component of `pattern` is matched with `glob_match()` against the names of the
+/// directories found for the previous components, so `*` never crosses a `/` and `**` matches
+/// like `*`. As in shells, wildcards only match hidden names when the component starts with a
+/// `.`. Directories that cannot be read match nothing. `pattern` itself is returned when it is
+/// not a glob pattern (see `is_glob_path()`) and is a directory.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-expand-dirs");
+/// # let _ = remove_dir_all(&base);
+/// for dir in ["Artist/FLAC", "Band/FLAC", "Band/MP3", ".Trash/FLAC", "Live [2004]", "Single"] {
+///     create_dir_all(base.join(dir)).unwrap();
+/// }
+/// write(base.join("Single").join("FLAC"), "").unwrap();
+///
+/// assert_eq!(
+///     expand_dirs(&base.join("*").join("FLAC")),
+///     [base.join("Artist/FLAC"), base.join("Band/FLAC")]
+/// );
+/// assert_eq!(
+///     expand_dirs(&base.join("B*").join("[A-Z]*")),
+///     [base.join("Band/FLAC"), base.join("Band/MP3")]
+/// );
+/// assert_eq!(expand_dirs(&base.join("Live [2004]")), [base.join("Live [2004]")]);
+/// assert!(expand_dirs(&base.join("*").join("OGG")).is_empty());
+/// ```
+pub fn expand_dirs(pattern: &Path) -> Vec<PathBuf> {
+    if !is_glob_path(pattern) {
+        return match to_long_path(pattern).is_dir() {
+            true => vec![pattern.to_path_buf()],
+            false => Vec::new(),
+        };
+    }
+
+    let mut dirs: Vec<PathBuf> = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let name: &str = match component {
+            Component::Normal(name) => name.to_str().unwrap_or(""),
+            _ => "",
+        };
+        if !name.contains(['*', '?', '[']) {
+            for dir in &mut dirs {
+                dir.push(component);
+            }
+            continue;
+        }
+
+        let mut matched: Vec<PathBuf> = Vec::new();
+        for dir in &dirs {
+            let read: &Path = match dir.as_os_str().is_empty() {
+                true => Path::new("."),
+                false => dir,
+            };
+            let Ok(entries) = read_dir(to_long_path(read)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let file_name: OsString = entry.file_name();
+                let Some(entry_name) = file_name.to_str() else {
+                    continue;
+                };
+                if (entry_name.starts_with('.') && !name.starts_with('.'))
+                    || !glob_match(name, entry_name)
+                {
+                    continue;
+                }
+                matched.push(dir.join(entry_name));
+            }
+        }
+        dirs = matched;
+    }
+
+    dirs.retain(|dir| to_long_path(dir).is_dir());
+    dirs.sort();
+    dirs
+}
+
+fn matches(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest: &[char] = &pattern[2..];
+            if rest.first() == Some(&'/') && matches(&rest[1..], text) {
+                return true;
+            }
+            (0..=text.len()).any(|i| matches(rest, &text[i..]))
+        }
+        Some('*') => {
+            let rest: &[char] = &pattern[1..];
+            let limit: usize = text.iter().position(|c| *c == '/').unwrap_or(text.len());
+            (0..=limit).any(|i| matches(rest, &text[i..]))
+        }
+        Some('?') => text.first().is_some_and(|c| *c != '/') && matches(&pattern[1..], &text[1..]),
+        Some('[') => match (class_end(pattern), text.first()) {
+            (Some(end), Some(c)) => {
+                *c != '/'
+                    && class_contains(&pattern[1..end], *c)
+                    && matches(&pattern[end + 1..], &text[1..])
+            }
+            (Some(_), None) => false,
+            (None, _) => text.first() == Some(&'[') && matches(&pattern[1..], &text[1..]),
+        },
+        Some(c) => text.first() == Some(c) && matches(&pattern[1..], &text[1..]),
+    }
+}
+
+/// Helper function that returns the index of the `]` closing the character class that opens at
+/// the start of `pattern`, if any.
+fn class_end(pattern: &[char]) -> Option<usize> {
+    let mut start: usize = 1;
+    if pattern.get(start) == Some(&'!') {
+        start += 1;
+    }
+    // A `]` directly after the opening bracket is part of the set
+    pattern
+        .iter()
+        .skip(start + 1)
+        .position(|c| *c == ']')
+        .map(|i| i + start + 1)
+}
+
+fn class_contains(class: &[char], c: char) -> bool {
+    let (negated, class): (bool, &[char]) = match class.first() {
+        Some('!') => (true, &class[1..]),
+        _ => (false, class),
+    };
+
+    let mut found: bool = false;
+    let mut i: usize = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+
+    found != negated
+}
@@ -0,0 +1,266 @@
+use super::node::{serialize_hex, serialize_unix_seconds, Entry, EntryKind, Node, ScanIssue};
+use super::sort::KindOrder;
+use serde::{Serialize, Serializer};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// JSON representation of an `Entry` sent to the frontend, borrowing from the Entry it was
+/// created from.
+///
+/// Fields are renamed to camelCase, and fields without a value are left out: `mime`, `art` and
+/// `modified` when they are `None`, `parent` for top-level entries and `child` for entries without
+/// children. `hasChildren` is only written, as `true`, by `DepthLimited` for entries whose
+/// children were left out. `Entry` itself keeps serializing every field under its Rust name, so
+/// serialized trees such as cache files keep loading.
+///
+/// `child` is written as an object keyed like `Entry.child`, with its members in the order of
+/// the `KindOrder` the representation was created with. See `NodeJson`.
+///
+/// # Fields
+/// See `Entry`, except for:
+/// `child`: `NodeJson` -> The children of the entry, in the order they are written in.
+/// `has_children`: `bool` -> Whether `child` was left out by `DepthLimited` although the entry has
+///                           children, which the frontend can fetch with `subtree()`.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::time::{Duration, UNIX_EPOCH};
+/// let mut song: Entry = Entry::new(
+///     PathBuf::from("/music/Artist/song.mp3"),
+///     EntryKind::Audio,
+///     None,
+///     PathBuf::from("Artist"),
+/// );
+/// song.id = 0x1f;
+/// song.mime = Some(String::from("audio/mpeg"));
+/// song.size = 3;
+/// song.modified = Some(UNIX_EPOCH + Duration::from_secs(30));
+///
+/// let mut artist: Entry = Entry::new(
+///     PathBuf::from("/music/Artist"),
+///     EntryKind::Directory,
+///     Some(PathBuf::from("/music/Artist/Artist.png")),
+///     PathBuf::new(),
+/// );
+/// artist.size = 3;
+/// artist.child.insert(String::from("song"), song);
+///
+/// assert_eq!(
+///     serde_json::to_string(&EntryJson::from(&artist)).unwrap(),
+///     "{\"id\":\"0000000000000000\",\"src\":\"/music/Artist\",\"kind\":\"directory\",\
+///       \"art\":\"/music/Artist/Artist.png\",\"size\":3,\"child\":{\"song\":{\
+///       \"id\":\"000000000000001f\",\"src\":\"/music/Artist/song.mp3\",\"kind\":\"audio\",\
+///       \"mime\":\"audio/mpeg\",\"size\":3,\"modified\":30,\"parent\":\"Artist\"}}}"
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryJson<'a> {
+    #[serde(serialize_with = "serialize_hex")]
+    pub id: u64,
+    pub src: &'a Path,
+    pub kind: EntryKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub art: Option<&'a Path>,
+    pub size: u64,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_unix_seconds"
+    )]
+    pub modified: Option<SystemTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ScanIssue>,
+    #[serde(skip_serializing_if = "is_empty_path")]
+    pub parent: &'a Path,
+    #[serde(skip_serializing_if = "NodeJson::is_empty")]
+    pub child: NodeJson<'a>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub has_children: bool,
+}
+
+impl<'a> EntryJson<'a> {
+    /// Helper function that returns the JSON representation of `entry` without the entries
+    /// nested more than `depth` levels below it, as described in `DepthLimited`, with the
+    /// children of every entry in `order`.
+    pub fn with_depth(entry: &'a Entry, depth: usize, order: KindOrder) -> EntryJson<'a> {
+        let truncated: bool = depth == 0 && !entry.child.is_empty();
+        let child: NodeJson<'a> = match truncated {
+            true => NodeJson::default(),
+            false => NodeJson::with_depth(&entry.child, depth.saturating_sub(1), order),
+        };
+
+        EntryJson {
+            id: entry.id,
+            src: &entry.src,
+            kind: entry.kind,
+            mime: entry.mime.as_deref(),
+            art: entry.art.as_deref(),
+            size: entry.size,
+            modified: entry.modified,
+            error: entry.error,
+            parent: &entry.parent,
+            child,
+            has_children: truncated,
+        }
+    }
+}
+
+impl<'a> From<&'a Entry> for EntryJson<'a> {
+    fn from(entry: &'a Entry) -> EntryJson<'a> {
+        EntryJson::with_depth(entry, usize::MAX, KindOrder::Mixed)
+    }
+}
+
+/// JSON representation of the entries of a Node, such as the children of an `EntryJson`, written
+/// as an object keyed like the Node. Unlike the Node, which is a map sorted by key, the members
+/// are written in the order of the list, so that a frontend reading them in order can list
+/// directories first. See `KindOrder`.
+///
+/// # Fields
+/// `0`: `Vec<(&str, EntryJson)>` -> The keys and representations of the entries, in the order
+///                                 they are written in.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let mut tree: Node = Node::new();
+/// tree.insert(String::from("Intro"), Entry { kind: EntryKind::Audio, ..Entry::default() });
+/// tree.insert(String::from("Live"), Entry::default());
+///
+/// let json: NodeJson = NodeJson::with_depth(&tree, usize::MAX, KindOrder::DirectoriesFirst);
+/// assert_eq!(json.0.iter().map(|(key, _)| *key).collect::<Vec<_>>(), ["Live", "Intro"]);
+/// assert!(serde_json::to_string(&json).unwrap().starts_with("{\"Live\":{"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NodeJson<'a>(pub Vec<(&'a str, EntryJson<'a>)>);
+
+impl<'a> NodeJson<'a> {
+    /// Helper function that returns the JSON representation of the entries of `node`, in
+    /// `order`, without the entries nested more than `depth` levels below them.
+    pub fn with_depth(node: &'a Node, depth: usize, order: KindOrder) -> NodeJson<'a> {
+        let mut children: Vec<(&String, &Entry)> = node.iter().collect();
+        order.apply(&mut children);
+        NodeJson(
+            children
+                .into_iter()
+                .map(|(key, entry)| (key.as_str(), EntryJson::with_depth(entry, depth, order)))
+                .collect(),
+        )
+    }
+
+    /// Helper function that returns whether there are no entries, so `EntryJson.child` is left
+    /// out for entries without children.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Serialize for NodeJson<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(key, entry)| (key, entry)))
+    }
+}
+
+/// Helper function that returns the JSON representation of every entry of `node`, keyed like
+/// `node`, with the children of every entry in `order`. See `EntryJson`.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let mut tree: Node = Node::new();
+/// tree.insert(String::from("Empty"), Entry::default());
+///
+/// assert_eq!(
+///     serde_json::to_string(&node_json(&tree, KindOrder::Mixed)).unwrap(),
+///     "{\"Empty\":{\"id\":\"0000000000000000\",\"src\":\"\",\"kind\":\"directory\",\"size\":0}}"
+/// );
+///
+/// // Album folders before loose tracks, at every level
+/// let audio = || Entry { kind: EntryKind::Audio, ..Entry::default() };
+/// let mut tree: Node = Node::new();
+/// let artist: &mut Entry = tree.entry(String::from("Artist")).or_default();
+/// artist.child.insert(String::from("B-Side"), audio());
+/// artist.child.insert(String::from("Debut"), Entry::default());
+/// tree.insert(String::from("Intro"), audio());
+/// tree.insert(String::from("Live"), Entry::default());
+///
+/// fn keys<'a>(json: &NodeJson<'a>) -> Vec<&'a str> {
+///     json.0.iter().map(|(key, _)| *key).collect()
+/// }
+/// let mixed: NodeJson = node_json(&tree, KindOrder::Mixed);
+/// assert_eq!(keys(&mixed), ["Artist", "Intro", "Live"]);
+/// assert_eq!(keys(&mixed.0[0].1.child), ["B-Side", "Debut"]);
+///
+/// let json: NodeJson = node_json(&tree, KindOrder::DirectoriesFirst);
+/// assert_eq!(keys(&json), ["Artist", "Live", "Intro"]);
+/// assert_eq!(keys(&json.0[0].1.child), ["Debut", "B-Side"]);
+///
+/// // Members are written in that order
+/// let written: String = serde_json::to_string(&json).unwrap();
+/// let at = |key: &str| written.find(&format!("\"{}\":", key)).unwrap();
+/// assert!(at("Artist") < at("Debut") && at("Debut") < at("B-Side"));
+/// assert!(at("B-Side") < at("Live") && at("Live") < at("Intro"));
+/// ```
+pub fn node_json(node: &Node, order: KindOrder) -> NodeJson<'_> {
+    NodeJson::with_depth(node, usize::MAX, order)
+}
+
+/// Node tree serialized like `node_json()`, leaving out the entries nested more than `depth`
+/// levels below the top level, so the frontend can load a large tree lazily. The top-level
+/// entries are at depth 0, so a `depth` of 0 only serializes them.
+///
+/// Entries at `depth` keep all of their own fields, and those that have children are written
+/// with `"hasChildren": true` instead of their `child` map. With a `depth` of `usize::MAX`, the
+/// output is the same as the one of `node_json()`.
+///
+/// # Fields
+/// `node`: `&Node` -> The Node tree to serialize.
+/// `depth`: `usize` -> Depth of the deepest entries serialized.
+/// `order`: `KindOrder` -> Order the children of every entry are written in.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let mut tree: Node = Node::new();
+/// let artist: &mut Entry = tree.entry(String::from("Artist")).or_default();
+/// let album: &mut Entry = artist.child.entry(String::from("Album")).or_default();
+/// album.child.insert(String::from("Track"), Entry::default());
+/// tree.insert(String::from("Empty"), Entry::default());
+///
+/// let order: KindOrder = KindOrder::Mixed;
+/// let limited: DepthLimited = DepthLimited { node: &tree, depth: 1, order };
+/// assert_eq!(
+///     serde_json::to_string(&limited).unwrap(),
+///     "{\"Artist\":{\"id\":\"0000000000000000\",\"src\":\"\",\"kind\":\"directory\",\"size\":0,\
+///       \"child\":{\"Album\":{\"id\":\"0000000000000000\",\"src\":\"\",\"kind\":\"directory\",\
+///       \"size\":0,\"hasChildren\":true}}},\
+///       \"Empty\":{\"id\":\"0000000000000000\",\"src\":\"\",\"kind\":\"directory\",\"size\":0}}"
+/// );
+///
+/// let full: DepthLimited = DepthLimited { node: &tree, depth: usize::MAX, order };
+/// assert_eq!(
+///     serde_json::to_string(&full).unwrap(),
+///     serde_json::to_string(&node_json(&tree, order)).unwrap()
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DepthLimited<'a> {
+    pub node: &'a Node,
+    pub depth: usize,
+    pub order: KindOrder,
+}
+
+impl Serialize for DepthLimited<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        NodeJson::with_depth(self.node, self.depth, self.order).serialize(serializer)
+    }
+}
+
+/// Helper function that returns whether `path` is empty, so `EntryJson.parent` is left out for
+/// top-level entries.
+fn is_empty_path(path: &&Path) -> bool {
+    path.as_os_str().is_empty()
+}
@@ -1,10 +1,55 @@
+mod builder;
+mod cache;
+mod checkpoint;
 mod config;
+mod diff;
+#[cfg(feature = "duplicates")]
+mod duplicates;
+mod events;
+mod glob;
+mod json;
 mod node;
+mod page;
+mod platform;
+mod relative;
+mod render;
+mod rescan;
+mod shuffle;
+mod sniff;
+mod sort;
+mod stats;
+#[cfg(test)]
+mod testing;
+mod traverse;
+mod tree;
 mod utils;
+mod validate;
+#[cfg(feature = "watch")]
+mod watch;
 
+pub use builder::*;
+pub use cache::*;
+pub use checkpoint::*;
 pub use config::*;
+pub use diff::*;
+#[cfg(feature = "duplicates")]
+pub use duplicates::*;
+pub use events::*;
+#[cfg(feature = "collation")]
+pub use feruca::Locale as CollationLocale;
+pub use glob::*;
+pub use json::*;
 pub use node::*;
+pub use page::*;
+pub use platform::*;
+pub use relative::*;
+pub use render::*;
+pub use rescan::*;
 pub use serde::{Deserialize, Serialize};
+pub use shuffle::*;
+pub use sniff::*;
+pub use sort::*;
+pub use stats::*;
 pub use std::collections::BTreeMap;
 pub use std::env;
 pub use std::ffi::OsString;
@@ -12,4 +57,9 @@ pub use std::fs::{read_dir, read_to_string, DirEntry};
 pub use std::io::Result;
 pub use std::path::{Path, PathBuf};
 pub use toml;
+pub use traverse::*;
+pub use tree::*;
 pub use utils::*;
+pub use validate::*;
+#[cfg(feature = "watch")]
+pub use watch::*;
@@ -1,6 +1,9 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::BTreeMap;
-use std::path::PathBuf;
+use std::hash::Hasher;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Logical representation of a file hierarchy as a tree-like structure.
 pub type Node = BTreeMap<String, Entry>;
@@ -8,25 +11,432 @@ pub type Node = BTreeMap<String, Entry>;
 /// Logical representation of a file or directory as JSON.
 ///
 /// # Fields
+/// `id`: `u64` -> Identifier of the entry, stable across runs, platforms and moves of the library.
+///                See `entry_id()`. Serialized as a 16-digit hexadecimal string, since JavaScript
+///                numbers cannot represent every `u64`. 0 for trees serialized before this field
+///                was added.
 /// `src`: `PathBuf` -> The file path for the current file object.
-/// `art`: `PathBuf` -> The file path for the image to be displayed as cover art.
-/// `parent`: `String` -> A key to be used in reverse traversal of the node tree.
+/// `kind`: `EntryKind` -> Whether the current file object is a directory or an audio file.
+/// `mime`: `Option<String>` -> MIME type of the audio file, derived from its extension with
+///                            `mime_for_extension()`. `None` for directories.
+/// `art`: `Option<PathBuf>` -> The file path for the image to be displayed as cover art, or `None`
+///                             when the entry has no art. Serialized as `null` when absent. For
+///                             compatibility with trees serialized before this field was optional,
+///                             an empty string deserializes as `None`.
+/// `size`: `u64` -> Size in bytes of the audio file, or the sum of the sizes of the children for
+///                  a directory. 0 when the size of a file could not be read.
+/// `modified`: `Option<SystemTime>` -> Last modification time, to the second, of the audio file,
+///                                     or the latest one among the descendants of a directory,
+///                                     falling back to the directory's own when it has none.
+///                                     `None` when the platform does not provide it. Serialized
+///                                     as Unix seconds.
+/// `scanned_at`: `Option<SystemTime>` -> Time, to the second, at which the contents of the
+///                                       directory were last read, used by `refresh()` to skip
+///                                       the directories that did not change since. `None` for
+///                                       audio files and for directories never read by a scan.
+///                                       Serialized as Unix seconds. Ignored when comparing
+///                                       entries, so that trees scanned at different times but
+///                                       holding the same files are equal.
+/// `error`: `Option<ScanIssue>` -> Why the contents of the directory could not be read, in which
+///                                 case it is kept in the tree without children, or `None` when
+///                                 they were read or the entry is an audio file. See `ScanIssue`.
+/// `parent`: `PathBuf` -> Keys leading from the root of the Node tree to the parent entry, joined
+///                        as a relative path, so entries sharing a name in different places of
+///                        the tree are told apart. Empty for top-level entries. See
+///                        `NodeExt::resolve_parent()`.
 /// `child`: `Node` -> The associated files underlying the current file object.
-#[derive(Debug, Deserialize, Serialize)]
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// // Entry serialized by an earlier version
+/// let entry: Entry = toml::from_str(
+///     "src = '/mnt/music/Song.mp3'\nkind = 'audio'\nart = ''\nparent = 'music'\n[child]",
+/// )
+/// .unwrap();
+/// assert_eq!(entry.art, None);
+/// ```
+#[derive(Clone, Debug, Default, Eq, Deserialize, Serialize)]
 pub struct Entry {
+    #[serde(
+        default,
+        serialize_with = "serialize_hex",
+        deserialize_with = "deserialize_hex"
+    )]
+    pub id: u64,
     pub src: PathBuf,
-    pub art: PathBuf,
-    pub parent: String,
+    pub kind: EntryKind,
+    #[serde(default)]
+    pub mime: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_art")]
+    pub art: Option<PathBuf>,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(
+        default,
+        serialize_with = "serialize_unix_seconds",
+        deserialize_with = "deserialize_unix_seconds"
+    )]
+    pub modified: Option<SystemTime>,
+    #[serde(
+        default,
+        serialize_with = "serialize_unix_seconds",
+        deserialize_with = "deserialize_unix_seconds"
+    )]
+    pub scanned_at: Option<SystemTime>,
+    #[serde(default)]
+    pub error: Option<ScanIssue>,
+    pub parent: PathBuf,
     pub child: Node,
 }
 
+impl PartialEq for Entry {
+    fn eq(&self, other: &Entry) -> bool {
+        let Entry {
+            id,
+            src,
+            kind,
+            mime,
+            art,
+            size,
+            modified,
+            scanned_at: _,
+            error,
+            parent,
+            child,
+        } = self;
+
+        *id == other.id
+            && *src == other.src
+            && *kind == other.kind
+            && *mime == other.mime
+            && *art == other.art
+            && *size == other.size
+            && *modified == other.modified
+            && *error == other.error
+            && *parent == other.parent
+            && *child == other.child
+    }
+}
+
 impl Entry {
-    pub fn default() -> Entry {
+    /// Helper function that returns an Entry without children, with an `id` and a `size` of 0 and
+    /// no `mime` type, `modified` or `scanned_at` time or `error`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// let entry: Entry = Entry::new(
+    ///     PathBuf::from("/mnt/music/Artist/Song.mp3"),
+    ///     EntryKind::Audio,
+    ///     Some(PathBuf::from("/mnt/music/Artist/Artist.jpg")),
+    ///     PathBuf::from("Artist"),
+    /// );
+    /// assert!(entry.child.is_empty());
+    /// assert_eq!(entry.clone(), entry);
+    ///
+    /// let mut tree: Node = Node::new();
+    /// tree.entry(String::from("Artist")).or_default().child.insert(String::from("Song"), entry);
+    /// assert_eq!(tree["Artist"].kind, EntryKind::Directory);
+    /// ```
+    pub fn new(src: PathBuf, kind: EntryKind, art: Option<PathBuf>, parent: PathBuf) -> Entry {
         Entry {
-            src: PathBuf::from(""),
-            art: PathBuf::from(""),
-            parent: String::from(""),
+            id: 0,
+            src,
+            kind,
+            mime: None,
+            art,
+            size: 0,
+            modified: None,
+            scanned_at: None,
+            error: None,
+            parent,
             child: Node::new(),
         }
     }
+
+    /// Helper function that returns whether the entry is a leaf of the Node tree, meaning an
+    /// `EntryKind::Audio` file that can be played. Only the stored `kind` is looked at, so the
+    /// file system is never accessed.
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// let mut song: Entry = Entry::default();
+    /// song.kind = EntryKind::Audio;
+    /// assert!(song.is_leaf() && !song.is_dir());
+    ///
+    /// // Empty directories are directories all the same
+    /// let album: Entry = Entry::default();
+    /// assert!(album.is_dir() && !album.is_leaf());
+    /// ```
+    pub fn is_leaf(&self) -> bool {
+        self.kind == EntryKind::Audio
+    }
+
+    /// Helper function that returns whether the entry is an `EntryKind::Directory`, with or
+    /// without children. Only the stored `kind` is looked at, so the file system is never
+    /// accessed.
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Directory
+    }
+}
+
+/// Helper function that returns the `Entry.id` of the entry reached by following `keys` from the
+/// root of a Node tree, such as `Artist/Album/Track`.
+///
+/// The id is the 64-bit FNV-1a hash of the keys joined with `/`, whatever the separator of the
+/// platform, so it only depends on the position of the entry in the tree and not on where the
+/// library is mounted. `populate()` fills it in for every entry.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-entry-id");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Artist").join("Album")).unwrap();
+/// write(base.join("Artist").join("Album").join("song.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// let album: &Entry = &tree["Artist"].child["Album"];
+/// assert_eq!(tree["Artist"].id, 0x7292700b6aa631fe);
+/// assert_eq!(album.id, 0x297c8624fd270ffa);
+/// assert_eq!(album.child["song"].id, 0x9952c228fd8ddece);
+/// assert_eq!(entry_id(&Path::new("Artist").join("Album")), album.id);
+///
+/// let serialized: String = toml::to_string(&album.child["song"]).unwrap();
+/// assert!(serialized.starts_with("id = \"9952c228fd8ddece\"\n"));
+/// ```
+pub fn entry_id(keys: &Path) -> u64 {
+    let mut hasher: Fnv1a = Fnv1a::default();
+    for (index, key) in keys.components().enumerate() {
+        if index > 0 {
+            hasher.write(b"/");
+        }
+        hasher.write(key.as_os_str().as_encoded_bytes());
+    }
+    hasher.finish()
+}
+
+/// 64-bit FNV-1a hasher, whose output is the same on every run and platform, unlike the one of
+/// `std::collections::hash_map::DefaultHasher`.
+pub(super) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Fnv1a {
+        Fnv1a(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Operations on a whole Node tree.
+pub trait NodeExt {
+    /// Helper function that returns the parent of `entry` by following `Entry.parent` from the
+    /// root of the tree, or `None` for top-level entries and entries that are not in the tree.
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// # use std::fs::{create_dir_all, remove_dir_all, write};
+    /// let base: PathBuf = env::temp_dir().join("vfh-doc-resolve-parent");
+    /// # let _ = remove_dir_all(&base);
+    /// for artist in ["Artist A", "Artist B"] {
+    ///     create_dir_all(base.join(artist).join("Live")).unwrap();
+    ///     write(base.join(artist).join("Live").join("Encore.mp3"), "").unwrap();
+    /// }
+    ///
+    /// let config: Config = toml::from_str("base = ''").unwrap();
+    /// let mut tree: Node = Node::new();
+    /// populate(&base, &mut tree, &config).unwrap();
+    ///
+    /// let encore: &Entry = &tree["Artist B"].child["Live"].child["Encore"];
+    /// assert_eq!(encore.parent, Path::new("Artist B").join("Live"));
+    ///
+    /// let live: &Entry = tree.resolve_parent(encore).unwrap();
+    /// assert_eq!(live.src, base.join("Artist B").join("Live"));
+    /// assert_eq!(tree.resolve_parent(live).unwrap().src, base.join("Artist B"));
+    /// assert_eq!(tree.resolve_parent(&tree["Artist A"]), None);
+    /// ```
+    fn resolve_parent(&self, entry: &Entry) -> Option<&Entry>;
+}
+
+impl NodeExt for Node {
+    fn resolve_parent(&self, entry: &Entry) -> Option<&Entry> {
+        let mut keys = entry.parent.iter();
+        let mut parent: &Entry = self.get(keys.next()?.to_str()?)?;
+
+        for key in keys {
+            parent = parent.child.get(key.to_str()?)?;
+        }
+        Some(parent)
+    }
+}
+
+/// Helper function that deserializes `Entry.art`, treating the empty path used for missing art by
+/// earlier versions as `None`.
+fn deserialize_art<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<PathBuf>, D::Error> {
+    let art: Option<PathBuf> = Option::deserialize(deserializer)?;
+    Ok(art.filter(|art| !art.as_os_str().is_empty()))
+}
+
+/// Helper function that serializes `Entry.id` as a 16-digit lowercase hexadecimal string.
+pub(super) fn serialize_hex<S: Serializer>(id: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    format!("{:016x}", id).serialize(serializer)
+}
+
+/// Helper function that deserializes `Entry.id` from the representation written by
+/// `serialize_hex()`.
+pub(super) fn deserialize_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let hex: String = String::deserialize(deserializer)?;
+    u64::from_str_radix(&hex, 16).map_err(serde::de::Error::custom)
+}
+
+/// Helper function that truncates `time` to whole seconds, the precision `Entry.modified` is
+/// serialized with, so entries compare equal after a round trip.
+pub(super) fn whole_seconds(time: SystemTime) -> SystemTime {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => UNIX_EPOCH + Duration::from_secs(after.as_secs()),
+        Err(before) => UNIX_EPOCH - Duration::from_secs(before.duration().as_secs()),
+    }
+}
+
+/// Helper function that serializes `Entry.modified` and `Entry.scanned_at` as whole seconds since
+/// the Unix epoch, negative for earlier times.
+pub(super) fn serialize_unix_seconds<S: Serializer>(
+    modified: &Option<SystemTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let seconds: Option<i64> = modified.map(|modified| match modified.duration_since(UNIX_EPOCH) {
+        Ok(after) => after.as_secs() as i64,
+        Err(before) => -(before.duration().as_secs() as i64),
+    });
+    seconds.serialize(serializer)
+}
+
+/// Helper function that deserializes `Entry.modified` and `Entry.scanned_at` from the
+/// representation written by `serialize_unix_seconds()`.
+fn deserialize_unix_seconds<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<SystemTime>, D::Error> {
+    let seconds: Option<i64> = Option::deserialize(deserializer)?;
+    Ok(seconds.map(|seconds| match seconds >= 0 {
+        true => UNIX_EPOCH + Duration::from_secs(seconds as u64),
+        false => UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs()),
+    }))
+}
+
+/// Kind of file object an `Entry` represents, serialized as a lowercase string so the frontend
+/// can decide whether selecting an entry navigates into it or plays it.
+///
+/// # Variants
+/// `Directory` -> A directory, whose contents are the children of the Entry. A directory may have
+///                no children, for instance an album folder that is still empty. This is the
+///                default.
+/// `Audio` -> An audio file, which never has children.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-entry-kind");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Empty Album")).unwrap();
+/// write(base.join("Single.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// assert_eq!(tree["Empty Album"].kind, EntryKind::Directory);
+/// assert!(tree["Empty Album"].child.is_empty());
+/// assert_eq!(tree["Single"].kind, EntryKind::Audio);
+///
+/// let serialized: String = toml::to_string(&tree["Empty Album"]).unwrap();
+/// assert!(serialized.contains("kind = \"directory\""));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    #[default]
+    Directory,
+    Audio,
+}
+
+/// Reason the contents of a directory could not be read by `populate()`, kept in its
+/// `Entry.error` so the frontend can show the directory as inaccessible rather than leave it out.
+/// Serialized as a snake_case string.
+///
+/// The error itself is still reported in `ScanStats.errors`, so logs get the details while the
+/// tree only holds what is needed for display. Flagged directories are never pruned by
+/// `Config.prune_empty_dirs`, and `refresh()` always reads them again.
+///
+/// # Variants
+/// `PermissionDenied` -> The directory could not be listed for lack of permission.
+/// `TimedOut` -> Listing the directory took longer than `Config.read_timeout_secs`.
+/// `Other` -> Listing the directory failed with any other error.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::io::ErrorKind;
+/// assert_eq!(ScanIssue::from(ErrorKind::PermissionDenied), ScanIssue::PermissionDenied);
+/// assert_eq!(ScanIssue::from(ErrorKind::InvalidData), ScanIssue::Other);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanIssue {
+    PermissionDenied,
+    TimedOut,
+    Other,
+}
+
+impl From<ErrorKind> for ScanIssue {
+    fn from(kind: ErrorKind) -> ScanIssue {
+        match kind {
+            ErrorKind::PermissionDenied => ScanIssue::PermissionDenied,
+            ErrorKind::TimedOut => ScanIssue::TimedOut,
+            _ => ScanIssue::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::json::EntryJson;
+    use super::super::utils::prune_empty_dirs;
+    use super::*;
+
+    #[test]
+    fn flagged_dirs_are_kept_and_serialized() {
+        let mut locked: Entry = Entry::new(
+            PathBuf::from("Locked"),
+            EntryKind::Directory,
+            None,
+            PathBuf::new(),
+        );
+        locked.error = Some(ScanIssue::PermissionDenied);
+        let mut tree: Node = Node::from([(String::from("Locked"), locked)]);
+
+        assert_eq!(prune_empty_dirs(&mut tree), 0);
+        let json: String = serde_json::to_string(&EntryJson::from(&tree["Locked"])).unwrap();
+        assert!(json.contains("\"error\":\"permission_denied\""));
+    }
 }
@@ -0,0 +1,154 @@
+use super::node::{Entry, Node};
+use super::sort::{natural_cmp, KindOrder};
+use super::traverse::{count_tree, flatten, TreeCounts};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::vec::IntoIter;
+
+/// Order in which `page()` lists the entries of a Node tree, deserialized from a snake_case
+/// string so the frontend can pass it along with a page request.
+///
+/// # Variants
+/// `Tree` -> Depth-first, with the children of every entry in the order of their keys, like
+///           `iter_depth_first()`. This is the default.
+/// `Natural` -> Depth-first, with the children of every entry in the natural order of their keys.
+///              See `natural_cmp()`.
+/// `RecentlyModified` -> Most recently modified first, entries without a modification time last,
+///                       ties in `Tree` order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageOrder {
+    #[default]
+    Tree,
+    Natural,
+    RecentlyModified,
+}
+
+/// Helper function that returns the number of entries of `node` and its descendants, directories
+/// included, which is the number of entries `page()` pages through.
+pub fn total_count(node: &Node) -> usize {
+    let counts: TreeCounts = count_tree(node);
+    counts.songs + counts.dirs
+}
+
+/// Helper function that returns at most `limit` entries of `node` and its descendants, together
+/// with the keys leading to them, skipping the first `offset` entries in `order`. An `offset` past
+/// the last entry returns an empty page.
+///
+/// With `KindOrder::DirectoriesFirst`, the children of every entry list their directories first
+/// in `PageOrder::Tree` and `PageOrder::Natural` order, and every directory comes before every
+/// audio file in `PageOrder::RecentlyModified` order.
+///
+/// In `PageOrder::Tree` and `PageOrder::Natural` order, skipped entries are only walked through,
+/// so no keys are allocated for them and the walk stops at the end of the page.
+/// `PageOrder::RecentlyModified` needs every entry to be sorted first.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let mut tree: Node = Node::new();
+/// let album: &mut Entry = tree.entry(String::from("Album")).or_default();
+/// for track in ["Track 1", "Track 10", "Track 2"] {
+///     album.child.insert(String::from(track), Entry::default());
+/// }
+/// assert_eq!(total_count(&tree), 4);
+///
+/// let keys = |page: Vec<(Vec<String>, &Entry)>| -> Vec<String> {
+///     page.into_iter().map(|(keys, _)| keys.join("/")).collect()
+/// };
+/// let mixed: KindOrder = KindOrder::Mixed;
+/// assert_eq!(
+///     keys(page(&tree, 1, 2, PageOrder::Tree, mixed)),
+///     ["Album/Track 1", "Album/Track 10"]
+/// );
+/// assert_eq!(
+///     keys(page(&tree, 2, 5, PageOrder::Natural, mixed)),
+///     ["Album/Track 2", "Album/Track 10"]
+/// );
+/// assert!(page(&tree, 4, 10, PageOrder::Tree, mixed).is_empty());
+/// assert!(page(&tree, usize::MAX, 10, PageOrder::RecentlyModified, mixed).is_empty());
+///
+/// // Album folders before loose tracks, at every level
+/// let audio = || Entry { kind: EntryKind::Audio, ..Entry::default() };
+/// let mut tree: Node = Node::new();
+/// let artist: &mut Entry = tree.entry(String::from("Artist")).or_default();
+/// artist.child.insert(String::from("B-Side"), audio());
+/// let debut: &mut Entry = artist.child.entry(String::from("Debut")).or_default();
+/// debut.child.insert(String::from("1"), audio());
+/// tree.insert(String::from("Intro"), audio());
+/// tree.insert(String::from("Live"), Entry::default());
+///
+/// assert_eq!(
+///     keys(page(&tree, 0, 10, PageOrder::Tree, mixed)),
+///     ["Artist", "Artist/B-Side", "Artist/Debut", "Artist/Debut/1", "Intro", "Live"]
+/// );
+/// assert_eq!(
+///     keys(page(&tree, 0, 10, PageOrder::Tree, KindOrder::DirectoriesFirst)),
+///     ["Artist", "Artist/Debut", "Artist/Debut/1", "Artist/B-Side", "Live", "Intro"]
+/// );
+/// assert_eq!(
+///     keys(page(&tree, 0, 10, PageOrder::RecentlyModified, KindOrder::DirectoriesFirst)),
+///     ["Artist", "Artist/Debut", "Live", "Artist/B-Side", "Artist/Debut/1", "Intro"]
+/// );
+/// ```
+pub fn page(
+    node: &Node,
+    offset: usize,
+    limit: usize,
+    order: PageOrder,
+    kinds: KindOrder,
+) -> Vec<(Vec<String>, &Entry)> {
+    match order {
+        PageOrder::Tree => walk(node, offset, limit, |mut children| {
+            kinds.apply(&mut children);
+            children
+        }),
+        PageOrder::Natural => walk(node, offset, limit, |mut children| {
+            children.sort_by(|(a, _), (b, _)| natural_cmp(a, b));
+            kinds.apply(&mut children);
+            children
+        }),
+        PageOrder::RecentlyModified => {
+            let mut entries: Vec<(Vec<String>, &Entry)> = flatten(node, KindOrder::Mixed);
+            entries.sort_by_key(|(_, entry)| Reverse(entry.modified));
+            kinds.apply(&mut entries);
+            entries.into_iter().skip(offset).take(limit).collect()
+        }
+    }
+}
+
+/// Helper function that walks `node` depth-first, with the children of every entry in the order
+/// returned by `arrange`, collecting the entries in the window described in `page()`.
+pub(super) fn walk<'a>(
+    node: &'a Node,
+    offset: usize,
+    limit: usize,
+    arrange: impl Fn(Vec<(&'a String, &'a Entry)>) -> Vec<(&'a String, &'a Entry)>,
+) -> Vec<(Vec<String>, &'a Entry)> {
+    let end: usize = offset.saturating_add(limit);
+    let mut entries: Vec<(Vec<String>, &Entry)> = Vec::new();
+    let mut stack: Vec<IntoIter<(&String, &Entry)>> =
+        vec![arrange(node.iter().collect()).into_iter()];
+    let mut keys: Vec<&str> = Vec::new();
+    let mut index: usize = 0;
+
+    while index < end {
+        let Some(level) = stack.last_mut() else {
+            break;
+        };
+        let Some((key, entry)) = level.next() else {
+            stack.pop();
+            keys.pop();
+            continue;
+        };
+
+        keys.push(key);
+        if index >= offset {
+            entries.push((keys.iter().copied().map(String::from).collect(), entry));
+        }
+        index += 1;
+        stack.push(arrange(entry.child.iter().collect()).into_iter());
+    }
+
+    entries
+}
@@ -0,0 +1,505 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs::{create_dir_all, remove_file, rename, File};
+use std::io::{Result, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Length, in characters, from which Windows refuses paths without the `\\?\` extended-length
+/// prefix. Directories must leave room for an 8.3 file name below them, so they are limited to 12
+/// characters less.
+pub const MAX_PATH: usize = 260;
+
+/// Operating system family, used to decide where the configuration file and music library are
+/// expected to live.
+///
+/// The platform is detected at runtime through `Platform::current()` so every build of the
+/// program resolves paths the same way on the same machine.
+///
+/// # Variants
+/// `Unix`: Linux and other Unix-like systems following the XDG base directory layout.
+/// `MacOs`: macOS, using `~/Library/Application Support` with XDG as a secondary location.
+/// `Windows`: Windows, using `%APPDATA%` and `%USERPROFILE%`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Platform {
+    Unix,
+    MacOs,
+    Windows,
+}
+
+impl Platform {
+    /// Helper function that returns the platform the program is currently running on.
+    pub fn current() -> Platform {
+        Platform::from_os(env::consts::OS)
+    }
+
+    /// Helper function that maps an operating system name, as reported by `std::env::consts::OS`,
+    /// to a `Platform`. Unknown names are treated as `Platform::Unix`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// assert_eq!(Platform::from_os("macos"), Platform::MacOs);
+    /// assert_eq!(Platform::from_os("windows"), Platform::Windows);
+    /// assert_eq!(Platform::from_os("linux"), Platform::Unix);
+    /// assert_eq!(Platform::from_os("freebsd"), Platform::Unix);
+    /// ```
+    pub fn from_os(os: &str) -> Platform {
+        match os {
+            "macos" | "ios" => Platform::MacOs,
+            "windows" => Platform::Windows,
+            _ => Platform::Unix,
+        }
+    }
+
+    /// Helper function that returns the user's home directory.
+    ///
+    /// This is `USERPROFILE` on Windows and `HOME` everywhere else, passed through
+    /// `resolve_home()`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// assert_eq!(Platform::Unix.home_dir(), resolve_home(env::var_os("HOME")));
+    /// ```
+    pub fn home_dir(&self) -> PathBuf {
+        let var: &str = match self {
+            Platform::Windows => "USERPROFILE",
+            Platform::Unix | Platform::MacOs => "HOME",
+        };
+        resolve_home(env::var_os(var))
+    }
+
+    /// Helper function that returns the possible locations of the configuration file, in order
+    /// of precedence.
+    ///
+    /// `Platform::Unix`:
+    /// 1. `$XDG_CONFIG_HOME/mpb/config.toml`, if `XDG_CONFIG_HOME` is set and not empty.
+    /// 2. `$HOME/.config/mpb/config.toml`
+    ///
+    /// `Platform::MacOs`:
+    /// 1. `$HOME/Library/Application Support/mpb/config.toml`
+    /// 2. `$XDG_CONFIG_HOME/mpb/config.toml`, if `XDG_CONFIG_HOME` is set and not empty.
+    /// 3. `$HOME/.config/mpb/config.toml`
+    ///
+    /// `Platform::Windows`:
+    /// 1. `%APPDATA%\mpb\config.toml`, or `%USERPROFILE%\AppData\Roaming\mpb\config.toml` if
+    ///    `APPDATA` is not set.
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// let candidates: Vec<PathBuf> = Platform::current().config_candidates();
+    /// assert!(candidates.iter().all(|path| path.ends_with("mpb/config.toml")));
+    /// ```
+    pub fn config_candidates(&self) -> Vec<PathBuf> {
+        let mut candidates: Vec<PathBuf> = Vec::new();
+        let home: PathBuf = self.home_dir();
+
+        match self {
+            Platform::Windows => {
+                let appdata: PathBuf = env::var_os("APPDATA")
+                    .filter(|appdata| !appdata.is_empty())
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| home.join("AppData").join("Roaming"));
+                candidates.push(appdata.join("mpb").join("config.toml"));
+                return candidates;
+            }
+            Platform::MacOs => {
+                candidates.push(
+                    home.join("Library")
+                        .join("Application Support")
+                        .join("mpb")
+                        .join("config.toml"),
+                );
+            }
+            Platform::Unix => {}
+        }
+
+        if let Some(xdg) = env::var_os("XDG_CONFIG_HOME").filter(|xdg| !xdg.is_empty()) {
+            candidates.push(PathBuf::from(xdg).join("mpb").join("config.toml"));
+        }
+        candidates.push(home.join(".config").join("mpb").join("config.toml"));
+
+        candidates
+    }
+
+    /// Helper function that returns the default entry point of the Node tree when no
+    /// configuration file is found, which is the `Music` directory in the user's home directory
+    /// on every platform.
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// assert_eq!(Platform::MacOs.default_base(), Platform::MacOs.home_dir().join("Music"));
+    /// ```
+    pub fn default_base(&self) -> PathBuf {
+        self.home_dir().join("Music")
+    }
+}
+
+/// Helper function that turns the value of the home directory environment variable into the
+/// home directory path.
+///
+/// When the variable is not set or empty, the home directory is assumed to be one level above
+/// the current working directory, `..`, so the program still finds a configuration file next to
+/// a checkout of the project.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// assert_eq!(resolve_home(Some(OsString::from("/home/user"))).to_str(), Some("/home/user"));
+/// assert_eq!(resolve_home(None).to_str(), Some(".."));
+/// ```
+pub fn resolve_home(home: Option<OsString>) -> PathBuf {
+    match home.filter(|home| !home.is_empty()) {
+        Some(home) => PathBuf::from(home),
+        None => PathBuf::from(".."),
+    }
+}
+
+/// Helper function that returns `path` in a form the file system calls of the platform accept
+/// whatever its length.
+///
+/// On Windows, paths of `MAX_PATH` minus 12 characters or more are made absolute, with `.` and
+/// `..` resolved, and given the `\\?\` extended-length prefix: `C:\Music\...` becomes
+/// `\\?\C:\Music\...` and `\\server\share\...` becomes `\\?\UNC\server\share\...`. Shorter
+/// paths and paths that already have a prefix are returned as they are, as is `path` on every
+/// other platform.
+///
+/// The Node tree is scanned through this function, so albums nested deeper than `MAX_PATH` are
+/// not left out. The `src` and `art` fields of its entries are always stored without the prefix,
+/// see `strip_long_path()`, and should be passed through `to_long_path()` again by consumers
+/// opening them with APIs that do not handle long paths themselves.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::path::Path;
+/// let short: &Path = Path::new("Music/Artist/Album/1.mp3");
+/// assert_eq!(to_long_path(short), short);
+/// ```
+pub fn to_long_path(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    {
+        use std::path::{Component, Prefix};
+
+        if path.as_os_str().len() < MAX_PATH - 12 {
+            return path.to_path_buf();
+        }
+        let absolute: PathBuf = match path.is_absolute() {
+            true => path.to_path_buf(),
+            false => match env::current_dir() {
+                Ok(current) => current.join(path),
+                Err(_) => return path.to_path_buf(),
+            },
+        };
+
+        let mut components = absolute.components();
+        let mut long: PathBuf = match components.next() {
+            Some(Component::Prefix(prefix)) => match prefix.kind() {
+                Prefix::Disk(drive) => PathBuf::from(format!(r"\\?\{}:", char::from(drive))),
+                Prefix::UNC(server, share) => {
+                    let mut long: OsString = OsString::from(r"\\?\UNC\");
+                    long.push(server);
+                    long.push(r"\");
+                    long.push(share);
+                    PathBuf::from(long)
+                }
+                // Already extended-length, or a device path
+                _ => return path.to_path_buf(),
+            },
+            _ => return path.to_path_buf(),
+        };
+        // Extended-length paths are passed to the file system as they are, unresolved
+        for component in components {
+            match component {
+                Component::RootDir => long.push(r"\"),
+                Component::ParentDir => {
+                    long.pop();
+                }
+                Component::Normal(name) => long.push(name),
+                Component::CurDir | Component::Prefix(_) => {}
+            }
+        }
+        long
+    }
+    #[cfg(not(windows))]
+    path.to_path_buf()
+}
+
+/// Helper function that returns `path` without the `\\?\` extended-length prefix that
+/// `to_long_path()` adds, and that `Path::canonicalize()` adds to every path on Windows, so the
+/// paths stored in a Node tree have the same form whether they were canonicalized or not:
+/// `\\?\C:\Music` becomes `C:\Music` and `\\?\UNC\server\share` becomes `\\server\share`.
+/// Other paths, and every path on other platforms, are returned as they are.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::path::Path;
+/// assert_eq!(strip_long_path(Path::new("/home/user/Music")), Path::new("/home/user/Music"));
+/// ```
+pub fn strip_long_path(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    {
+        use std::path::{Component, Prefix};
+
+        let Some(Component::Prefix(prefix)) = path.components().next() else {
+            return path.to_path_buf();
+        };
+        let Some(text) = path.to_str() else {
+            return path.to_path_buf();
+        };
+        match prefix.kind() {
+            Prefix::VerbatimDisk(_) => PathBuf::from(&text[4..]),
+            Prefix::VerbatimUNC(..) => PathBuf::from(format!(r"\\{}", &text[8..])),
+            _ => path.to_path_buf(),
+        }
+    }
+    #[cfg(not(windows))]
+    path.to_path_buf()
+}
+
+/// Number of temporary files created by `write_atomic()` so far, making their names unique within
+/// the process.
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Helper function that atomically replaces the file at `path` with `contents`, creating any
+/// missing parent directories.
+///
+/// The contents are written and synced to a temporary file next to `path`, named after `path`,
+/// the process and a counter so that concurrent writers never share one, which is then renamed
+/// over `path`. A crash therefore leaves either the previous file or the new one, never a
+/// truncated one. The temporary file is removed when any step fails.
+pub(super) fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let parent: Option<&Path> = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty());
+    if let Some(parent) = parent {
+        create_dir_all(to_long_path(parent))?;
+    }
+
+    let mut tmp: PathBuf = path.to_path_buf();
+    tmp.as_mut_os_string().push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let written: Result<()> = File::create(to_long_path(&tmp))
+        .and_then(|mut file| file.write_all(contents).and_then(|_| file.sync_all()))
+        .and_then(|_| rename(to_long_path(&tmp), to_long_path(path)));
+    if written.is_err() {
+        let _ = remove_file(to_long_path(&tmp));
+        return written;
+    }
+
+    // Sync the directory as well, for the rename itself to survive a crash
+    #[cfg(unix)]
+    if let Ok(dir) = File::open(to_long_path(parent.unwrap_or(Path::new(".")))) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{lock_env, Env};
+    use super::*;
+    use std::fs::{read_dir, read_to_string, remove_dir_all};
+    use std::thread;
+
+    #[test]
+    fn home_dir_follows_platform() {
+        let mut env: Env = lock_env();
+        env.set("HOME", "/home/user");
+        env.set("USERPROFILE", "C:/Users/user");
+        assert_eq!(Platform::Unix.home_dir(), PathBuf::from("/home/user"));
+        assert_eq!(Platform::MacOs.home_dir(), PathBuf::from("/home/user"));
+        assert_eq!(Platform::Windows.home_dir(), PathBuf::from("C:/Users/user"));
+        assert_eq!(
+            Platform::MacOs.default_base(),
+            PathBuf::from("/home/user/Music")
+        );
+
+        env.remove("HOME");
+        assert_eq!(Platform::Unix.home_dir(), PathBuf::from(".."));
+        env.set("HOME", "");
+        assert_eq!(Platform::Unix.home_dir(), PathBuf::from(".."));
+    }
+
+    #[test]
+    fn resolve_home_keeps_a_single_separator() {
+        let home: PathBuf = resolve_home(Some(OsString::from("/home/user/")));
+        assert_eq!(home.join(".config").to_str(), Some("/home/user/.config"));
+        assert_eq!(resolve_home(Some(OsString::new())).to_str(), Some(".."));
+        assert_eq!(resolve_home(None).join("Music").to_str(), Some("../Music"));
+    }
+
+    #[test]
+    fn unix_config_candidates() {
+        let mut env: Env = lock_env();
+        env.set("XDG_CONFIG_HOME", "/etc/xdg");
+        env.set("HOME", "/home/user");
+        assert_eq!(
+            Platform::Unix.config_candidates(),
+            [
+                PathBuf::from("/etc/xdg/mpb/config.toml"),
+                PathBuf::from("/home/user/.config/mpb/config.toml"),
+            ]
+        );
+
+        // An empty XDG_CONFIG_HOME is ignored
+        env.set("XDG_CONFIG_HOME", "");
+        assert_eq!(
+            Platform::Unix.config_candidates(),
+            [PathBuf::from("/home/user/.config/mpb/config.toml")]
+        );
+
+        // Trailing separators in HOME are not repeated
+        env.set("HOME", "/home/user/");
+        let candidates: Vec<PathBuf> = Platform::Unix.config_candidates();
+        assert_eq!(
+            candidates[0].to_str(),
+            Some("/home/user/.config/mpb/config.toml")
+        );
+        assert_eq!(
+            Platform::Unix.default_base().to_str(),
+            Some("/home/user/Music")
+        );
+
+        env.remove("HOME");
+        assert_eq!(
+            Platform::Unix.config_candidates(),
+            [PathBuf::from("../.config/mpb/config.toml")]
+        );
+        assert_eq!(Platform::Unix.default_base().to_str(), Some("../Music"));
+    }
+
+    #[test]
+    fn macos_config_candidates() {
+        let mut env: Env = lock_env();
+        env.set("HOME", "/Users/user");
+        env.remove("XDG_CONFIG_HOME");
+        assert_eq!(
+            Platform::MacOs.config_candidates(),
+            [
+                PathBuf::from("/Users/user/Library/Application Support/mpb/config.toml"),
+                PathBuf::from("/Users/user/.config/mpb/config.toml"),
+            ]
+        );
+
+        env.set("XDG_CONFIG_HOME", "/etc/xdg");
+        assert_eq!(
+            Platform::MacOs.config_candidates()[1],
+            PathBuf::from("/etc/xdg/mpb/config.toml")
+        );
+    }
+
+    #[test]
+    fn windows_config_candidates() {
+        let mut env: Env = lock_env();
+        let roaming: PathBuf = PathBuf::from(r"C:\Users\user\AppData\Roaming");
+        env.set("APPDATA", &roaming);
+        env.set("XDG_CONFIG_HOME", "/etc/xdg");
+        assert_eq!(
+            Platform::Windows.config_candidates(),
+            [roaming.join("mpb").join("config.toml")]
+        );
+
+        env.remove("APPDATA");
+        env.set("USERPROFILE", r"C:\Users\user");
+        let roaming: PathBuf = PathBuf::from(r"C:\Users\user")
+            .join("AppData")
+            .join("Roaming");
+        assert_eq!(
+            Platform::Windows.config_candidates(),
+            [roaming.join("mpb").join("config.toml")]
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn long_paths_get_a_prefix() {
+        let name: String = "a".repeat(100);
+        let long: PathBuf = Path::new(r"C:\Music").join(&name).join(&name).join(&name);
+        assert_eq!(
+            to_long_path(&long).to_str().unwrap(),
+            format!(r"\\?\C:\Music\{name}\{name}\{name}")
+        );
+        let share: PathBuf = Path::new(r"\\nas\music")
+            .join(&name)
+            .join("..")
+            .join(&name)
+            .join(&name)
+            .join(&name);
+        assert_eq!(
+            to_long_path(&share).to_str().unwrap(),
+            format!(r"\\?\UNC\nas\music\{name}\{name}\{name}")
+        );
+        assert_eq!(to_long_path(&to_long_path(&long)), to_long_path(&long));
+        assert_eq!(to_long_path(Path::new(r"C:\Music")), Path::new(r"C:\Music"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn long_path_prefixes_are_stripped() {
+        assert_eq!(
+            strip_long_path(Path::new(r"\\?\C:\Music")),
+            Path::new(r"C:\Music")
+        );
+        assert_eq!(
+            strip_long_path(Path::new(r"\\?\UNC\nas\music")),
+            Path::new(r"\\nas\music")
+        );
+        assert_eq!(
+            strip_long_path(Path::new(r"\\.\COM1")),
+            Path::new(r"\\.\COM1")
+        );
+
+        let long: PathBuf = Path::new(r"C:\Music").join("a".repeat(300));
+        assert_eq!(strip_long_path(&to_long_path(&long)), long);
+    }
+
+    #[test]
+    fn write_atomic_concurrent_writers() {
+        let dir: PathBuf = env::temp_dir().join("vfh-test-write-atomic");
+        let _ = remove_dir_all(&dir);
+        let path: PathBuf = dir.join("nested").join("file.toml");
+
+        let writers: Vec<thread::JoinHandle<()>> = (0..8)
+            .map(|writer| {
+                let path: PathBuf = path.clone();
+                thread::spawn(move || {
+                    for _ in 0..50 {
+                        let contents: String = format!("writer = {}\n", writer).repeat(100);
+                        write_atomic(&path, contents.as_bytes()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        // Every write replaced the file whole, and no temporary file was left behind
+        let contents: String = read_to_string(&path).unwrap();
+        let first: &str = contents.lines().next().unwrap();
+        assert!(contents.lines().all(|line| line == first));
+        assert_eq!(contents.lines().count(), 100);
+        assert_eq!(read_dir(path.parent().unwrap()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn write_atomic_failure_leaves_no_temporary_file() {
+        let dir: PathBuf = env::temp_dir().join("vfh-test-write-atomic-failure");
+        let _ = remove_dir_all(&dir);
+        let path: PathBuf = dir.join("taken");
+        create_dir_all(path.join("child")).unwrap();
+
+        // A directory cannot be replaced by a file
+        assert!(write_atomic(&path, b"contents").is_err());
+        assert_eq!(read_dir(&dir).unwrap().count(), 1);
+    }
+}
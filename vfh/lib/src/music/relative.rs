@@ -0,0 +1,80 @@
+use super::node::Node;
+use super::traverse::for_each_depth_first_mut;
+use std::path::{Path, PathBuf};
+
+/// Helper function that rewrites the `src` and `art` paths of every entry of `node` and its
+/// descendants relative to `base`, so the tree can be sent to the frontend or written to a cache
+/// file without leaking where the library lives, and survives the library being moved.
+///
+/// Paths that are not below `base`, such as inherited cover art or `Config.default_art` living
+/// elsewhere, are left absolute and returned together with the keys of their entry, so they can
+/// be reported. Use `absolutize()` to turn the paths back into absolute ones.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let base: PathBuf = PathBuf::from("/home/alex/Music");
+/// let mut song: Entry = Entry::default();
+/// song.kind = EntryKind::Audio;
+/// song.src = base.join("Artist").join("song.mp3");
+/// song.art = Some(PathBuf::from("/usr/share/mpb/placeholder.png"));
+/// let mut artist: Entry = Entry::default();
+/// artist.src = base.join("Artist");
+/// artist.art = Some(base.join("Artist").join("Artist.png"));
+/// artist.child.insert(String::from("song"), song);
+/// let mut tree: Node = Node::from([(String::from("Artist"), artist)]);
+/// let original: Node = tree.clone();
+///
+/// let outside: Vec<(Vec<String>, PathBuf)> = relativize(&mut tree, &base);
+/// assert_eq!(tree["Artist"].src, Path::new("Artist"));
+/// assert_eq!(tree["Artist"].art, Some(Path::new("Artist").join("Artist.png")));
+/// assert_eq!(tree["Artist"].child["song"].src, Path::new("Artist").join("song.mp3"));
+/// assert_eq!(
+///     outside,
+///     [(
+///         vec![String::from("Artist"), String::from("song")],
+///         PathBuf::from("/usr/share/mpb/placeholder.png")
+///     )]
+/// );
+///
+/// // Rehydrated after the library moved
+/// absolutize(&mut tree, Path::new("/mnt/music"));
+/// assert_eq!(tree["Artist"].child["song"].src, Path::new("/mnt/music/Artist/song.mp3"));
+/// assert_eq!(tree["Artist"].child["song"].art, original["Artist"].child["song"].art);
+/// ```
+pub fn relativize(node: &mut Node, base: &Path) -> Vec<(Vec<String>, PathBuf)> {
+    let mut outside: Vec<(Vec<String>, PathBuf)> = Vec::new();
+
+    for_each_depth_first_mut(node, |keys, entry| {
+        for path in [Some(&mut entry.src), entry.art.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            match path.strip_prefix(base) {
+                Ok(relative) => *path = relative.to_path_buf(),
+                Err(_) => outside.push((
+                    keys.iter().copied().map(String::from).collect(),
+                    path.clone(),
+                )),
+            }
+        }
+    });
+
+    outside
+}
+
+/// Helper function that turns the relative `src` and `art` paths of every entry of `node` and
+/// its descendants, as written by `relativize()`, back into absolute paths below `base`. Paths
+/// that are already absolute are left untouched.
+pub fn absolutize(node: &mut Node, base: &Path) {
+    for_each_depth_first_mut(node, |_, entry| {
+        for path in [Some(&mut entry.src), entry.art.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            if path.is_relative() {
+                *path = base.join(&path);
+            }
+        }
+    });
+}
@@ -0,0 +1,148 @@
+use super::node::{Entry, Node};
+use super::sort::KindOrder;
+use std::fmt::{Result, Write};
+
+/// Options of `render_tree()`.
+///
+/// # Fields
+/// `show_art`: `bool` -> Whether the cover art of every entry is printed after its key.
+/// `show_size`: `bool` -> Whether `Entry.size`, in bytes, is printed before the key of every
+///                        entry.
+/// `max_depth`: `Option<usize>` -> Depth past which entries are left out, where the top-level
+///                                entries are at depth 0. Every entry is printed when unset.
+/// `order`: `KindOrder` -> Whether the directories of every level are printed before its audio
+///                         files.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    pub show_art: bool,
+    pub show_size: bool,
+    pub max_depth: Option<usize>,
+    pub order: KindOrder,
+}
+
+/// Helper function that renders `node` like the `tree` command does, one entry per line with
+/// box-drawing characters leading to it. See `write_tree()` to write into an existing buffer.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let mut tree: Node = Node::new();
+/// let artist: &mut Entry = tree.entry(String::from("Artist")).or_default();
+/// artist.size = 7;
+/// let album: &mut Entry = artist.child.entry(String::from("Album")).or_default();
+/// album.size = 7;
+/// album.art = Some(PathBuf::from("/music/Artist/Album/Album.png"));
+/// for (key, size) in [("1", 3), ("2", 4)] {
+///     let mut track: Entry = Entry::default();
+///     track.kind = EntryKind::Audio;
+///     track.size = size;
+///     album.child.insert(String::from(key), track);
+/// }
+/// tree.insert(String::from("Single"), Entry::default());
+///
+/// let options: RenderOptions = RenderOptions::default();
+/// assert_eq!(
+///     render_tree(&tree, &options),
+///     "\
+/// ├── Artist
+/// │   └── Album
+/// │       ├── 1
+/// │       └── 2
+/// └── Single
+/// "
+/// );
+///
+/// let options: RenderOptions = RenderOptions {
+///     show_art: true,
+///     show_size: true,
+///     max_depth: Some(1),
+///     order: KindOrder::Mixed,
+/// };
+/// assert_eq!(
+///     render_tree(&tree, &options),
+///     "\
+/// ├── [7] Artist
+/// │   └── [7] Album (art: /music/Artist/Album/Album.png)
+/// └── [0] Single
+/// "
+/// );
+///
+/// // Album folders before loose tracks
+/// let mut track: Entry = Entry::default();
+/// track.kind = EntryKind::Audio;
+/// tree.insert(String::from("Intro"), track);
+/// let options: RenderOptions = RenderOptions {
+///     max_depth: Some(0),
+///     order: KindOrder::DirectoriesFirst,
+///     ..RenderOptions::default()
+/// };
+/// assert_eq!(
+///     render_tree(&tree, &options),
+///     "\
+/// ├── Artist
+/// ├── Single
+/// └── Intro
+/// "
+/// );
+/// ```
+pub fn render_tree(node: &Node, opts: &RenderOptions) -> String {
+    let mut rendered: String = String::new();
+    // Writing into a String never fails
+    let _ = write_tree(&mut rendered, node, opts);
+    rendered
+}
+
+/// Helper function that writes `node` into `out` as rendered by `render_tree()`, without
+/// building intermediate Strings.
+pub fn write_tree(out: &mut impl Write, node: &Node, opts: &RenderOptions) -> Result {
+    write_level(out, node, opts, &mut String::new(), 0)
+}
+
+/// Helper function that writes the entries of `node`, at depth `depth`, each line starting with
+/// `prefix`.
+fn write_level(
+    out: &mut impl Write,
+    node: &Node,
+    opts: &RenderOptions,
+    prefix: &mut String,
+    depth: usize,
+) -> Result {
+    if opts.max_depth.is_some_and(|max_depth| depth > max_depth) {
+        return Ok(());
+    }
+
+    let mut entries: Vec<(&String, &Entry)> = node.iter().collect();
+    opts.order.apply(&mut entries);
+    let mut entries = entries.into_iter().peekable();
+    while let Some((key, entry)) = entries.next() {
+        let last: bool = entries.peek().is_none();
+        write_line(out, key, entry, opts, prefix, last)?;
+
+        let indent: &str = if last { "    " } else { "│   " };
+        prefix.push_str(indent);
+        write_level(out, &entry.child, opts, prefix, depth + 1)?;
+        prefix.truncate(prefix.len() - indent.len());
+    }
+    Ok(())
+}
+
+/// Helper function that writes the line of the entry `entry` keyed by `key`.
+fn write_line(
+    out: &mut impl Write,
+    key: &str,
+    entry: &Entry,
+    opts: &RenderOptions,
+    prefix: &str,
+    last: bool,
+) -> Result {
+    let branch: &str = if last { "└── " } else { "├── " };
+    write!(out, "{}{}", prefix, branch)?;
+    if opts.show_size {
+        write!(out, "[{}] ", entry.size)?;
+    }
+    write!(out, "{}", key)?;
+    if let Some(art) = entry.art.as_ref().filter(|_| opts.show_art) {
+        write!(out, " (art: {})", art.display())?;
+    }
+    writeln!(out)
+}
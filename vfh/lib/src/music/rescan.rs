@@ -0,0 +1,157 @@
+use super::config::Config;
+use super::node::Node;
+use super::utils::populate_from;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Guard for the background thread started by `spawn_rescanner()`.
+///
+/// Dropping the guard stops the thread and waits for it to finish, which takes at most as long
+/// as the scan in progress, if any.
+pub struct Rescanner {
+    stop: Option<Sender<()>>,
+    changes: Receiver<()>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Rescanner {
+    /// Returns the channel receiving a message every time a rescan swapped a tree that differs
+    /// from the previous one into the shared tree.
+    pub fn changes(&self) -> &Receiver<()> {
+        &self.changes
+    }
+}
+
+impl Drop for Rescanner {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Helper function that starts a background thread re-running `populate()` on `Config.base`
+/// every `Config.rescan_interval_secs` seconds and swapping the fresh Node tree into `tree`.
+///
+/// The tree is only replaced, and `Rescanner::changes()` notified, when the fresh tree differs
+/// from the shared one. A scan that fails, such as when `Config.base` is no longer a directory
+/// because the drive holding the library was unmounted, leaves the shared tree untouched and is
+/// retried at the next interval. A scan that merely met files or directories it could not read
+/// still replaces the tree, with those directories flagged with `Entry.error` as `populate()`
+/// does, so a single unreadable folder does not keep the rest of the library stale.
+///
+/// When `Config.rescan_interval_secs` is unset, no thread is started. An interval of 0 is
+/// treated as 1 second.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// # use std::sync::{Arc, RwLock};
+/// # use std::time::Duration;
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-rescanner");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Artist")).unwrap();
+///
+/// let config: Config = ConfigBuilder::new(&base)
+///     .rescan_interval_secs(Some(1))
+///     .build()
+///     .unwrap();
+/// let tree: Arc<RwLock<Node>> = Arc::new(RwLock::new(Node::new()));
+/// populate(&base, &mut tree.write().unwrap(), &config).unwrap();
+///
+/// let rescanner: Rescanner = spawn_rescanner(config, Arc::clone(&tree));
+/// write(base.join("Artist").join("Song.mp3"), "").unwrap();
+/// rescanner.changes().recv_timeout(Duration::from_secs(5)).unwrap();
+/// assert!(tree.read().unwrap()["Artist"].child.contains_key("Song"));
+///
+/// // A missing base keeps the previous tree
+/// remove_dir_all(&base).unwrap();
+/// assert!(rescanner.changes().recv_timeout(Duration::from_secs(2)).is_err());
+/// assert!(tree.read().unwrap().contains_key("Artist"));
+/// drop(rescanner);
+/// ```
+pub fn spawn_rescanner(config: Config, tree: Arc<RwLock<Node>>) -> Rescanner {
+    let (stop, stopped) = channel::<()>();
+    let (changed, changes) = channel::<()>();
+    let handle: Option<JoinHandle<()>> = config.rescan_interval_secs.map(|secs| {
+        let interval: Duration = Duration::from_secs(secs.max(1));
+        thread::spawn(move || rescan_loop(&config, &tree, interval, &stopped, &changed))
+    });
+
+    Rescanner {
+        stop: Some(stop),
+        changes,
+        handle,
+    }
+}
+
+/// Helper function that runs the rescans described in `spawn_rescanner()` until `stopped`
+/// disconnects.
+fn rescan_loop(
+    config: &Config,
+    tree: &RwLock<Node>,
+    interval: Duration,
+    stopped: &Receiver<()>,
+    changed: &Sender<()>,
+) {
+    while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+        // Keep the current tree while the base is missing, such as an unmounted drive. Entries
+        // that could not be read are flagged in the fresh tree instead
+        let fresh: Node = match populate_from(&config.base, config) {
+            Ok(fresh) => fresh,
+            Err(_) => continue,
+        };
+
+        let mut shared = tree.write().unwrap_or_else(PoisonError::into_inner);
+        if *shared != fresh {
+            *shared = fresh;
+            drop(shared);
+            let _ = changed.send(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::builder::ConfigBuilder;
+    use super::super::node::ScanIssue;
+    use super::super::testing::{fail, fixture};
+    use super::*;
+    use std::fs::{create_dir_all, write};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn unreadable_dirs_do_not_stop_rescans() {
+        let base = fixture("rescanner-errors");
+        for album in ["Album", "Locked"] {
+            create_dir_all(base.join(album)).unwrap();
+            write(base.join(album).join("1.mp3"), "").unwrap();
+        }
+        fail(&base.join("Locked"), ErrorKind::PermissionDenied);
+        let mut builder: ConfigBuilder = ConfigBuilder::new(&base).rescan_interval_secs(Some(1));
+        #[cfg(unix)]
+        {
+            // A link to itself can never be read either
+            std::os::unix::fs::symlink("loop.mp3", base.join("Album").join("loop.mp3")).unwrap();
+            builder = builder.follow_symlinks(true);
+        }
+        let config: Config = builder.build().unwrap();
+        let tree: Arc<RwLock<Node>> = Arc::new(RwLock::new(populate_from(&base, &config).unwrap()));
+
+        // Changes to the rest of the library still come through
+        let rescanner: Rescanner = spawn_rescanner(config, Arc::clone(&tree));
+        write(base.join("Album").join("2.mp3"), "").unwrap();
+        rescanner
+            .changes()
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap();
+        drop(rescanner);
+        let tree = tree.read().unwrap();
+        assert!(tree["Album"].child.contains_key("2"));
+        assert_eq!(tree["Locked"].error, Some(ScanIssue::PermissionDenied));
+    }
+}
@@ -0,0 +1,163 @@
+use super::node::{Entry, EntryKind, Node};
+use super::traverse::{count_songs, flatten_audio, iter_breadth_first, iter_depth_first};
+
+/// Source of random numbers for `random_leaf()`, `shuffled_leaves()` and
+/// `random_subtree_at_depth()`, supplied by the caller so results can be reproduced.
+///
+/// Implemented for every `FnMut() -> u64`, so any random number generator can be plugged in with
+/// a closure, such as `|| rng.next_u64()`.
+pub trait RandomSource {
+    /// Helper function that returns the next uniformly distributed `u64`.
+    fn next_u64(&mut self) -> u64;
+}
+
+impl<F: FnMut() -> u64> RandomSource for F {
+    fn next_u64(&mut self) -> u64 {
+        self()
+    }
+}
+
+/// Helper function that returns a uniformly distributed index below `len`, which must not be 0.
+///
+/// Values falling in the incomplete range at the top of `u64` are drawn again, so every index is
+/// equally likely.
+fn random_index(rng: &mut impl RandomSource, len: usize) -> usize {
+    let len: u64 = len as u64;
+    let zone: u64 = u64::MAX - u64::MAX % len;
+    loop {
+        let value: u64 = rng.next_u64();
+        if value < zone {
+            return (value % len) as usize;
+        }
+    }
+}
+
+/// Helper function that returns an audio file of `node` or its descendants picked uniformly at
+/// random among all of them, together with the keys leading to it, or `None` if there is none.
+///
+/// Every song is equally likely, whatever the number of songs of the artist or album it belongs
+/// to.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-random-leaf");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Prolific")).unwrap();
+/// create_dir_all(base.join("One Hit")).unwrap();
+/// for song in ["1.mp3", "2.mp3", "3.mp3"] {
+///     write(base.join("Prolific").join(song), "").unwrap();
+/// }
+/// write(base.join("One Hit").join("hit.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// // Cycles through 0, 1, 2, ... so every song comes up once per round
+/// let mut counter: u64 = 0;
+/// let mut rng = || {
+///     counter += 1;
+///     counter - 1
+/// };
+/// let picks: Vec<Vec<String>> = (0..4).map(|_| random_leaf(&tree, &mut rng).unwrap().0).collect();
+/// assert_eq!(
+///     picks,
+///     [["One Hit", "hit"], ["Prolific", "1"], ["Prolific", "2"], ["Prolific", "3"]]
+/// );
+///
+/// assert_eq!(random_leaf(&Node::new(), &mut rng), None);
+/// ```
+pub fn random_leaf<'a>(
+    node: &'a Node,
+    rng: &mut impl RandomSource,
+) -> Option<(Vec<String>, &'a Entry)> {
+    let count: usize = count_songs(node);
+    if count == 0 {
+        return None;
+    }
+
+    let index: usize = random_index(rng, count);
+    let (keys, entry) = iter_depth_first(node)
+        .filter(|(_, entry)| entry.kind == EntryKind::Audio)
+        .nth(index)?;
+    Some((keys.into_iter().map(String::from).collect(), entry))
+}
+
+/// Helper function that returns every audio file of `node` and its descendants, together with the
+/// keys leading to it, in a uniformly random order.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-shuffled-leaves");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Album")).unwrap();
+/// for song in ["1.mp3", "2.mp3", "3.mp3"] {
+///     write(base.join("Album").join(song), "").unwrap();
+/// }
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// let mut rng = || 0;
+/// let shuffled: Vec<(Vec<String>, &Entry)> = shuffled_leaves(&tree, &mut rng);
+/// let keys: Vec<&str> = shuffled.iter().map(|(keys, _)| keys[1].as_str()).collect();
+/// assert_eq!(keys, ["2", "3", "1"]);
+/// ```
+pub fn shuffled_leaves<'a>(
+    node: &'a Node,
+    rng: &mut impl RandomSource,
+) -> Vec<(Vec<String>, &'a Entry)> {
+    let mut leaves: Vec<(Vec<String>, &Entry)> = flatten_audio(node);
+
+    // Fisher-Yates shuffle
+    for last in (1..leaves.len()).rev() {
+        leaves.swap(last, random_index(rng, last + 1));
+    }
+    leaves
+}
+
+/// Helper function that returns a directory of `node` or its descendants at `depth`, where
+/// top-level entries are at depth 0, picked uniformly at random among all of them, together with
+/// the keys leading to it, or `None` if there is none. With artists at the top level, a depth of
+/// 1 picks a random album.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let mut tree: Node = Node::new();
+/// for (artist, album) in [("A", "First"), ("A", "Second"), ("B", "Third")] {
+///     let artist: &mut Entry = tree.entry(String::from(artist)).or_default();
+///     artist.child.insert(String::from(album), Entry::default());
+/// }
+///
+/// let mut rng = || 2;
+/// let (keys, _) = random_subtree_at_depth(&tree, 1, &mut rng).unwrap();
+/// assert_eq!(keys, ["B", "Third"]);
+/// assert_eq!(random_subtree_at_depth(&tree, 2, &mut rng), None);
+/// ```
+pub fn random_subtree_at_depth<'a>(
+    node: &'a Node,
+    depth: usize,
+    rng: &mut impl RandomSource,
+) -> Option<(Vec<String>, &'a Entry)> {
+    let at_depth = || {
+        iter_breadth_first(node)
+            .skip_while(move |(current, _, _)| *current < depth)
+            .take_while(move |(current, _, _)| *current == depth)
+            .filter(|(_, _, entry)| entry.kind == EntryKind::Directory)
+    };
+
+    let count: usize = at_depth().count();
+    if count == 0 {
+        return None;
+    }
+
+    let index: usize = random_index(rng, count);
+    let (_, keys, entry) = at_depth().nth(index)?;
+    Some((keys.into_iter().map(String::from).collect(), entry))
+}
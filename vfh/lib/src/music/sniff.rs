@@ -0,0 +1,92 @@
+use super::platform::to_long_path;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+
+/// Number of bytes read from the start of a file by `sniff_audio()`.
+pub const SNIFF_LENGTH: usize = 512;
+
+/// Brands of the `ftyp` box of the MPEG-4 files holding audio alone, such as `.m4a` and `.m4b`
+/// files.
+const AUDIO_BRANDS: &[&[u8; 4]] = &[b"M4A ", b"M4B ", b"M4P ", b"F4A ", b"F4B "];
+
+/// Helper function that returns the MIME type of the audio container `bytes`, the first bytes of
+/// a file, start with, or `None` if they start with none of the following:
+/// - An ID3v2 tag or an MPEG audio frame sync (`audio/mpeg`).
+/// - `fLaC` (`audio/flac`).
+/// - `OggS` (`audio/ogg`, or `audio/opus` when its first packet is an Opus header).
+/// - `RIFF` followed by `WAVE` (`audio/wav`).
+/// - An MPEG-4 `ftyp` box of an audio brand such as `M4A ` (`audio/mp4`).
+///
+/// The MIME types are those `mime_for_extension()` gives the usual extensions of the same files.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// assert_eq!(sniff_mime(b"ID3\x04\x00\x00\x00\x00\x00\x00"), Some("audio/mpeg"));
+/// assert_eq!(sniff_mime(&[0xFF, 0xFB, 0x90, 0x64]), Some("audio/mpeg"));
+/// assert_eq!(sniff_mime(b"fLaC\x00\x00\x00\x22"), Some("audio/flac"));
+/// assert_eq!(sniff_mime(b"RIFF\x24\x00\x00\x00WAVEfmt "), Some("audio/wav"));
+/// assert_eq!(sniff_mime(b"\x00\x00\x00\x20ftypM4A \x00\x00\x00\x00"), Some("audio/mp4"));
+/// assert_eq!(sniff_mime(b"RIFF\x24\x00\x00\x00AVI LIST"), None);
+/// assert_eq!(sniff_mime(b"\x00\x00\x00\x20ftypisom\x00\x00\x02\x00"), None);
+/// assert_eq!(sniff_mime(b"Just some notes"), None);
+/// assert_eq!(sniff_mime(b""), None);
+/// ```
+pub fn sniff_mime(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [b'I', b'D', b'3', ..] => Some("audio/mpeg"),
+        // 11 set bits of frame sync, then a version other than reserved and a layer other than
+        // reserved, which also rules out AAC ADTS headers
+        [0xFF, second, ..]
+            if second & 0xE0 == 0xE0 && second & 0x18 != 0x08 && second & 0x06 != 0 =>
+        {
+            Some("audio/mpeg")
+        }
+        [b'f', b'L', b'a', b'C', ..] => Some("audio/flac"),
+        [b'O', b'g', b'g', b'S', ..] => match bytes.get(28..36) {
+            Some(b"OpusHead") => Some("audio/opus"),
+            _ => Some("audio/ogg"),
+        },
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some("audio/wav"),
+        [_, _, _, _, b'f', b't', b'y', b'p', brand @ ..]
+            if AUDIO_BRANDS.iter().any(|audio| brand.starts_with(*audio)) =>
+        {
+            Some("audio/mp4")
+        }
+        _ => None,
+    }
+}
+
+/// Helper function that returns the MIME type of the audio file at `path` detected from its
+/// contents with `sniff_mime()`, or `None` if it is not a recognized audio file or cannot be read.
+///
+/// At most `SNIFF_LENGTH` bytes are read, in a single read, so sniffing a large file costs no
+/// more than sniffing a small one.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let dir: PathBuf = env::temp_dir().join("vfh-doc-sniff-audio");
+/// # let _ = remove_dir_all(&dir);
+/// create_dir_all(&dir).unwrap();
+/// write(dir.join("episode"), b"ID3\x04\x00\x00\x00\x00\x00\x00").unwrap();
+/// write(dir.join("notes"), "Show notes").unwrap();
+///
+/// assert_eq!(sniff_audio(&dir.join("episode")), Some("audio/mpeg"));
+/// assert_eq!(sniff_audio(&dir.join("notes")), None);
+/// assert_eq!(sniff_audio(&dir.join("missing")), None);
+/// ```
+pub fn sniff_audio(path: &Path) -> Option<&'static str> {
+    let mut file: File = File::open(to_long_path(path)).ok()?;
+    let mut bytes: [u8; SNIFF_LENGTH] = [0; SNIFF_LENGTH];
+    let read: usize = loop {
+        match file.read(&mut bytes) {
+            Ok(read) => break read,
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => return None,
+        }
+    };
+    sniff_mime(&bytes[..read])
+}
@@ -0,0 +1,267 @@
+use super::node::Entry;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Helper function that compares two keys in natural order, so runs of ASCII digits compare by
+/// their numeric value and `Track 2` comes before `Track 10`. Everything else compares character
+/// by character, like `str::cmp()`.
+///
+/// Keys that are otherwise equal but for the leading zeros of their numbers, such as `01a` and
+/// `1a`, are told apart by the first number whose zeros differ, fewest first, then by
+/// `str::cmp()`, so only identical keys compare equal.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let mut keys: Vec<&str> =
+///     vec!["10 - Song", "Track 10", "2 - Song", "Track 2", "B2", "A10", "A9"];
+/// keys.sort_by(|a, b| natural_cmp(a, b));
+/// assert_eq!(keys, ["2 - Song", "10 - Song", "A9", "A10", "B2", "Track 2", "Track 10"]);
+///
+/// let mut keys: Vec<&str> = vec!["1b", "1a", "01a", "1", "disc1-10", "disc1-9", "disc10-1"];
+/// keys.sort_by(|a, b| natural_cmp(a, b));
+/// assert_eq!(keys, ["1", "1a", "01a", "1b", "disc1-9", "disc1-10", "disc10-1"]);
+/// ```
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars: Peekable<Chars> = a.chars().peekable();
+    let mut b_chars: Peekable<Chars> = b.chars().peekable();
+    let mut zeros: Ordering = Ordering::Equal;
+
+    loop {
+        let order: Ordering = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return zeros.then_with(|| a.cmp(b)),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let a_digits: String = take_digits(&mut a_chars);
+                let b_digits: String = take_digits(&mut b_chars);
+                let a_value: &str = a_digits.trim_start_matches('0');
+                let b_value: &str = b_digits.trim_start_matches('0');
+                zeros = zeros.then_with(|| a_digits.len().cmp(&b_digits.len()));
+
+                a_value
+                    .len()
+                    .cmp(&b_value.len())
+                    .then_with(|| a_value.cmp(b_value))
+            }
+            (Some(x), Some(y)) => {
+                let order: Ordering = x.cmp(y);
+                a_chars.next();
+                b_chars.next();
+                order
+            }
+        };
+
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+}
+
+/// Helper function that consumes the run of ASCII digits at the start of `chars`.
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits: String = String::new();
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        digits.push(digit);
+    }
+    digits
+}
+
+/// Whether the lists of entries produced for the frontend, such as those of `sorted_children()`,
+/// `page()`, `flatten()` and `node_json()`, interleave directories and audio files or list the
+/// directories first, like file managers do. The Node itself always interleaves them in the
+/// order of their keys, which a map cannot change. Deserialized from a snake_case string, like
+/// `PageOrder`.
+///
+/// # Variants
+/// `Mixed` -> Directories and audio files interleaved. This is the default.
+/// `DirectoriesFirst` -> Every directory before the audio files next to it, each group keeping the
+///                       order it would otherwise have.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let mut album: Entry = Entry::default();
+/// for (key, kind) in [("1", EntryKind::Audio), ("Bonus", EntryKind::Directory)] {
+///     album.child.insert(String::from(key), Entry { kind, ..Entry::default() });
+/// }
+///
+/// let mut children: Vec<(&String, &Entry)> = album.child.iter().collect();
+/// KindOrder::DirectoriesFirst.apply(&mut children);
+/// assert_eq!(children.iter().map(|(key, _)| key.as_str()).collect::<Vec<_>>(), ["Bonus", "1"]);
+///
+/// let order: KindOrder = serde_json::from_str("\"directories_first\"").unwrap();
+/// assert_eq!(order, KindOrder::DirectoriesFirst);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KindOrder {
+    #[default]
+    Mixed,
+    DirectoriesFirst,
+}
+
+impl KindOrder {
+    /// Helper function that reorders `entries` as described by `self`, keeping the relative order
+    /// of the directories and of the audio files.
+    pub fn apply<K>(self, entries: &mut [(K, &Entry)]) {
+        if self == KindOrder::DirectoriesFirst {
+            entries.sort_by_key(|(_, entry)| !entry.is_dir());
+        }
+    }
+}
+
+/// Leading articles ignored by `display_sort_key()` unless configured otherwise. See
+/// `Config.articles`.
+pub const DEFAULT_ARTICLES: &[&str] = &["The", "A", "An"];
+
+/// Helper function that returns the key `name` is sorted by for presentation: `name` without the
+/// first of `articles` it starts with, so `The Beatles` sorts under `B`.
+///
+/// Articles match regardless of case and only as a whole word, followed by whitespace, so
+/// `Therapy?` and `Anthrax` are left alone. A name made of nothing but an article is left alone
+/// too.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// assert_eq!(display_sort_key("The Beatles", DEFAULT_ARTICLES), "Beatles");
+/// assert_eq!(display_sort_key("A Tribe Called Quest", DEFAULT_ARTICLES), "Tribe Called Quest");
+/// assert_eq!(display_sort_key("the\u{a0}Who", DEFAULT_ARTICLES), "Who");
+/// assert_eq!(display_sort_key("Therapy?", DEFAULT_ARTICLES), "Therapy?");
+/// assert_eq!(display_sort_key("Anthrax", DEFAULT_ARTICLES), "Anthrax");
+/// assert_eq!(display_sort_key("The", DEFAULT_ARTICLES), "The");
+/// assert_eq!(display_sort_key("Ébène Quartet", &["É"]), "Ébène Quartet");
+/// assert_eq!(display_sort_key("Él Último", &["él"]), "Último");
+/// ```
+pub fn display_sort_key(name: &str, articles: &[&str]) -> String {
+    for article in articles {
+        let mut chars = name.char_indices();
+        let matched: bool = article.chars().all(|expected| {
+            chars.next().is_some_and(|(_, found)| {
+                found == expected || found.to_lowercase().eq(expected.to_lowercase())
+            })
+        });
+        if !matched {
+            continue;
+        }
+
+        let rest: &str = chars.next().map_or("", |(index, _)| &name[index..]);
+        if rest.starts_with(char::is_whitespace) && !rest.trim_start().is_empty() {
+            return String::from(rest.trim_start());
+        }
+    }
+    String::from(name)
+}
+
+/// Helper function that returns the children of `entry` for presentation, in the natural order
+/// of their keys as defined by `natural_cmp()`, ignoring the leading `articles` as described in
+/// `display_sort_key()`. Keys equal once their article is stripped are ordered by the whole key.
+/// With `KindOrder::DirectoriesFirst`, directories come before audio files, each in that order.
+/// The Node itself keeps its own order.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let mut album: Entry = Entry::default();
+/// for key in ["10 - Song", "2 - Song", "1 - Intro"] {
+///     album.child.insert(String::from(key), Entry::default());
+/// }
+/// assert_eq!(album.child.keys().collect::<Vec<_>>(), ["1 - Intro", "10 - Song", "2 - Song"]);
+///
+/// let keys = |children: Vec<(&String, &Entry)>| -> Vec<String> {
+///     children.into_iter().map(|(key, _)| key.clone()).collect()
+/// };
+/// let sorted: Vec<(&String, &Entry)> = sorted_children(&album, &[], KindOrder::Mixed);
+/// assert_eq!(keys(sorted), ["1 - Intro", "2 - Song", "10 - Song"]);
+///
+/// let mut artists: Entry = Entry::default();
+/// let names: [&str; 6] =
+///     ["The Beatles", "Therapy?", "A Tribe Called Quest", "Björk", "Beatles", "Die Ärzte"];
+/// for key in names {
+///     artists.child.insert(String::from(key), Entry::default());
+/// }
+/// let config: Config = toml::from_str("articles = ['Die']").unwrap();
+/// let articles: Vec<&str> = config.sort_articles();
+/// let sorted: Vec<(&String, &Entry)> = sorted_children(&artists, &articles, KindOrder::Mixed);
+/// assert_eq!(
+///     keys(sorted),
+///     ["Beatles", "The Beatles", "Björk", "Therapy?", "A Tribe Called Quest", "Die Ärzte"]
+/// );
+///
+/// // Album folders before loose tracks
+/// let mut artist: Entry = Entry::default();
+/// for (key, kind) in [
+///     ("2 - Single", EntryKind::Audio),
+///     ("Live", EntryKind::Directory),
+///     ("10 - B-Side", EntryKind::Audio),
+///     ("1999", EntryKind::Directory),
+///     ("The Debut", EntryKind::Directory),
+/// ] {
+///     artist.child.insert(String::from(key), Entry { kind, ..Entry::default() });
+/// }
+/// let sorted: Vec<(&String, &Entry)> =
+///     sorted_children(&artist, DEFAULT_ARTICLES, KindOrder::DirectoriesFirst);
+/// assert_eq!(keys(sorted), ["1999", "The Debut", "Live", "2 - Single", "10 - B-Side"]);
+/// ```
+pub fn sorted_children<'a>(
+    entry: &'a Entry,
+    articles: &[&str],
+    order: KindOrder,
+) -> Vec<(&'a String, &'a Entry)> {
+    let mut children: Vec<(String, &String, &Entry)> = entry
+        .child
+        .iter()
+        .map(|(key, child)| (display_sort_key(key, articles), key, child))
+        .collect();
+    children.sort_by(|(a_sort, a, _), (b_sort, b, _)| {
+        natural_cmp(a_sort, b_sort).then_with(|| natural_cmp(a, b))
+    });
+    let mut children: Vec<(&String, &Entry)> = children
+        .into_iter()
+        .map(|(_, key, child)| (key, child))
+        .collect();
+    order.apply(&mut children);
+    children
+}
+
+/// Helper function that returns the children of `entry` ordered by the Unicode Collation
+/// Algorithm for `locale`, for presentation. Unlike the byte order of the Node, case is ignored
+/// unless keys only differ by it, and accented letters sort next to their base letter instead of
+/// after `Z`. Directories come first with `KindOrder::DirectoriesFirst`, as in
+/// `sorted_children()`. The Node itself keeps its own order, so serialized trees are unaffected.
+///
+/// Requires the `collation` feature.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let mut artists: Entry = Entry::default();
+/// for key in ["Zz Top", "Édith Piaf", "edith", "Eagles", "abba", "ABBA", "Ólafur Arnalds"] {
+///     artists.child.insert(String::from(key), Entry::default());
+/// }
+/// assert_eq!(artists.child.keys().last().unwrap(), "Ólafur Arnalds");
+///
+/// let sorted: Vec<(&String, &Entry)> =
+///     sorted_children_collated(&artists, CollationLocale::Root, KindOrder::Mixed);
+/// let keys: Vec<&String> = sorted.into_iter().map(|(key, _)| key).collect();
+/// assert_eq!(
+///     keys,
+///     ["abba", "ABBA", "Eagles", "edith", "Édith Piaf", "Ólafur Arnalds", "Zz Top"]
+/// );
+/// ```
+#[cfg(feature = "collation")]
+pub fn sorted_children_collated(
+    entry: &Entry,
+    locale: feruca::Locale,
+    order: KindOrder,
+) -> Vec<(&String, &Entry)> {
+    let mut collator: feruca::Collator =
+        feruca::Collator::new(feruca::Tailoring::Cldr(locale), true, true);
+    let mut children: Vec<(&String, &Entry)> = entry.child.iter().collect();
+    children.sort_by(|(a, _), (b, _)| collator.collate(a.as_str(), b.as_str()));
+    order.apply(&mut children);
+    children
+}
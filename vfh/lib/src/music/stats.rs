@@ -0,0 +1,220 @@
+use super::config::ConfigError;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Statistics collected while populating a Node tree.
+///
+/// Serializes with `duration` as fractional seconds, for the statistics of a scan to be sent to
+/// a frontend as they are. Displays as a one-line summary.
+///
+/// # Fields
+/// `dirs`: `usize` -> Directories read, including the one the scan started from.
+/// `files`: `usize` -> Audio files inserted into the Node tree.
+/// `skipped_extension`: `usize` -> Files left out of the tree because they are not audio files.
+///                                 See `is_audio_path()`.
+/// `skipped_excluded`: `usize` -> Files and directories left out of the tree because they match
+///                                one of the `Config.exclude` patterns.
+/// `skipped_hidden`: `usize` -> Hidden files and directories left out of the tree because
+///                              `Config.include_hidden` is not set.
+/// `skipped_junk`: `usize` -> Files and directories left out of the tree because they are junk
+///                            left by an operating system and `Config.skip_junk` is set. See
+///                            `is_junk()`.
+/// `skipped_symlinks`: `usize` -> Symbolic links left out of the tree because
+///                                `Config.follow_symlinks` is not set.
+/// `skipped_cycles`: `usize` -> Directories not descended into because they are an ancestor of
+///                              themselves through a symbolic link or a mount.
+/// `skipped_depth`: `usize` -> Directories left out of the tree because they are nested deeper
+///                             than `Config.max_depth`.
+/// `pruned_dirs`: `usize` -> Directories left out of the tree because they contain no audio file
+///                           and `Config.prune_empty_dirs` is set.
+/// `key_collisions`: `Vec<PathBuf>` -> Files and directories whose key was already taken by
+///                                     another entry of the same directory, and were inserted
+///                                     under a disambiguated key. See `populate()`.
+/// `case_collisions`: `Vec<PathBuf>` -> Files and directories left out of the tree because their
+///                                      name only differs in case from an earlier entry of the
+///                                      same directory, when `Config.fold_case` is set.
+/// `empty_files`: `Vec<PathBuf>` -> Audio files left out of the tree because their size is 0
+///                                  bytes and `Config.skip_empty_files` is set.
+/// `broken_symlinks`: `Vec<PathBuf>` -> Symbolic links left out of the tree because their target
+///                                      does not exist, when `Config.follow_symlinks` is set.
+/// `errors`: `Vec<ScanError>` -> File system errors that left an entry out of the tree, or a
+///                              directory without children flagged with `Entry.error`, because
+///                              `Config.abort_on_error` is not set.
+/// `invalid_overrides`: `Vec<ConfigError>` -> Per-directory override files that could not be
+///                                            read and were ignored.
+/// `limit_reached`: `bool` -> Whether the scan stopped early because the Node tree reached
+///                           `Config.max_entries` entries, parallel and async scans included.
+/// `duration`: `Duration` -> Wall-clock time the scan took.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-scan-stats");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Album")).unwrap();
+/// for file in ["Album/1.mp3", "Album/2.flac", "Album/notes.txt", ".hidden.mp3"] {
+///     write(base.join(file), "").unwrap();
+/// }
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// let stats: ScanStats = populate(&base, &mut tree, &config).unwrap();
+/// assert_eq!((stats.dirs, stats.files), (2, 2));
+/// assert_eq!((stats.skipped_extension, stats.skipped_hidden), (1, 1));
+///
+/// let summary: String = stats.to_string();
+/// assert!(summary.starts_with("2 directories, 2 audio files, skipped 1 by extension, 1 hidden,"));
+///
+/// let json: serde_json::Value = serde_json::to_value(&stats).unwrap();
+/// assert_eq!(json["files"], 2);
+/// assert!(json["duration"].is_f64());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ScanStats {
+    pub dirs: usize,
+    pub files: usize,
+    pub skipped_extension: usize,
+    pub skipped_excluded: usize,
+    pub skipped_hidden: usize,
+    pub skipped_junk: usize,
+    pub skipped_symlinks: usize,
+    pub skipped_cycles: usize,
+    pub skipped_depth: usize,
+    pub pruned_dirs: usize,
+    pub key_collisions: Vec<PathBuf>,
+    pub case_collisions: Vec<PathBuf>,
+    pub empty_files: Vec<PathBuf>,
+    pub broken_symlinks: Vec<PathBuf>,
+    pub errors: Vec<ScanError>,
+    pub invalid_overrides: Vec<ConfigError>,
+    pub limit_reached: bool,
+    #[serde(serialize_with = "serialize_seconds")]
+    pub duration: Duration,
+}
+
+impl ScanStats {
+    /// Helper function that adds the counts and lists of `other`, collected by another scan, to
+    /// those of `self`.
+    pub(super) fn add(&mut self, other: ScanStats) {
+        self.dirs += other.dirs;
+        self.files += other.files;
+        self.skipped_extension += other.skipped_extension;
+        self.skipped_excluded += other.skipped_excluded;
+        self.skipped_hidden += other.skipped_hidden;
+        self.skipped_junk += other.skipped_junk;
+        self.skipped_symlinks += other.skipped_symlinks;
+        self.skipped_cycles += other.skipped_cycles;
+        self.skipped_depth += other.skipped_depth;
+        self.pruned_dirs += other.pruned_dirs;
+        self.key_collisions.extend(other.key_collisions);
+        self.case_collisions.extend(other.case_collisions);
+        self.empty_files.extend(other.empty_files);
+        self.broken_symlinks.extend(other.broken_symlinks);
+        self.errors.extend(other.errors);
+        self.invalid_overrides.extend(other.invalid_overrides);
+        self.limit_reached |= other.limit_reached;
+        self.duration += other.duration;
+    }
+}
+
+impl Display for ScanStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} directories, {} audio files, skipped {} by extension, {} hidden, {} excluded, \
+             {} symlinks, {} errors",
+            self.dirs,
+            self.files,
+            self.skipped_extension,
+            self.skipped_hidden,
+            self.skipped_excluded,
+            self.skipped_symlinks,
+            self.errors.len(),
+        )?;
+        if self.limit_reached {
+            write!(f, ", stopped at the entry limit")?;
+        }
+        write!(f, " in {:.2}s", self.duration.as_secs_f64())
+    }
+}
+
+/// A file system error met while populating a Node tree. See `populate()`.
+///
+/// # Fields
+/// `path`: `PathBuf` -> The file or directory that could not be read.
+/// `kind`: `ErrorKind` -> The kind of the `io::Error` returned when reading `path`.
+///
+/// Serializes as `{ "path": ..., "kind": ... }`, with the description of `kind`, such as
+/// `"permission denied"`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanError {
+    pub path: PathBuf,
+    pub kind: ErrorKind,
+}
+
+impl Serialize for ScanError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ScanError", 2)?;
+        state.serialize_field("path", &self.path)?;
+        state.serialize_field("kind", &self.kind.to_string())?;
+        state.end()
+    }
+}
+
+/// Progress of a scan, handed to the callback of `populate_with_progress()`.
+///
+/// # Fields
+/// `dirs`: `usize` -> Directories visited so far, including the one the scan started from.
+/// `files`: `usize` -> Audio files inserted into the Node tree so far.
+/// `path`: `&Path` -> The directory about to be read, or the one the scan started from once it
+///                    is done.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScanProgress<'a> {
+    pub dirs: usize,
+    pub files: usize,
+    pub path: &'a Path,
+}
+
+/// Progress of `find_duplicates_with_progress()`.
+///
+/// # Fields
+/// `hashed_files`: `usize` -> Files hashed so far, or found unreadable.
+/// `total_files`: `usize` -> Files sharing their size with another one, which are all hashed.
+/// `hashed_bytes`: `u64` -> Sum of the sizes of the files hashed so far.
+/// `total_bytes`: `u64` -> Sum of the sizes of the files to hash.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct HashProgress {
+    pub hashed_files: usize,
+    pub total_files: usize,
+    pub hashed_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// Outcome of a call to `refresh()`.
+///
+/// # Fields
+/// `rescanned_dirs`: `usize` -> Directories whose contents were read again, including the one
+///                              `refresh()` was called on and the ones new to the tree.
+/// `skipped_dirs`: `usize` -> Directories left as they were, because they did not change since
+///                            they were last read.
+/// `added_files`: `usize` -> Audio files that were not in the Node tree before.
+/// `removed_files`: `usize` -> Audio files of the Node tree that are gone.
+/// `stats`: `ScanStats` -> Statistics collected while reading the rescanned directories.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RefreshReport {
+    pub rescanned_dirs: usize,
+    pub skipped_dirs: usize,
+    pub added_files: usize,
+    pub removed_files: usize,
+    pub stats: ScanStats,
+}
+
+/// Helper function that serializes `ScanStats.duration` as fractional seconds.
+fn serialize_seconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    duration.as_secs_f64().serialize(serializer)
+}
@@ -0,0 +1,99 @@
+use super::platform::strip_long_path;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs::{create_dir_all, remove_dir_all};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// Directories that tests made unreadable, whoever runs them, with the kind of error listing them
+/// fails with. Listing one of those that fail with `ErrorKind::TimedOut` hangs for `HANG`
+/// instead, longer than the shortest `Config.read_timeout_secs`.
+static FAILURES: Mutex<Vec<(PathBuf, ErrorKind)>> = Mutex::new(Vec::new());
+
+/// Lock held by the tests that read or change environment variables, which every test of the
+/// process shares.
+static ENV: Mutex<()> = Mutex::new(());
+
+/// Time listing a directory that fails with `ErrorKind::TimedOut` hangs for.
+pub(super) const HANG: Duration = Duration::from_secs(3);
+
+/// Helper function that returns an empty directory for the test called `name`, canonicalized
+/// like `populate()` does.
+pub(super) fn fixture(name: &str) -> PathBuf {
+    let dir: PathBuf = env::temp_dir().join(format!("vfh-test-{}", name));
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+    strip_long_path(&dir.canonicalize().unwrap())
+}
+
+/// Helper function that makes listing `dir` fail with an error of kind `kind`.
+pub(super) fn fail(dir: &Path, kind: ErrorKind) {
+    let mut failures = FAILURES.lock().unwrap_or_else(PoisonError::into_inner);
+    failures.push((dir.to_path_buf(), kind));
+}
+
+/// Helper function that makes listing `dir` succeed again.
+pub(super) fn recover(dir: &Path) {
+    let mut failures = FAILURES.lock().unwrap_or_else(PoisonError::into_inner);
+    failures.retain(|(failing, _)| failing != dir);
+}
+
+/// Helper function that returns the kind of error listing `dir` fails with, if a test made it
+/// fail.
+pub(super) fn failure(dir: &Path) -> Option<ErrorKind> {
+    let failures = FAILURES.lock().unwrap_or_else(PoisonError::into_inner);
+    failures
+        .iter()
+        .find(|(failing, _)| failing == dir)
+        .map(|(_, kind)| *kind)
+}
+
+/// Environment variables of a test, which no other test reads or changes while it is held.
+/// Variables set or removed through it get their previous value back when it is dropped.
+pub(super) struct Env {
+    saved: Vec<(&'static str, Option<OsString>)>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+/// Helper function that waits for the other tests to be done with the environment variables
+/// and returns them.
+pub(super) fn lock_env() -> Env {
+    Env {
+        saved: Vec::new(),
+        _lock: ENV.lock().unwrap_or_else(PoisonError::into_inner),
+    }
+}
+
+impl Env {
+    /// Helper function that sets the environment variable `name` to `value`.
+    pub(super) fn set(&mut self, name: &'static str, value: impl AsRef<OsStr>) {
+        self.save(name);
+        env::set_var(name, value);
+    }
+
+    /// Helper function that removes the environment variable `name`.
+    pub(super) fn remove(&mut self, name: &'static str) {
+        self.save(name);
+        env::remove_var(name);
+    }
+
+    /// Helper function that records the value of `name` the first time it is changed.
+    fn save(&mut self, name: &'static str) {
+        if self.saved.iter().all(|(saved, _)| *saved != name) {
+            self.saved.push((name, env::var_os(name)));
+        }
+    }
+}
+
+impl Drop for Env {
+    fn drop(&mut self) {
+        for (name, value) in self.saved.drain(..).rev() {
+            match value {
+                Some(value) => env::set_var(name, value),
+                None => env::remove_var(name),
+            }
+        }
+    }
+}
@@ -0,0 +1,827 @@
+use super::node::{Entry, EntryKind, Node};
+use super::page::walk;
+use super::platform::strip_long_path;
+use super::sort::KindOrder;
+use super::utils::{entry_key, find_child, fold_key, latest_modified, normalize_key};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{btree_map, BTreeMap, VecDeque};
+use std::path::{is_separator, Component, Path, PathBuf};
+
+/// Iterator over every entry of a Node tree, returned by `iter_depth_first()`.
+pub struct DepthFirst<'a> {
+    stack: Vec<btree_map::Iter<'a, String, Entry>>,
+    keys: Vec<&'a str>,
+}
+
+impl<'a> Iterator for DepthFirst<'a> {
+    type Item = (Vec<&'a str>, &'a Entry);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let depth: usize = self.stack.len().checked_sub(1)?;
+            match self.stack[depth].next() {
+                Some((key, entry)) => {
+                    self.keys.truncate(depth);
+                    self.keys.push(key);
+                    self.stack.push(entry.child.iter());
+                    return Some((self.keys.clone(), entry));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Helper function that returns an iterator over every entry of `node` and its descendants,
+/// together with the keys leading to the entry from `node`.
+///
+/// Entries are visited depth-first, each one before its children, and siblings in the sorted
+/// order of their keys. An explicit stack is used, so arbitrarily deep trees are traversed without
+/// recursion.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-iter-depth-first");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("B").join("Album")).unwrap();
+/// create_dir_all(base.join("A")).unwrap();
+/// write(base.join("B").join("Album").join("2.mp3"), "").unwrap();
+/// write(base.join("B").join("Album").join("1.mp3"), "").unwrap();
+/// write(base.join("A").join("3.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// let paths: Vec<Vec<&str>> = iter_depth_first(&tree).map(|(keys, _)| keys).collect();
+/// assert_eq!(
+///     paths,
+///     [
+///         vec!["A"],
+///         vec!["A", "3"],
+///         vec!["B"],
+///         vec!["B", "Album"],
+///         vec!["B", "Album", "1"],
+///         vec!["B", "Album", "2"],
+///     ]
+/// );
+///
+/// // Flat track list
+/// let queue: Vec<&PathBuf> = iter_depth_first(&tree)
+///     .filter(|(_, entry)| entry.kind == EntryKind::Audio)
+///     .map(|(_, entry)| &entry.src)
+///     .collect();
+/// assert_eq!(queue.len(), 3);
+/// ```
+///
+/// Deep trees do not overflow the call stack:
+/// ```
+/// # use vfh::music::*;
+/// let mut tree: Node = Node::new();
+/// let mut node: &mut Node = &mut tree;
+/// for _ in 0..2000 {
+///     node = &mut node.entry(String::from("d")).or_default().child;
+/// }
+/// assert_eq!(iter_depth_first(&tree).count(), 2000);
+/// assert_eq!(iter_depth_first(&tree).last().unwrap().0.len(), 2000);
+/// ```
+pub fn iter_depth_first(node: &Node) -> DepthFirst<'_> {
+    DepthFirst {
+        stack: vec![node.iter()],
+        keys: Vec::new(),
+    }
+}
+
+/// Helper function that calls `f` on every entry of `node` and its descendants, in the order of
+/// `iter_depth_first()`, with mutable access to the entry for in-place fixups.
+///
+/// `f` is called on an entry before its children are visited, so children added or removed by
+/// `f` are visited accordingly.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-for-each-depth-first-mut");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Artist").join("Album")).unwrap();
+/// write(base.join("Artist").join("Album").join("song.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// for_each_depth_first_mut(&mut tree, |keys: &[&str], entry: &mut Entry| {
+///     if entry.art.is_none() {
+///         entry.art = Some(PathBuf::from(format!("/placeholders/{}.png", keys.len())));
+///     }
+/// });
+/// let song: &Entry = &tree["Artist"].child["Album"].child["song"];
+/// assert_eq!(song.art, Some(PathBuf::from("/placeholders/3.png")));
+/// ```
+pub fn for_each_depth_first_mut(node: &mut Node, mut f: impl FnMut(&[&str], &mut Entry)) {
+    let mut stack: Vec<btree_map::IterMut<'_, String, Entry>> = vec![node.iter_mut()];
+    let mut keys: Vec<&str> = Vec::new();
+
+    while let Some(depth) = stack.len().checked_sub(1) {
+        match stack[depth].next() {
+            Some((key, entry)) => {
+                keys.truncate(depth);
+                keys.push(key);
+                f(&keys, entry);
+                stack.push(entry.child.iter_mut());
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+}
+
+/// Iterator over every entry of a Node tree, returned by `iter_breadth_first()`.
+pub struct BreadthFirst<'a> {
+    queue: VecDeque<(usize, Vec<&'a str>, btree_map::Iter<'a, String, Entry>)>,
+}
+
+impl<'a> Iterator for BreadthFirst<'a> {
+    type Item = (usize, Vec<&'a str>, &'a Entry);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (depth, parent, entries) = self.queue.front_mut()?;
+            match entries.next() {
+                Some((key, entry)) => {
+                    let depth: usize = *depth;
+                    let mut keys: Vec<&str> = parent.clone();
+                    keys.push(key);
+                    if !entry.child.is_empty() {
+                        self.queue
+                            .push_back((depth + 1, keys.clone(), entry.child.iter()));
+                    }
+                    return Some((depth, keys, entry));
+                }
+                None => {
+                    self.queue.pop_front();
+                }
+            }
+        }
+    }
+}
+
+/// Helper function that returns an iterator over every entry of `node` and its descendants,
+/// level by level, together with the depth of the entry and the keys leading to it from `node`.
+///
+/// The entries directly in `node` are at depth 0. Within a level, entries are visited in the
+/// order of their parents, and siblings in the sorted order of their keys. The iterator is lazy:
+/// it only holds the children of the entries already visited that have not been read yet, so
+/// taking the first levels does not walk the rest of the tree.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-iter-breadth-first");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("B").join("Album")).unwrap();
+/// create_dir_all(base.join("A")).unwrap();
+/// write(base.join("B").join("Album").join("1.mp3"), "").unwrap();
+/// write(base.join("A").join("3.mp3"), "").unwrap();
+/// write(base.join("intro.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// let visits: Vec<(usize, Vec<&str>)> =
+///     iter_breadth_first(&tree).map(|(depth, keys, _)| (depth, keys)).collect();
+/// assert_eq!(
+///     visits,
+///     [
+///         (0, vec!["A"]),
+///         (0, vec!["B"]),
+///         (0, vec!["intro"]),
+///         (1, vec!["A", "3"]),
+///         (1, vec!["B", "Album"]),
+///         (2, vec!["B", "Album", "1"]),
+///     ]
+/// );
+///
+/// // The first two levels only
+/// let top: usize = iter_breadth_first(&tree).take_while(|(depth, _, _)| *depth < 2).count();
+/// assert_eq!(top, 5);
+/// ```
+pub fn iter_breadth_first(node: &Node) -> BreadthFirst<'_> {
+    BreadthFirst {
+        queue: VecDeque::from([(0, Vec::new(), node.iter())]),
+    }
+}
+
+/// Helper function that returns the Entry of `node` whose `src` is `target`, where `node` was
+/// populated from `base`.
+///
+/// Instead of scanning the whole tree, the tree is descended along the components of `target`
+/// relative to `base`, deriving each key like `populate()` does, so the extension stripped from
+/// the keys of files and the keys disambiguated after a collision are accounted for.
+///
+/// Returns `None` for paths outside of `base`, paths with `..` components or a trailing
+/// separator, and paths that are not in the tree, such as skipped non-audio files. A `target`
+/// that only differs from the `src` of an entry by its Unicode normalization, such as a name
+/// typed in NFC for a file written in NFD on macOS, finds that entry. See `normalize_key()`.
+/// When `fold` is set, as it should be for a tree populated with `Config.fold_case` set, a
+/// `target` whose names only differ in case from those of a single entry at each level finds
+/// that entry too, as on a case-insensitive file system. See `fold_key()`.
+///
+/// When `base` is a symbolic link, `base` and `target` may each be given through the link or
+/// through the directory it points to, whether `node` was populated with
+/// `Config.canonicalize_base` set or not. The file system is only looked at when `target` is
+/// not found as given.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-find-by-path");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Artist").join("Album")).unwrap();
+/// write(base.join("Artist").join("Album").join("song.mp3"), "").unwrap();
+/// write(base.join("Artist").join("Album").join("song.txt"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// let song: PathBuf = base.join("Artist").join("Album").join("song.mp3");
+/// assert_eq!(find_by_path(&tree, &base, &song, false).unwrap().src, song);
+///
+/// let album: PathBuf = base.join("Artist").join("Album");
+/// assert_eq!(find_by_path(&tree, &base, &album, false).unwrap().src, album);
+///
+/// // Skipped files, trailing separators and paths outside of base
+/// assert_eq!(find_by_path(&tree, &base, &album.join("song.txt"), false), None);
+/// assert_eq!(find_by_path(&tree, &base, &album.join("missing.mp3"), false), None);
+/// let trailing: PathBuf = PathBuf::from(format!("{}/", album.display()));
+/// assert_eq!(find_by_path(&tree, &base, &trailing, false), None);
+/// assert_eq!(find_by_path(&tree, &base, &env::temp_dir().join("song.mp3"), false), None);
+/// assert_eq!(find_by_path(&tree, &base, &base, false), None);
+///
+/// // Names in another case are only found in folded trees
+/// let upper: PathBuf = base.join("ARTIST").join("Album").join("Song.mp3");
+/// assert_eq!(find_by_path(&tree, &base, &upper, false), None);
+/// assert_eq!(find_by_path(&tree, &base, &upper, true).unwrap().src, song);
+/// ```
+pub fn find_by_path<'a>(
+    node: &'a Node,
+    base: &Path,
+    target: &Path,
+    fold: bool,
+) -> Option<&'a Entry> {
+    locate(node, base, target, fold).map(|(_, entry)| entry)
+}
+
+/// Helper function that removes the Entry of `node` whose `src` is `target` and returns it, where
+/// `node` was populated from `base`. Removing a directory removes its whole subtree.
+///
+/// The entry is located like `find_by_path()` does with `fold`, and nothing is removed when it
+/// returns `None`.
+/// The `size` and `modified` fields of the ancestors of the removed entry are recomputed from
+/// their remaining children. When `prune` is set, ancestor directories left without children are
+/// removed as well.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-remove-by-path");
+/// # let _ = remove_dir_all(&base);
+/// let album: PathBuf = base.join("Artist").join("Album");
+/// create_dir_all(&album).unwrap();
+/// create_dir_all(base.join("Other").join("Album")).unwrap();
+/// write(album.join("song.mp3"), "").unwrap();
+/// write(base.join("Other").join("Album").join("song.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// // Missing entries are left alone
+/// assert_eq!(remove_by_path(&mut tree, &base, &album.join("missing.mp3"), true, false), None);
+///
+/// // Keeping empty ancestors
+/// let mut kept: Node = tree.clone();
+/// let song: Entry =
+///     remove_by_path(&mut kept, &base, &album.join("song.mp3"), false, false).unwrap();
+/// assert_eq!(song.src, album.join("song.mp3"));
+/// assert!(kept["Artist"].child["Album"].child.is_empty());
+///
+/// // Pruning empty ancestors
+/// remove_by_path(&mut tree, &base, &album.join("song.mp3"), true, false).unwrap();
+/// assert_eq!(tree.keys().collect::<Vec<_>>(), ["Other"]);
+///
+/// // Whole subtrees
+/// let other: Entry = remove_by_path(&mut tree, &base, &base.join("Other"), true, false).unwrap();
+/// assert_eq!(other.child["Album"].child.keys().collect::<Vec<_>>(), ["song"]);
+/// assert!(tree.is_empty());
+/// ```
+pub fn remove_by_path(
+    node: &mut Node,
+    base: &Path,
+    target: &Path,
+    prune: bool,
+    fold: bool,
+) -> Option<Entry> {
+    let (keys, entry) = locate(node, base, target, fold)?;
+    let keys: Vec<String> = keys.into_iter().map(String::from).collect();
+    let src: PathBuf = entry.src.clone();
+    remove_under(node, &keys, &src, prune)
+}
+
+/// Helper function that performs `remove_by_path()` on `node`, following `keys` from it.
+fn remove_under(node: &mut Node, keys: &[String], target: &Path, prune: bool) -> Option<Entry> {
+    let (key, rest) = keys.split_first()?;
+    if rest.is_empty() {
+        if node.get(key)?.src != target {
+            return None;
+        }
+        return node.remove(key);
+    }
+
+    let parent: &mut Entry = node.get_mut(key)?;
+    let removed: Entry = remove_under(&mut parent.child, rest, target, prune)?;
+    parent.size = parent.child.values().map(|child| child.size).sum();
+    parent.modified = latest_modified(&parent.child);
+    if prune && parent.child.is_empty() {
+        node.remove(key);
+    }
+    Some(removed)
+}
+
+/// Helper function that returns the Entry of `node` whose `src` is `target` together with the
+/// keys leading to it, as described in `find_by_path()`.
+fn locate<'a>(
+    node: &'a Node,
+    base: &Path,
+    target: &Path,
+    fold: bool,
+) -> Option<(Vec<&'a str>, &'a Entry)> {
+    let trailing: bool = target
+        .as_os_str()
+        .as_encoded_bytes()
+        .last()
+        .is_some_and(|byte| is_separator(char::from(*byte)));
+    if trailing {
+        return None;
+    }
+
+    locate_below(node, base, target, fold).or_else(|| {
+        // The tree may hold the canonical form of the paths given, or the other way around
+        let canonical: PathBuf = strip_long_path(&base.canonicalize().ok()?);
+        let relative: PathBuf = relative_to_base(target, base, &canonical)?;
+        locate_below(node, &canonical, &canonical.join(&relative), fold)
+            .or_else(|| locate_below(node, base, &base.join(&relative), fold))
+    })
+}
+
+/// Helper function that returns the path of `target` relative to `base`, whose canonical form is
+/// `canonical`, whether `target` starts with `base`, with `canonical`, or with another path to
+/// the same directory. `target` itself may not exist, as long as its parent directory does.
+fn relative_to_base(target: &Path, base: &Path, canonical: &Path) -> Option<PathBuf> {
+    if let Ok(relative) = target.strip_prefix(base).or(target.strip_prefix(canonical)) {
+        return Some(relative.to_path_buf());
+    }
+    let parent: PathBuf = strip_long_path(&target.parent()?.canonicalize().ok()?);
+    Some(
+        parent
+            .strip_prefix(canonical)
+            .ok()?
+            .join(target.file_name()?),
+    )
+}
+
+/// Helper function that locates the Entry of `node` whose `src` is `target` like `locate()`
+/// does, without looking at the file system.
+fn locate_below<'a>(
+    node: &'a Node,
+    base: &Path,
+    target: &Path,
+    fold: bool,
+) -> Option<(Vec<&'a str>, &'a Entry)> {
+    let relative: &Path = target.strip_prefix(base).ok()?;
+    let mut path: PathBuf = base.to_path_buf();
+    let mut keys: Vec<&str> = Vec::new();
+    let mut entry: Option<&Entry> = None;
+    for component in relative.components() {
+        let Component::Normal(name) = component else {
+            return None;
+        };
+        path.push(name);
+        let children: &Node = entry.map_or(node, |entry| &entry.child);
+        let (key, child) = find_child(children, &path)
+            .or_else(|| find_folded(children, &path).filter(|_| fold))?;
+        // The rest of `target` is looked up below the name found, in whichever case it has
+        path.clone_from(&child.src);
+        keys.push(key);
+        entry = Some(child);
+    }
+
+    Some((keys, entry?))
+}
+
+/// Helper function that returns the Entry reached by following `keys` from `node`, such as
+/// `["Artist", "Album", "Track"]`, or `None` if any key in the chain is missing or `keys` is
+/// empty. See `Tree::get_nested()` to also resolve the root.
+///
+/// A key missing from the tree is looked up again normalized with `normalize_key()`, so keys
+/// typed in any Unicode normalization find the entries of a tree with normalized keys. When
+/// `fold` is set, as it should be for a tree populated with `Config.fold_case` set, a key still
+/// missing then finds the only entry whose key folds to the same with `fold_key()`, so keys
+/// typed in any case find its entries.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let mut tree: Node = Node::new();
+/// let album: &mut Entry = tree.entry(String::from("Artist")).or_default();
+/// album.child.insert(String::from("Album"), Entry::default());
+///
+/// assert!(get_nested(&tree, &["Artist", "Album"], false).is_some());
+/// assert_eq!(get_nested(&tree, &["Artist", "Missing"], false), None);
+/// assert_eq!(get_nested(&tree, &[], false), None);
+///
+/// get_nested_mut(&mut tree, &["Artist", "Album"], false).unwrap().kind = EntryKind::Audio;
+/// assert_eq!(tree["Artist"].child["Album"].kind, EntryKind::Audio);
+///
+/// // Keys in another case, found only in folded trees
+/// assert_eq!(get_nested(&tree, &["artist", "ALBUM"], false), None);
+/// let album: &Entry = &tree["Artist"].child["Album"];
+/// assert_eq!(get_nested(&tree, &["artist", "ALBUM"], true), Some(album));
+///
+/// // Siblings that only differ in case are told apart by their exact keys
+/// tree.insert(String::from("artist"), Entry::default());
+/// assert_eq!(get_nested(&tree, &["artist"], false), Some(&tree["artist"]));
+/// assert_eq!(get_nested(&tree, &["ARTIST"], true), None);
+/// ```
+pub fn get_nested<'a>(node: &'a Node, keys: &[&str], fold: bool) -> Option<&'a Entry> {
+    let (first, rest) = keys.split_first()?;
+    let mut entry: &Entry = get_child(node, first, fold)?;
+
+    for key in rest {
+        entry = get_child(&entry.child, key, fold)?;
+    }
+    Some(entry)
+}
+
+/// Helper function that behaves like `get_nested()`, returning a mutable reference.
+pub fn get_nested_mut<'a>(node: &'a mut Node, keys: &[&str], fold: bool) -> Option<&'a mut Entry> {
+    let (first, rest) = keys.split_first()?;
+    let mut entry: &mut Entry = get_child_mut(node, first, fold)?;
+
+    for key in rest {
+        entry = get_child_mut(&mut entry.child, key, fold)?;
+    }
+    Some(entry)
+}
+
+/// Helper function that returns the child of `node` stored under `key`, under `key` normalized
+/// with `normalize_key()`, or, when `fold` is set, under the only key of `node` folding to the
+/// same as `key`.
+fn get_child<'a>(node: &'a Node, key: &str, fold: bool) -> Option<&'a Entry> {
+    node.get(key)
+        .or_else(|| node.get(&normalize_key(key)))
+        .or_else(|| node.get(folded_key(node, key).filter(|_| fold)?))
+}
+
+/// Helper function that behaves like `get_child()`, returning a mutable reference.
+fn get_child_mut<'a>(node: &'a mut Node, key: &str, fold: bool) -> Option<&'a mut Entry> {
+    let normalized: String = normalize_key(key);
+    let key: String = match (node.contains_key(key), node.contains_key(&normalized)) {
+        (true, _) => key.to_string(),
+        (false, true) => normalized,
+        (false, false) if fold => folded_key(node, key)?.clone(),
+        (false, false) => return None,
+    };
+    node.get_mut(&key)
+}
+
+/// Helper function that returns the only key of `node` that folds to the same key as `key` with
+/// `fold_key()`, or `None` if there is none or several.
+fn folded_key<'a>(node: &'a Node, key: &str) -> Option<&'a String> {
+    let key: String = fold_key(key);
+    only(node.keys().filter(|other| fold_key(other) == key))
+}
+
+/// Helper function that returns the key and Entry of the only child of `node` whose file name
+/// folds to the same key as that of `path` with `fold_key()`, or `None` if there is none or
+/// several.
+fn find_folded<'a>(node: &'a Node, path: &Path) -> Option<(&'a String, &'a Entry)> {
+    let name: String = fold_key(&entry_key(path.file_name()?));
+    only(node.iter().filter(|(_, entry)| {
+        entry
+            .src
+            .file_name()
+            .is_some_and(|other| fold_key(&entry_key(other)) == name)
+    }))
+}
+
+/// Helper function that returns the only item of `items`, or `None` if there is none or several.
+fn only<T>(mut items: impl Iterator<Item = T>) -> Option<T> {
+    match (items.next(), items.next()) {
+        (Some(item), None) => Some(item),
+        _ => None,
+    }
+}
+
+/// Helper function that returns the children of the Entry reached by following `keys` from
+/// `node`, so a single album can be serialized without the rest of the tree. An empty `keys`
+/// returns `node` itself, and any missing key returns `None`.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let mut tree: Node = Node::new();
+/// let artist: &mut Entry = tree.entry(String::from("Artist")).or_default();
+/// let album: &mut Entry = artist.child.entry(String::from("Album")).or_default();
+/// album.child.insert(String::from("Track"), Entry::default());
+///
+/// let tracks: &Node = subtree(&tree, &["Artist", "Album"]).unwrap();
+/// assert_eq!(tracks.keys().collect::<Vec<_>>(), ["Track"]);
+/// assert_eq!(subtree(&tree, &[]), Some(&tree));
+/// assert_eq!(subtree(&tree, &["Artist", "Missing"]), None);
+/// assert_eq!(subtree(&tree, &["Artist", "Album", "Track", "Extra"]), None);
+///
+/// let album: &Entry = subtree_entry(&tree, &["Artist", "Album"]).unwrap();
+/// assert_eq!(&album.child, tracks);
+/// assert_eq!(subtree_entry(&tree, &[]), None);
+/// ```
+pub fn subtree<'a>(node: &'a Node, keys: &[&str]) -> Option<&'a Node> {
+    match keys.is_empty() {
+        true => Some(node),
+        false => get_nested(node, keys, false).map(|entry| &entry.child),
+    }
+}
+
+/// Helper function that returns the Entry reached by following `keys` from `node`, whose children
+/// are returned by `subtree()`. Behaves like `get_nested()` without folding.
+pub fn subtree_entry<'a>(node: &'a Node, keys: &[&str]) -> Option<&'a Entry> {
+    get_nested(node, keys, false)
+}
+
+/// Helper function that returns every entry of `node` and its descendants, together with the keys
+/// leading to the entry, in the order of `iter_depth_first()`. With `KindOrder::DirectoriesFirst`,
+/// the directories of every level are walked before its audio files instead.
+///
+/// In `KindOrder::Mixed` order, the tree is counted first so the result is allocated once.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-flatten");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Artist").join("Album")).unwrap();
+/// create_dir_all(base.join("Artist").join("Empty")).unwrap();
+/// write(base.join("Artist").join("Album").join("1.mp3"), "").unwrap();
+/// write(base.join("Artist").join("Album").join("2.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// assert_eq!(flatten(&tree, KindOrder::Mixed).len(), 5);
+///
+/// let queue: Vec<(Vec<String>, &Entry)> = flatten_audio(&tree);
+/// let keys: Vec<&[String]> = queue.iter().map(|(keys, _)| keys.as_slice()).collect();
+/// assert_eq!(keys, [["Artist", "Album", "1"], ["Artist", "Album", "2"]]);
+///
+/// // Album folders before loose tracks
+/// write(base.join("Artist").join("Acoustic.mp3"), "").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+/// let keys = |order: KindOrder| -> Vec<String> {
+///     flatten(&tree, order).into_iter().map(|(keys, _)| keys.join("/")).collect()
+/// };
+/// assert_eq!(
+///     keys(KindOrder::Mixed),
+///     [
+///         "Artist",
+///         "Artist/Acoustic",
+///         "Artist/Album",
+///         "Artist/Album/1",
+///         "Artist/Album/2",
+///         "Artist/Empty",
+///     ]
+/// );
+/// assert_eq!(
+///     keys(KindOrder::DirectoriesFirst),
+///     [
+///         "Artist",
+///         "Artist/Album",
+///         "Artist/Album/1",
+///         "Artist/Album/2",
+///         "Artist/Empty",
+///         "Artist/Acoustic",
+///     ]
+/// );
+/// ```
+pub fn flatten(node: &Node, order: KindOrder) -> Vec<(Vec<String>, &Entry)> {
+    match order {
+        KindOrder::Mixed => collect_owned(node, |_| true),
+        KindOrder::DirectoriesFirst => walk(node, 0, usize::MAX, |mut children| {
+            order.apply(&mut children);
+            children
+        }),
+    }
+}
+
+/// Helper function that behaves like `flatten()`, skipping every entry that is not an
+/// `EntryKind::Audio` file. Directories without any audio file contribute nothing.
+pub fn flatten_audio(node: &Node) -> Vec<(Vec<String>, &Entry)> {
+    collect_owned(node, |entry| entry.kind == EntryKind::Audio)
+}
+
+/// Helper function that collects the entries of `node` accepted by `accept`, with owned keys.
+fn collect_owned(node: &Node, accept: impl Fn(&Entry) -> bool) -> Vec<(Vec<String>, &Entry)> {
+    let count: usize = iter_depth_first(node)
+        .filter(|(_, entry)| accept(entry))
+        .count();
+    let mut entries: Vec<(Vec<String>, &Entry)> = Vec::with_capacity(count);
+
+    for (keys, entry) in iter_depth_first(node).filter(|(_, entry)| accept(entry)) {
+        entries.push((keys.into_iter().map(String::from).collect(), entry));
+    }
+    entries
+}
+
+/// Number of songs and directories in a Node tree, returned by `count_tree()`.
+///
+/// # Fields
+/// `songs`: `usize` -> Number of `EntryKind::Audio` entries.
+/// `dirs`: `usize` -> Number of `EntryKind::Directory` entries, including empty ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TreeCounts {
+    pub songs: usize,
+    pub dirs: usize,
+}
+
+/// Helper function that counts the songs and directories of `node` and its descendants in a
+/// single traversal, without recursion.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-count");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Artist").join("Album")).unwrap();
+/// create_dir_all(base.join("Artist").join("Empty")).unwrap();
+/// create_dir_all(base.join("Other")).unwrap();
+/// write(base.join("Artist").join("Album").join("1.mp3"), "").unwrap();
+/// write(base.join("Artist").join("Album").join("2.mp3"), "").unwrap();
+/// write(base.join("single.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// assert_eq!(count_tree(&tree), TreeCounts { songs: 3, dirs: 4 });
+/// assert_eq!(count_songs(&tree), 3);
+/// assert_eq!(count_dirs(&tree), 4);
+/// assert_eq!(count_tree(&Node::new()), TreeCounts::default());
+/// ```
+pub fn count_tree(node: &Node) -> TreeCounts {
+    let mut counts: TreeCounts = TreeCounts::default();
+    let mut stack: Vec<&Node> = vec![node];
+
+    while let Some(node) = stack.pop() {
+        for entry in node.values() {
+            match entry.kind {
+                EntryKind::Audio => counts.songs += 1,
+                EntryKind::Directory => counts.dirs += 1,
+            }
+            stack.push(&entry.child);
+        }
+    }
+    counts
+}
+
+/// Helper function that returns the number of songs in `node` and its descendants. See
+/// `count_tree()`.
+pub fn count_songs(node: &Node) -> usize {
+    count_tree(node).songs
+}
+
+/// Helper function that returns the number of directories in `node` and its descendants. See
+/// `count_tree()`.
+pub fn count_dirs(node: &Node) -> usize {
+    count_tree(node).dirs
+}
+
+/// Helper function that returns the total size in bytes of the audio files in `node` and its
+/// descendants, without recursion.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-total-size");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Artist").join("Album")).unwrap();
+/// create_dir_all(base.join("Artist").join("Empty")).unwrap();
+/// write(base.join("Artist").join("Album").join("1.mp3"), [0; 100]).unwrap();
+/// write(base.join("Artist").join("Album").join("2.mp3"), [0; 50]).unwrap();
+/// write(base.join("single.mp3"), [0; 7]).unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// assert_eq!(total_size(&tree), 157);
+/// assert_eq!(tree["Artist"].size, 150);
+/// assert_eq!(tree["Artist"].child["Album"].child["2"].size, 50);
+/// assert_eq!(tree["Artist"].child["Empty"].size, 0);
+/// ```
+pub fn total_size(node: &Node) -> u64 {
+    let mut total: u64 = 0;
+    let mut stack: Vec<&Node> = vec![node];
+
+    while let Some(node) = stack.pop() {
+        for entry in node.values() {
+            if entry.kind == EntryKind::Audio {
+                total += entry.size;
+            }
+            stack.push(&entry.child);
+        }
+    }
+    total
+}
+
+/// Helper function that returns the `n` most recently modified audio files in `node` and its
+/// descendants, newest first, together with the keys leading to them.
+///
+/// Files without a `modified` time come last. Files modified at the same time keep the order of
+/// `iter_depth_first()`.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::time::{Duration, UNIX_EPOCH};
+/// let mut tree: Node = Node::new();
+/// for (key, seconds) in [("old", 10), ("new", 30), ("middle", 20)] {
+///     let mut entry: Entry = Entry::default();
+///     entry.kind = EntryKind::Audio;
+///     entry.modified = Some(UNIX_EPOCH + Duration::from_secs(seconds));
+///     tree.insert(String::from(key), entry);
+/// }
+/// let mut unknown: Entry = Entry::default();
+/// unknown.kind = EntryKind::Audio;
+/// tree.insert(String::from("unknown"), unknown);
+///
+/// let recent: Vec<(Vec<String>, &Entry)> = recently_modified(&tree, 3);
+/// let keys: Vec<&str> = recent.iter().map(|(keys, _)| keys[0].as_str()).collect();
+/// assert_eq!(keys, ["new", "middle", "old"]);
+///
+/// // Serialized as Unix seconds
+/// let serialized: String = toml::to_string(&tree["new"]).unwrap();
+/// assert!(serialized.contains("modified = 30"));
+/// assert_eq!(toml::from_str::<Entry>(&serialized).unwrap(), tree["new"]);
+/// ```
+pub fn recently_modified(node: &Node, n: usize) -> Vec<(Vec<String>, &Entry)> {
+    let mut entries: Vec<(Vec<String>, &Entry)> = flatten_audio(node);
+    entries.sort_by_key(|(_, entry)| Reverse(entry.modified));
+    entries.truncate(n);
+    entries
+}
+
+/// Helper function that maps the `Entry.id` of every entry of `node` and its descendants back to
+/// the keys leading to the entry, so ids persisted by the frontend, such as favorites, can be
+/// resolved with `get_nested()` after a restart.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-build-id-index");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Artist").join("Album")).unwrap();
+/// write(base.join("Artist").join("Album").join("song.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// let index: BTreeMap<u64, Vec<String>> = build_id_index(&tree);
+/// assert_eq!(index.len(), 3);
+/// assert_eq!(index[&0x9952c228fd8ddece], ["Artist", "Album", "song"]);
+///
+/// let keys: Vec<&str> = index[&tree["Artist"].id].iter().map(String::as_str).collect();
+/// assert_eq!(get_nested(&tree, &keys, false), Some(&tree["Artist"]));
+/// ```
+pub fn build_id_index(node: &Node) -> BTreeMap<u64, Vec<String>> {
+    iter_depth_first(node)
+        .map(|(keys, entry)| (entry.id, keys.into_iter().map(String::from).collect()))
+        .collect()
+}
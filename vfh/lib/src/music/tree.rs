@@ -0,0 +1,169 @@
+use super::config::Config;
+use super::glob::is_glob_path;
+use super::node::{entry_id, Entry, EntryKind, Node};
+use super::traverse::{get_nested, get_nested_mut};
+use super::utils::{latest_modified, populate, populate_all, scan_base, update_art};
+use serde::{Deserialize, Serialize};
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+/// Node tree together with the directory it was built from and an Entry for that directory.
+///
+/// This is the recommended entry point for building and sending a library to the frontend in a
+/// single payload. `populate()` keeps working on plain Nodes.
+///
+/// # Fields
+/// `base`: `PathBuf` -> The directory the tree was built from. Empty when the tree was built
+///                      from several entry points, as there is no single directory then.
+/// `root`: `Entry` -> Entry for `base`, whose children are the top-level entries of the tree.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-tree").join("Music");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Artist")).unwrap();
+/// write(base.join("Artist").join("song.mp3"), "").unwrap();
+/// write(base.join("Music.png"), "").unwrap();
+///
+/// let config: Config = ConfigBuilder::new(&base).build().unwrap();
+/// let tree: Tree = Tree::build(&config).unwrap();
+/// assert_eq!(tree.base, base);
+/// assert_eq!(tree.root.src, base);
+/// assert_eq!(tree.root.art, Some(base.join("Music.png")));
+/// assert_eq!(tree.root.child["Artist"].child.keys().collect::<Vec<_>>(), ["song"]);
+///
+/// let serialized: String = toml::to_string(&tree).unwrap();
+/// assert_eq!(toml::from_str::<Tree>(&serialized).unwrap(), tree);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Tree {
+    pub base: PathBuf,
+    pub root: Entry,
+}
+
+impl Tree {
+    /// Helper function that builds the tree of the library described by `config`.
+    ///
+    /// When `Config::roots()` returns a single directory, `base` is that directory, canonicalized
+    /// unless `Config.canonicalize_base` is unset, and the children of `root` are filled in by
+    /// `populate()`. Otherwise, including when that directory is a glob pattern, they are filled
+    /// in by `populate_all()`, and `base` and `root.src` are left empty.
+    pub fn build(config: &Config) -> Result<Tree> {
+        let roots: Vec<PathBuf> = config.roots();
+
+        let (base, child): (PathBuf, Node) = match roots.as_slice() {
+            [base] if !is_glob_path(base) => {
+                let base: PathBuf = scan_base(base, config);
+                let mut child: Node = Node::new();
+                populate(&base, &mut child, config)?;
+                (base, child)
+            }
+            _ => (PathBuf::new(), populate_all(&roots, config)?),
+        };
+
+        let art: Option<PathBuf> = match base.as_os_str().is_empty() {
+            true => None,
+            false => update_art(&base, config),
+        };
+        let mut root: Entry = Entry::new(base.clone(), EntryKind::Directory, art, PathBuf::new());
+        root.id = entry_id(Path::new(""));
+        root.size = child.values().map(|entry| entry.size).sum();
+        root.modified = latest_modified(&child);
+        root.child = child;
+
+        Ok(Tree { base, root })
+    }
+
+    /// Helper function that returns the Entry reached by following `keys` from the root, or the
+    /// root itself when `keys` is empty. See `get_nested()`, whose folding is left off.
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// let mut tree: Tree = Tree {
+    ///     base: PathBuf::from("/mnt/music"),
+    ///     root: Entry::default(),
+    /// };
+    /// tree.root.child.insert(String::from("Artist"), Entry::default());
+    ///
+    /// assert_eq!(tree.get_nested(&[]), Some(&tree.root));
+    /// assert!(tree.get_nested(&["Artist"]).is_some());
+    /// assert_eq!(tree.get_nested(&["Artist", "Album"]), None);
+    /// ```
+    pub fn get_nested(&self, keys: &[&str]) -> Option<&Entry> {
+        match keys.is_empty() {
+            true => Some(&self.root),
+            false => get_nested(&self.root.child, keys, false),
+        }
+    }
+
+    /// Helper function that behaves like `Tree::get_nested()`, returning a mutable reference.
+    pub fn get_nested_mut(&mut self, keys: &[&str]) -> Option<&mut Entry> {
+        match keys.is_empty() {
+            true => Some(&mut self.root),
+            false => get_nested_mut(&mut self.root.child, keys, false),
+        }
+    }
+}
+
+/// Iterator over the ancestors of an Entry of a `Tree`, returned by `ancestors()`.
+pub struct Ancestors<'a> {
+    tree: &'a Tree,
+    keys: Option<Vec<&'a str>>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = &'a Entry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let keys: &mut Vec<&str> = self.keys.as_mut()?;
+        let ancestor: Option<&Entry> = self.tree.get_nested(keys);
+        if ancestor.is_none() || keys.pop().is_none() {
+            self.keys = None;
+        }
+        ancestor
+    }
+}
+
+/// Helper function that returns an iterator over the ancestors of `entry` in `tree`, from its
+/// parent up to `Tree.root`, by following `Entry.parent`. The iterator is empty for the root
+/// itself, and stops early when `entry` is not in `tree`.
+///
+/// Only the tree in memory is walked, so the file system is never accessed.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let mut tree: Tree = Tree {
+///     base: PathBuf::from("/music"),
+///     root: Entry::default(),
+/// };
+/// let mut album: Entry = Entry::default();
+/// album.src = PathBuf::from("/music/Artist/Album");
+/// album.parent = PathBuf::from("Artist");
+/// let mut song: Entry = Entry::default();
+/// song.kind = EntryKind::Audio;
+/// song.parent = Path::new("Artist").join("Album");
+/// album.child.insert(String::from("song"), song);
+/// let artist: &mut Entry = tree.root.child.entry(String::from("Artist")).or_default();
+/// artist.src = PathBuf::from("/music/Artist");
+/// artist.child.insert(String::from("Album"), album);
+///
+/// let song: &Entry = tree.get_nested(&["Artist", "Album", "song"]).unwrap();
+/// let sources: Vec<&Path> = ancestors(&tree, song).map(|entry| entry.src.as_path()).collect();
+/// assert_eq!(
+///     sources,
+///     [Path::new("/music/Artist/Album"), Path::new("/music/Artist"), Path::new("")]
+/// );
+///
+/// assert_eq!(ancestors(&tree, &tree.root).count(), 0);
+/// ```
+pub fn ancestors<'a>(tree: &'a Tree, entry: &'a Entry) -> Ancestors<'a> {
+    let keys: Option<Vec<&str>> = match std::ptr::eq(entry, &tree.root) {
+        true => None,
+        false => entry.parent.iter().map(|key| key.to_str()).collect(),
+    };
+    Ancestors { tree, keys }
+}
@@ -1,21 +1,52 @@
-use super::node::{Entry, Node};
+use super::config::{
+    Config, ConfigError, DirOverrides, DEFAULT_LIBRARY, IGNORE_FILE_NAME, OVERRIDES_FILE_NAME,
+};
+use super::glob::{expand_dirs, glob_match, is_glob_path, path_matches};
+use super::node::{entry_id, whole_seconds, Entry, EntryKind, Node, ScanIssue};
+use super::platform::{strip_long_path, to_long_path};
+use super::sniff::sniff_audio;
+use super::stats::{RefreshReport, ScanError, ScanProgress, ScanStats};
+#[cfg(test)]
+use super::testing;
+use super::traverse::count_songs;
+#[cfg(feature = "rayon")]
+use std::sync::Condvar;
+use std::sync::{Mutex, PoisonError};
 use std::{
+    collections::{btree_map, BTreeMap},
     ffi::{OsStr, OsString},
-    fs::{read_dir, DirEntry},
-    io::Result,
-    path::{Path, PathBuf},
+    fs::{
+        metadata, read_dir, read_to_string, symlink_metadata, DirEntry, FileType, Metadata, ReadDir,
+    },
+    io::{Error, ErrorKind, Result},
+    path::{Component, Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    sync::mpsc::channel,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant, SystemTime},
+    vec,
+};
+use unicode_normalization::UnicodeNormalization;
+#[cfg(feature = "tokio")]
+use {
+    futures::future::join_all,
+    std::{future::Future, pin::Pin},
+    tokio::sync::Semaphore,
 };
 
-/// Helper function that returns a `PathBuf` for the `Entry.art` field.
+/// Helper function that returns a `PathBuf` for the `Entry.art` field, or `None` if no image is
+/// found.
 ///
 /// Image naming convention: `${KEY}.${EXT}`, where
 ///     &#09;`KEY`: `OsString` -> The name of file or directory as it appears in the file browser or terminal.
 ///                        &#09;&#09;&#09;&#09;&#09;&emsp;If it is a file, the key is the basename with file extension stripped.
 ///     &#09;`EXT`: `&str` -> The chosen file extension to be displayed in the Tauri web app.
-///                           &#09;&#09;&#09;&#09;&emsp;In order of precedence:
+///                           &#09;&#09;&#09;&#09;&emsp;In order of precedence, unless overridden by `Config.art_extensions`:
 ///                            &#09;&#09;&#09;&#09;&emsp;- `.webp`
 ///                            &#09;&#09;&#09;&#09;&emsp;- `.apng`
 ///                            &#09;&#09;&#09;&#09;&emsp;- `.gif`
+///                            &#09;&#09;&#09;&#09;&emsp;- `.avif`
 ///                            &#09;&#09;&#09;&#09;&emsp;- `.svg`
 ///                            &#09;&#09;&#09;&#09;&emsp;- `.png`
 ///                            &#09;&#09;&#09;&#09;&emsp;- `.jpeg`
@@ -28,21 +59,104 @@ use std::{
 /// This allows for extensive customization for individual Artist, Album, and Song file objects as
 /// well as ease of setting cover art for all descendants of a common ancestor in the Node tree.
 ///
+/// Hidden images (see `is_hidden()`) are never used unless `Config.include_hidden` is set, and
+/// junk files such as AppleDouble `._cover.jpg` files (see `is_junk()`) never are unless
+/// `Config.skip_junk` is unset.
+///
 /// # Examples
 /// ```
+/// # use vfh::music::*;
 /// let config: Config = Config::new().unwrap(); // get_base_directory() is the entry point for
 ///                                             // the file hiearchy being built
 /// let mut tree: Node = Node::new(); // Node -> BTreeMap<OsString, Entry>
-/// let _ = populate(&config.base, &mut tree); // populate() calls update_art()
+/// let _ = populate(&config.base, &mut tree, &config); // populate() calls update_art()
+/// ```
+///
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let album: PathBuf = env::temp_dir().join("vfh-doc-update-art").join("Album");
+/// # let _ = remove_dir_all(&album);
+/// create_dir_all(&album).unwrap();
+/// write(album.join("Album.webp"), "").unwrap();
+/// write(album.join("Album.jpg"), "").unwrap();
+///
+/// let mut config: Config = toml::from_str("base = ''").unwrap();
+/// config.art_extensions = vec![String::from("jpg"), String::from("webp")];
+/// assert_eq!(update_art(&album, &config), Some(album.join("Album.jpg")));
 /// ```
-pub fn update_art(path: &Path) -> PathBuf {
+pub fn update_art(path: &Path, config: &Config) -> Option<PathBuf> {
+    find_art(path, config, |_| true, &ArtListings::default())
+}
+
+/// File names of the directories searched for cover art by `find_art()`, grouped by file stem and
+/// sorted, so that a directory is listed once however many of its entries have their art looked
+/// up, and the same image wins on every scan when several only differ by the case of their
+/// extension. Only the `ART_LISTINGS` most recently searched directories are kept.
+#[derive(Default)]
+struct ArtListings(Mutex<Vec<(PathBuf, Arc<ArtListing>)>>);
+
+/// File names of a single directory, grouped by file stem. See `ArtListings`.
+type ArtListing = BTreeMap<OsString, Vec<OsString>>;
+
+/// Number of directory listings kept by `ArtListings`, enough for the directory being read and
+/// its ancestors while a scan descends into it.
+const ART_LISTINGS: usize = 8;
+
+impl ArtListings {
+    /// Helper function that returns the file names of `dir`, listing it unless it was listed
+    /// already. A directory that cannot be listed has no file names.
+    fn get(&self, dir: &Path) -> Arc<ArtListing> {
+        let mut listings = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(index) = listings.iter().position(|(listed, _)| listed == dir) {
+            let listing = listings.remove(index);
+            listings.push(listing);
+            return Arc::clone(&listings[listings.len() - 1].1);
+        }
+
+        let mut listing: ArtListing = ArtListing::new();
+        for entry in read_dir(to_long_path(dir)).into_iter().flatten().flatten() {
+            let name: OsString = entry.file_name();
+            let stem: OsString = Path::new(&name)
+                .file_stem()
+                .unwrap_or(OsStr::new(""))
+                .to_os_string();
+            listing.entry(stem).or_default().push(name);
+        }
+        listing.values_mut().for_each(|names| names.sort());
+
+        if listings.len() >= ART_LISTINGS {
+            listings.remove(0);
+        }
+        let listing: Arc<ArtListing> = Arc::new(listing);
+        listings.push((dir.to_path_buf(), Arc::clone(&listing)));
+        listing
+    }
+}
+
+/// Helper function that performs the search described in `update_art()`, skipping any candidate
+/// image for which `accept` returns false, with the directories searched listed through
+/// `listings`.
+fn find_art(
+    path: &Path,
+    config: &Config,
+    accept: impl Fn(&Path) -> bool,
+    listings: &ArtListings,
+) -> Option<PathBuf> {
+    let accept = |art: &Path| {
+        (config.include_hidden || !is_hidden(art))
+            && !(config.skip_junk && is_junk(art, false, &config.junk))
+            && accept(art)
+    };
     let mut pb: PathBuf = path.to_path_buf();
-    let extensions: [&str; 8] = [
-        ".webp", ".apng", ".gif", ".avif", ".svg", ".png", ".jpeg", ".jpg",
-    ];
+    let extensions: Vec<String> = config
+        .art_extensions
+        .iter()
+        .map(|ext| format!(".{}", ext))
+        .collect();
 
     // Check if child node has a corresponding art file first
-    for ext in extensions {
+    for ext in &extensions {
         let mut p: PathBuf = pb.clone();
         let mut name: OsString = p.file_stem().unwrap_or(OsStr::new("")).to_os_string();
 
@@ -50,7 +164,7 @@ pub fn update_art(path: &Path) -> PathBuf {
             break;
         }
 
-        match p.is_dir() {
+        match to_long_path(&p).is_dir() {
             // If dir, we want to search inside path for art
             true => {
                 name.push(ext);
@@ -64,15 +178,15 @@ pub fn update_art(path: &Path) -> PathBuf {
             }
         }
 
-        if p.exists() {
-            return p;
+        if let Some(p) = find_existing(p, config, listings).filter(|p| accept(p)) {
+            return Some(p);
         }
     }
 
     // Check if parent node has a corresponding art file to use instead
     // Parent node should always be a directory file object, so we do not need to strip path
     pb.pop();
-    for ext in extensions {
+    for ext in &extensions {
         let mut p: PathBuf = pb.clone();
         let mut name: OsString = p.file_stem().unwrap_or(OsStr::new("")).to_os_string();
 
@@ -83,84 +197,4314 @@ pub fn update_art(path: &Path) -> PathBuf {
         name.push(ext);
         p.push(name);
 
-        if p.exists() {
-            return p;
+        if let Some(p) = find_existing(p, config, listings).filter(|p| accept(p)) {
+            return Some(p);
+        }
+    }
+
+    None // no art if nothing is found
+}
+
+/// Helper function that returns the existing file at `candidate` or, when
+/// `Config.case_insensitive_extensions` is set, a file next to it whose name only differs by the
+/// case of its extension, the first in file name order when there are several. An exact match
+/// always wins. Symbolic links whose target does not exist never match.
+fn find_existing(candidate: PathBuf, config: &Config, listings: &ArtListings) -> Option<PathBuf> {
+    if to_long_path(&candidate).exists() {
+        return Some(candidate);
+    }
+    if !config.case_insensitive_extensions {
+        return None;
+    }
+
+    let extension: &str = candidate.extension()?.to_str()?;
+    let dir: &Path = candidate.parent()?;
+    let listing: Arc<ArtListing> = listings.get(dir);
+    listing
+        .get(candidate.file_stem()?)?
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| {
+            path.extension()
+                .and_then(OsStr::to_str)
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+                && to_long_path(path).exists()
+        })
+}
+
+/// Extensions, without the leading `.`, of the audio files always inserted into a Node tree.
+/// `Config.extensions` adds to them.
+pub const AUDIO_EXTENSIONS: &[&str] = &[
+    "mp3", "ogg", "oga", "opus", "wav", "aiff", "aac", "m4a", "m4b", "alac", "flac", "ape", "wv",
+    "mka",
+];
+
+/// Helper function that returns whether `path` is an audio file, meaning its extension is one of
+/// `AUDIO_EXTENSIONS` or of `extra`, regardless of case.
+///
+/// `populate()` uses it with `Config.extensions` as `extra`, unless the extensions are replaced
+/// by an override file or `Config.case_insensitive_extensions` is unset.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// assert!(is_audio_path(Path::new("Album/song.mp3"), &[]));
+/// assert!(is_audio_path(Path::new("Album/song.FLAC"), &[]));
+/// assert!(!is_audio_path(Path::new("Album/song.mid"), &[]));
+/// assert!(is_audio_path(Path::new("Album/song.mid"), &[String::from("MID")]));
+/// assert!(!is_audio_path(Path::new("Album/flac"), &[]));
+///
+/// // Every built-in extension is inserted by populate()
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-is-audio-path");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(&base).unwrap();
+/// for extension in ["m4a", "m4b", "aiff", "alac", "ape", "wv", "mka", "oga"] {
+///     write(base.join(format!("{}.{}", extension, extension)), "").unwrap();
+///     write(base.join(format!("upper {}.{}", extension, extension.to_uppercase())), "").unwrap();
+/// }
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// let stats: ScanStats = populate(&base, &mut tree, &config).unwrap();
+/// assert_eq!(stats.skipped_extension, 0);
+/// for extension in ["m4a", "m4b", "aiff", "alac", "ape", "wv", "mka", "oga"] {
+///     assert_eq!(tree[extension].src, base.join(format!("{}.{}", extension, extension)));
+///     assert!(tree.contains_key(&format!("upper {}", extension)));
+/// }
+/// ```
+pub fn is_audio_path(path: &Path, extra: &[String]) -> bool {
+    let Some(extension) = path.extension().and_then(OsStr::to_str) else {
+        return false;
+    };
+
+    AUDIO_EXTENSIONS
+        .iter()
+        .copied()
+        .chain(extra.iter().map(String::as_str))
+        .any(|allowed| allowed.eq_ignore_ascii_case(extension))
+}
+
+/// Helper function that returns the MIME type of a file with the extension `extension`, without
+/// the leading `.` and regardless of case, for the audio files of `AUDIO_EXTENSIONS` and the
+/// images of the default `Config.art_extensions` as well as a few other common audio formats.
+/// Other extensions get `application/octet-stream`.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// assert_eq!(mime_for_extension("mp3"), "audio/mpeg");
+/// assert_eq!(mime_for_extension("FLAC"), "audio/flac");
+/// assert_eq!(mime_for_extension("svg"), "image/svg+xml");
+/// assert_eq!(mime_for_extension("xyz"), "application/octet-stream");
+///
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-mime-for-extension");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Album")).unwrap();
+/// write(base.join("Album").join("song.opus"), "").unwrap();
+/// write(base.join("Album").join("other.xyz"), "").unwrap();
+///
+/// let mut config: Config = toml::from_str("base = ''").unwrap();
+/// config.extensions.push(String::from("xyz"));
+/// let mut tree: Node = Node::new();
+/// populate(&base.join("Album"), &mut tree, &config).unwrap();
+/// assert_eq!(tree["song"].mime.as_deref(), Some("audio/opus"));
+/// assert_eq!(tree["other"].mime.as_deref(), Some("application/octet-stream"));
+///
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+/// assert_eq!(tree["Album"].mime, None);
+/// assert_eq!(tree["Album"].child.keys().collect::<Vec<_>>(), ["other", "song"]);
+/// ```
+pub fn mime_for_extension(extension: &str) -> &'static str {
+    match extension.to_ascii_lowercase().as_str() {
+        "mp3" => "audio/mpeg",
+        "ogg" | "oga" => "audio/ogg",
+        "opus" => "audio/opus",
+        "wav" => "audio/wav",
+        "aac" => "audio/aac",
+        "flac" => "audio/flac",
+        "m4a" | "m4b" | "alac" => "audio/mp4",
+        "aiff" => "audio/aiff",
+        "ape" => "audio/ape",
+        "wv" => "audio/wavpack",
+        "mka" => "audio/x-matroska",
+        "weba" => "audio/webm",
+        "webp" => "image/webp",
+        "apng" => "image/apng",
+        "gif" => "image/gif",
+        "avif" => "image/avif",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpeg" | "jpg" => "image/jpeg",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Helper function that returns whether the file or directory at `path` is hidden, meaning its
+/// name starts with a `.`.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// assert!(is_hidden(Path::new("Music/.stversions")));
+/// assert!(is_hidden(Path::new("Album/.cover.jpg")));
+/// assert!(!is_hidden(Path::new(".config/Album")));
+/// ```
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."))
+}
+
+/// Glob patterns matching the names of the directories left by operating systems and NAS
+/// software, skipped by scans when `Config.skip_junk` is set.
+pub const JUNK_DIRS: &[&str] = &[
+    "@eaDir",
+    ".Trash-*",
+    "$RECYCLE.BIN",
+    "System Volume Information",
+    ".git",
+];
+
+/// Glob patterns matching the names of the metadata files left by operating systems, skipped by
+/// scans when `Config.skip_junk` is set.
+pub const JUNK_FILES: &[&str] = &[".DS_Store", "._*", "Thumbs.db", "desktop.ini"];
+
+/// Helper function that returns whether the file or directory at `path` is junk left by an
+/// operating system, meaning its name matches one of `JUNK_DIRS` (for a directory),
+/// `JUNK_FILES` (for a file) or `extra` (for either).
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// assert!(is_junk(Path::new("Album/@eaDir"), true, &[]));
+/// assert!(is_junk(Path::new("Music/.Trash-1000"), true, &[]));
+/// assert!(is_junk(Path::new("Album/._cover.jpg"), false, &[]));
+/// assert!(!is_junk(Path::new("Album/@eaDir"), false, &[]));
+/// assert!(!is_junk(Path::new("Album/cover.jpg"), false, &[]));
+/// assert!(is_junk(Path::new("Album/.picasa.ini"), false, &["*.ini".to_string()]));
+/// ```
+pub fn is_junk(path: &Path, is_dir: bool, extra: &[String]) -> bool {
+    let Some(name) = path.file_name().and_then(OsStr::to_str) else {
+        return false;
+    };
+    let builtin: &[&str] = match is_dir {
+        true => JUNK_DIRS,
+        false => JUNK_FILES,
+    };
+    builtin.iter().any(|pattern| glob_match(pattern, name))
+        || extra.iter().any(|pattern| glob_match(pattern, name))
+}
+
+/// Helper function that returns the key under which a file or directory named `name` is inserted
+/// into a Node tree.
+///
+/// Valid UTF-8 names are used as-is. Any byte of `name` that is not part of valid UTF-8 is
+/// written as `%` followed by its two uppercase hexadecimal digits, so names that only differ in
+/// invalid bytes keep distinct keys instead of collapsing onto the same `U+FFFD` replacement.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::ffi::OsStr;
+/// assert_eq!(entry_key(OsStr::new("Ænima")), "Ænima");
+/// # #[cfg(unix)]
+/// # {
+/// use std::os::unix::ffi::OsStrExt;
+/// assert_eq!(entry_key(OsStr::from_bytes(b"caf\xe9")), "caf%E9");
+/// # }
+/// ```
+pub fn entry_key(name: &OsStr) -> String {
+    if let Some(name) = name.to_str() {
+        return name.to_string();
+    }
+
+    let mut key: String = String::new();
+    for chunk in name.as_encoded_bytes().utf8_chunks() {
+        key.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            key.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    key
+}
+
+/// Helper function that returns `key` in Unicode Normalization Form C, where accented letters
+/// are composed into a single character when possible.
+///
+/// macOS writes file names in the decomposed form NFD, where `ö` is an `o` followed by a
+/// combining diaeresis, while names typed elsewhere are usually in NFC, so the same name may
+/// come in either form. `populate()` normalizes keys with this function unless
+/// `Config.normalize_keys` is unset, and `get_nested()` and `find_by_path()` accept either form.
+/// Only keys and `parent` are normalized, while `src` keeps the original name so the file can
+/// still be opened.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let decomposed: &str = "Bjo\u{308}rk";
+/// assert_ne!(decomposed, "Björk");
+/// assert_eq!(normalize_key(decomposed), "Björk");
+/// assert_eq!(normalize_key("Björk"), "Björk");
+/// ```
+pub fn normalize_key(key: &str) -> String {
+    key.nfc().collect()
+}
+
+/// Helper function that returns `key` normalized with `normalize_key()` and in lower case, so
+/// two names are the same name on a case-insensitive file system when they fold to the same key.
+///
+/// The case-insensitive file systems of macOS and Windows hold a single one of the names that
+/// only differ in case, such as `ABBA` and `Abba`, while those of Linux may hold both. When
+/// `Config.fold_case` is set, `populate()` inserts the first of them in file name order under its
+/// own casing and leaves the others out, reporting them in `ScanStats.case_collisions`, so the
+/// same library gives the same tree on every platform. File names that only differ in case from
+/// a key otherwise taken, such as `song.flac` next to `Song.mp3`, get disambiguated keys as
+/// described in `populate()`.
+///
+/// `get_nested()` and `find_by_path()` fall back to this folding when a key or path is not found
+/// as given and their `fold` argument is set, as long as it matches a single entry.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// assert_eq!(fold_key("ABBA"), "abba");
+/// assert_eq!(fold_key("Bjo\u{308}rk"), fold_key("BJÖRK"));
+/// ```
+///
+/// Scanning a library whose directories only differ in case:
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-fold-key");
+/// # let _ = remove_dir_all(&base);
+/// for artist in ["ABBA", "Abba"] {
+///     create_dir_all(base.join(artist)).unwrap();
+///     write(base.join(artist).join("Waterloo.mp3"), "").unwrap();
+/// }
+/// write(base.join("Song.mp3"), "").unwrap();
+/// write(base.join("song.flac"), "").unwrap();
+///
+/// let mut config: Config = toml::from_str("base = ''").unwrap();
+/// config.fold_case = true;
+/// let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+/// assert_eq!(tree.keys().collect::<Vec<_>>(), ["ABBA", "Song", "song (flac)"]);
+/// assert_eq!(tree["ABBA"].src, base.join("ABBA"));
+/// assert_eq!(stats.case_collisions, [base.join("Abba")]);
+/// assert_eq!(stats.key_collisions, [base.join("song.flac")]);
+///
+/// // Lookups in any case
+/// let song: &Entry = &tree["ABBA"].child["Waterloo"];
+/// let path: PathBuf = base.join("abba").join("waterloo.mp3");
+/// assert_eq!(get_nested(&tree, &["abba", "WATERLOO"], config.fold_case), Some(song));
+/// assert_eq!(find_by_path(&tree, &base, &path, config.fold_case), Some(song));
+///
+/// // Both are kept otherwise
+/// config.fold_case = false;
+/// let tree: Node = populate_from(&base, &config).unwrap();
+/// assert_eq!(tree.keys().collect::<Vec<_>>(), ["ABBA", "Abba", "Song", "song"]);
+/// ```
+pub fn fold_key(key: &str) -> String {
+    normalize_key(key).to_lowercase()
+}
+
+/// Helper function that returns the key of a file or directory named `name`, converted with
+/// `entry_key()` and, when `normalize` is set, `normalize_key()`.
+fn name_key(name: &OsStr, normalize: bool) -> String {
+    let key: String = entry_key(name);
+    match normalize {
+        true => normalize_key(&key),
+        false => key,
+    }
+}
+
+/// Helper function that returns the key the file or directory at `path` is inserted under among
+/// the children `node` of its parent directory, normalized with `normalize_key()` when
+/// `normalize` is set.
+///
+/// The key is the file stem of `path`, converted with `entry_key()`, unless another entry of
+/// `node` already uses it, or uses a key that folds to the same with `fold_key()` when `fold` is
+/// set. The key then gets the extension of `path` in parentheses, as in
+/// `song (flac)`, followed by ` (2)`, ` (3)` and so on while that is also taken or `path` has no
+/// extension. An entry of `node` whose `src` is `path` keeps its key.
+pub(super) fn unique_key(node: &Node, path: &Path, normalize: bool, fold: bool) -> String {
+    let taken = |key: &str| match fold {
+        true => {
+            let key: String = fold_key(key);
+            node.iter()
+                .any(|(other, entry)| entry.src != path && fold_key(other) == key)
         }
+        false => node.get(key).is_some_and(|entry| entry.src != path),
+    };
+    let stem: String = name_key(path.file_stem().unwrap_or(OsStr::new("")), normalize);
+    if !taken(&stem) {
+        return stem;
+    }
+
+    let disambiguated: String = match path.extension() {
+        Some(extension) => format!("{} ({})", stem, name_key(extension, normalize)),
+        None => stem,
+    };
+    let mut key: String = disambiguated.clone();
+    let mut index: usize = 1;
+    while taken(&key) {
+        index += 1;
+        key = format!("{} ({})", disambiguated, index);
+    }
+    key
+}
+
+/// Helper function that returns the key and Entry of the child of `node` whose `src` is `path`,
+/// looking under the keys `unique_key()` gives first. When there is none, a child whose `src`
+/// only differs from `path` by its Unicode normalization is returned instead.
+pub(super) fn find_child<'a>(node: &'a Node, path: &Path) -> Option<(&'a String, &'a Entry)> {
+    let stem: String = entry_key(path.file_stem().unwrap_or(OsStr::new("")));
+    let found = |(_, entry): &(&String, &Entry)| entry.src == path;
+    node.get_key_value(&stem)
+        .filter(found)
+        .or_else(|| node.get_key_value(&normalize_key(&stem)).filter(found))
+        .or_else(|| node.iter().find(|(_, entry)| entry.src == path))
+        .or_else(|| {
+            // Names in ASCII only have a single normalization
+            let path: &str = path.to_str().filter(|path| !path.is_ascii())?;
+            let path: String = normalize_key(path);
+            node.iter().find(|(_, entry)| {
+                entry
+                    .src
+                    .to_str()
+                    .is_some_and(|src| normalize_key(src) == path)
+            })
+        })
+}
+
+/// Helper function that returns the child of `node` whose file name only differs from that of
+/// `path` by its case or Unicode normalization, which makes them the same file on a
+/// case-insensitive file system, or `None` if there is none. See `fold_key()`.
+pub(super) fn folded_sibling<'a>(node: &'a Node, path: &Path) -> Option<&'a Entry> {
+    let name: String = fold_key(&entry_key(path.file_name()?));
+    node.values().find(|entry| {
+        entry.src != path
+            && entry
+                .src
+                .file_name()
+                .is_some_and(|other| fold_key(&entry_key(other)) == name)
+    })
+}
+
+/// Helper function that returns whether the directory at `dir`, last read at `scanned_at`, may
+/// have changed since.
+fn is_changed(dir: &Path, scanned_at: Option<SystemTime>) -> bool {
+    let modified: Option<SystemTime> = metadata(to_long_path(dir))
+        .and_then(|m| m.modified())
+        .ok()
+        .map(whole_seconds);
+    // Modification times may be coarser than a second, so a directory modified during the
+    // second it was read in may have changed after it was read
+    match (scanned_at, modified) {
+        (Some(scanned_at), Some(modified)) => modified >= scanned_at,
+        _ => true,
     }
+}
+
+/// Helper function that removes the child of `node` whose `src` is `path` and returns it, as long
+/// as it is of the given `kind`.
+fn take_child(node: &mut Node, path: &Path, kind: EntryKind) -> Option<Entry> {
+    let key: String = find_child(node, path)
+        .filter(|(_, entry)| entry.kind == kind)?
+        .0
+        .clone();
+    node.remove(&key)
+}
 
-    return PathBuf::new(); // default to an empty Path if nothing is found
+/// Helper function that returns the patterns listed in the contents of an ignore file, such as
+/// the `.mpbignore` files read by `populate()`.
+///
+/// Every line holds a single pattern, matched with `path_matches()` relative to the directory
+/// containing the file, so `Bonus/` ignores every directory named `Bonus` in its subtree while
+/// `/Bonus/` only ignores the one next to the file. Leading and trailing whitespace is trimmed,
+/// and blank lines and comments, starting with `#`, are skipped.
+///
+/// Negated patterns, starting with `!`, are not supported: they are skipped as well, so nothing
+/// ignored by another pattern can be included back.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// let contents: &str = "# Artwork\nscans/\n\n  *.cue  \n!keep.cue\n";
+/// assert_eq!(parse_ignore_file(contents), ["scans/", "*.cue"]);
+/// ```
+pub fn parse_ignore_file(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('!'))
+        .map(String::from)
+        .collect()
 }
 
 /// Helper function that populates file paths and associated metadata in a Node tree.
 ///
+/// Prefer `Tree::build()`, which also records the base directory and an Entry for it.
+///
+/// The contents of every directory are read in file name order. Directories and the audio files
+/// among their contents are inserted, each keyed by the file stem of its name, converted with
+/// `entry_key()` and normalized with `normalize_key()` unless `Config.normalize_keys` is unset.
+/// When several entries share a stem, such as `song.flac` and `song.mp3` or a `Live` directory
+/// and `Live.flac`, the first one keeps the stem while the others are keyed by their stem and
+/// extension, as in `song (mp3)`, and reported in `ScanStats.key_collisions`. Every field of
+/// `config` describes how it changes what is inserted, and any directory may adjust them for its
+/// subtree with a `.mpb.toml` file (see `DirOverrides`) or a `.mpbignore` file (see
+/// `parse_ignore_file()`). Everything left out is counted in the returned `ScanStats`.
+///
+/// Files that cannot be read are left out and directories that cannot be listed are kept empty,
+/// with `Entry.error` set, unless `Config.abort_on_error` is set. `dir` itself must be an
+/// existing directory whose contents can be listed: otherwise an error wrapping
+/// `ConfigError::NotFound`, `ConfigError::NotADirectory` or `ConfigError::PermissionDenied` is
+/// returned, of the matching `ErrorKind`, and `node` is left untouched. Call
+/// `Config::validate()` beforehand to check every base at once.
+///
+/// When `node` already holds entries, the entries of `dir` are merged into it with `merge()` once
+/// the scan succeeded, and entries of `node` that `dir` no longer holds are kept. Use
+/// `populate_fresh()` to replace the contents of `node` instead, `populate_from()` to build a new
+/// Node tree, or `populate_with_progress()` to follow the progress of a long scan or to cancel
+/// it.
+///
 /// # Examples
 /// ```
-/// let config: Config = Config::new().unwrap(); // get_base_directory() is the entry point for
-///                                             // the file hiearchy being built
-/// let mut tree: Node = Node::new(); // Node -> BTreeMap<OsString, Entry>
-/// let _ = populate(&config.base, &mut tree); // populate() mutates Node tree
-/// ```
-pub fn populate(dir: &Path, node: &mut Node) -> Result<()> {
-    if dir.is_dir() {
-        for entry in read_dir(&dir)? {
-            let entry: DirEntry = entry?;
-            let path: PathBuf = entry.path();
-
-            if path.is_dir() {
-                let name: String = path
-                    .file_stem()
-                    .unwrap_or(OsStr::new(""))
-                    .to_string_lossy()
-                    .to_string();
-                let parent: String = dir
-                    .file_stem()
-                    .unwrap_or(OsStr::new(""))
-                    .to_string_lossy()
-                    .to_string();
-                let next_entry: Entry = Entry {
-                    src: path.clone(),
-                    art: update_art(&path),
-                    parent,
-                    child: Node::new(),
-                };
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-populate");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Artist").join("Album")).unwrap();
+/// write(base.join("Artist").join("Album").join("song.mp3"), "").unwrap();
+/// write(base.join("Artist").join("notes.txt"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// let stats: ScanStats = populate(&base, &mut tree, &config).unwrap();
+/// let song: &Entry = &tree["Artist"].child["Album"].child["song"];
+/// assert_eq!(song.mime.as_deref(), Some("audio/mpeg"));
+/// assert_eq!(stats.skipped_extension, 1);
+/// ```
+pub fn populate(dir: &Path, node: &mut Node, config: &Config) -> Result<ScanStats> {
+    let (scanned, stats): (Node, ScanStats) = populate_from_with_stats(dir, config)?;
+    merge(node, scanned);
+    Ok(stats)
+}
 
-                node.insert(name.clone(), next_entry);
-
-                // Recurse inserted nodes until a file object is reached
-                populate(
-                    &path,
-                    &mut node.get_mut(&name).unwrap_or(&mut Entry::default()).child,
-                )?;
-            } else {
-                // Disallow any non-audio formats in HTML from being inserted into Node tree
-                if ["mp3", "ogg", "opus", "wav", "aac", "flac"].contains(
-                    &path
-                        .extension()
-                        .unwrap_or(OsStr::new(""))
-                        .to_str()
-                        .unwrap_or(""),
-                ) {
-                    let name: String = path
-                        .file_stem()
-                        .unwrap_or(OsStr::new(""))
-                        .to_string_lossy()
-                        .to_string();
-                    let parent: String = dir
-                        .file_stem()
-                        .unwrap_or(OsStr::new(""))
-                        .to_string_lossy()
-                        .to_string();
-                    let next_entry = Entry {
-                        src: path.clone(),
-                        art: update_art(&path),
-                        parent,
-                        child: Node::new(),
-                    };
-
-                    node.insert(name, next_entry);
-                }
+/// Helper function that returns the Node tree of `dir`, built exactly like `populate()` builds
+/// it into an empty Node.
+///
+/// Nothing has to be created beforehand, and nothing is shared between calls, so several scans
+/// can run at once and the caller decides which of their trees to keep. Use
+/// `populate_from_with_stats()` to also get the `ScanStats` of the scan.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// # use std::thread;
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-populate-from");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Album")).unwrap();
+/// write(base.join("Album").join("song.mp3"), "").unwrap();
+/// write(base.join("Album").join("song.flac"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut flac_only: Config = config.clone();
+/// flac_only.exclude = vec![String::from("*.mp3")];
+///
+/// // Two scans of the same directory at once
+/// let (all, flac): (Node, Node) = thread::scope(|scope| {
+///     let all = scope.spawn(|| populate_from(&base, &config).unwrap());
+///     let flac = scope.spawn(|| populate_from(&base, &flac_only).unwrap());
+///     (all.join().unwrap(), flac.join().unwrap())
+/// });
+/// assert_eq!(all["Album"].child.len(), 2);
+/// assert_eq!(flac["Album"].child.keys().collect::<Vec<_>>(), ["song"]);
+///
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+/// assert_eq!(all, tree);
+/// ```
+pub fn populate_from(dir: &Path, config: &Config) -> Result<Node> {
+    Ok(populate_from_with_stats(dir, config)?.0)
+}
+
+/// Helper function that returns the Node tree of `dir` like `populate_from()` does, along with
+/// the `ScanStats` of its scan.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-populate-from-with-stats");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Album")).unwrap();
+/// write(base.join("Album").join("song.mp3"), "").unwrap();
+/// write(base.join("Album").join("notes.txt"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+/// assert_eq!(count_songs(&tree), 1);
+/// assert_eq!((stats.dirs, stats.files, stats.skipped_extension), (2, 1, 1));
+/// ```
+pub fn populate_from_with_stats(dir: &Path, config: &Config) -> Result<(Node, ScanStats)> {
+    let dir: PathBuf = scan_base(dir, config);
+    scan_tree(Scan::new(&dir, config), &mut Progress::new(None))
+}
+
+/// Helper function that populates `node` like `populate()` does, after removing all of its
+/// entries, so that `node` ends up holding the tree of `dir` alone rather than the result of
+/// merging it with what `node` held before.
+///
+/// `node` is cleared before `dir` is read, so it is left empty when the scan fails.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, remove_file, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-populate-fresh");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Album")).unwrap();
+/// write(base.join("Album").join("1.mp3"), "").unwrap();
+/// write(base.join("Live.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+/// let mut merged: Node = tree.clone();
+///
+/// // A track is replaced, and a file turns into a directory
+/// remove_file(base.join("Album").join("1.mp3")).unwrap();
+/// write(base.join("Album").join("2.mp3"), "").unwrap();
+/// remove_file(base.join("Live.mp3")).unwrap();
+/// create_dir_all(base.join("Live")).unwrap();
+/// write(base.join("Live").join("encore.mp3"), "").unwrap();
+///
+/// populate(&base, &mut merged, &config).unwrap();
+/// assert_eq!(merged["Album"].child.keys().collect::<Vec<_>>(), ["1", "2"]);
+/// assert_eq!(merged["Live"].child.keys().collect::<Vec<_>>(), ["encore"]);
+///
+/// populate_fresh(&base, &mut tree, &config).unwrap();
+/// assert_eq!(tree["Album"].child.keys().collect::<Vec<_>>(), ["2"]);
+/// assert_eq!(tree["Live"].child.keys().collect::<Vec<_>>(), ["encore"]);
+///
+/// // The directory turns back into a file: merging keeps the directory
+/// remove_dir_all(base.join("Live")).unwrap();
+/// write(base.join("Live.mp3"), "").unwrap();
+///
+/// populate(&base, &mut merged, &config).unwrap();
+/// assert_eq!(merged["Live"].kind, EntryKind::Directory);
+///
+/// populate_fresh(&base, &mut tree, &config).unwrap();
+/// assert_eq!(tree["Live"].kind, EntryKind::Audio);
+/// assert_eq!(tree["Live"].src, base.join("Live.mp3"));
+/// ```
+pub fn populate_fresh(dir: &Path, node: &mut Node, config: &Config) -> Result<ScanStats> {
+    node.clear();
+    populate(dir, node, config)
+}
+
+/// Helper function that populates `node` like `populate()` does, calling `progress` along the way
+/// so the scan of a large library can be followed.
+///
+/// `progress` is called with a `ScanProgress` when a directory is about to be read, as long as at
+/// least `PROGRESS_INTERVAL` directories and audio files were visited since the previous call,
+/// which keeps the number of calls low enough to forward each of them to a frontend. It is
+/// always called for `dir` itself, first, and once more with the final counts when the scan is
+/// done. The Node tree is never handed to `progress`.
+///
+/// The scan checks `cancel` before every file and directory it reads, so it stops promptly once
+/// `cancel` is set, from any thread, and returns an error of kind `ErrorKind::Interrupted`.
+/// `node` is then left untouched.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::sync::atomic::AtomicBool;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-populate-with-progress");
+/// # let _ = remove_dir_all(&base);
+/// for album in 0..50 {
+///     let album: PathBuf = base.join("Artist").join(format!("Album {}", album));
+///     create_dir_all(&album).unwrap();
+///     for track in 0..10 {
+///         write(album.join(format!("{}.mp3", track)), "").unwrap();
+///     }
+/// }
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let cancel: AtomicBool = AtomicBool::new(false);
+/// let mut reports: Vec<(usize, usize, PathBuf)> = Vec::new();
+/// let mut tree: Node = Node::new();
+/// populate_with_progress(&base, &mut tree, &config, &cancel, |progress: ScanProgress| {
+///     reports.push((progress.dirs, progress.files, progress.path.to_path_buf()));
+/// })
+/// .unwrap();
+///
+/// assert_eq!(reports.first(), Some(&(1, 0, base.clone())));
+/// assert_eq!(reports.last(), Some(&(52, 500, base.clone())));
+/// assert!(reports.len() <= (52 + 500) / PROGRESS_INTERVAL + 2);
+/// assert!(reports.windows(2).all(|pair| pair[0].0 + pair[0].1 < pair[1].0 + pair[1].1));
+/// ```
+///
+/// Cancelling from another thread:
+/// ```
+/// # use vfh::music::*;
+/// # use std::sync::atomic::AtomicBool;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// use std::io::ErrorKind;
+/// use std::sync::atomic::Ordering;
+/// use std::sync::{mpsc, Arc};
+/// use std::thread;
+///
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-populate-with-progress-cancel");
+/// # let _ = remove_dir_all(&base);
+/// for album in 0..20 {
+///     let album: PathBuf = base.join(format!("Album {}", album));
+///     create_dir_all(&album).unwrap();
+///     write(album.join("song.mp3"), "").unwrap();
+/// }
+///
+/// // The canceller sets the flag when asked to, and acknowledges it
+/// let cancel: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+/// let (ask, asked) = mpsc::channel::<()>();
+/// let (done, acknowledged) = mpsc::channel::<()>();
+/// let canceller = thread::spawn({
+///     let cancel: Arc<AtomicBool> = Arc::clone(&cancel);
+///     move || {
+///         asked.recv().unwrap();
+///         cancel.store(true, Ordering::Relaxed);
+///         done.send(()).unwrap();
+///     }
+/// });
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// let mut reports: usize = 0;
+/// let result = populate_with_progress(&base, &mut tree, &config, &cancel, |_| {
+///     reports += 1;
+///     ask.send(()).unwrap();
+///     acknowledged.recv().unwrap();
+/// });
+/// canceller.join().unwrap();
+///
+/// // Cancelled before any entry of the base directory was read
+/// assert_eq!(result.unwrap_err().kind(), ErrorKind::Interrupted);
+/// assert_eq!(reports, 1);
+/// assert!(tree.is_empty());
+/// ```
+pub fn populate_with_progress(
+    dir: &Path,
+    node: &mut Node,
+    config: &Config,
+    cancel: &AtomicBool,
+    mut progress: impl FnMut(ScanProgress),
+) -> Result<ScanStats> {
+    let dir: PathBuf = scan_base(dir, config);
+    let mut reporter: Progress = Progress::new(Some(&mut progress));
+    let scan: Scan = Scan {
+        cancel: Some(cancel),
+        ..Scan::new(&dir, config)
+    };
+    let stats: ScanStats = populate_under(scan, node, &mut reporter)?;
+    reporter.report(&dir, &stats);
+    Ok(stats)
+}
+
+/// Helper function that brings `node`, populated from `base` with `config` by an earlier scan, up
+/// to date with the file system, reading again only the directories that changed since.
+///
+/// `base` is always read again. Every directory below it whose modification time is not older
+/// than its `Entry.scanned_at` is read again too: new files and directories are inserted, and
+/// the entries of the ones that vanished are removed. Since modification times may be as coarse
+/// as a second, or more, a directory modified during the second it was read in counts as
+/// changed. The other directories are left as they were, and only their subdirectories are
+/// looked at. Directories without an `Entry.scanned_at`, such as those inserted with
+/// `insert_path()`, are always read again.
+///
+/// A directory only changes when entries are added to it, removed from it or renamed, so changes
+/// to the contents of the files of an unchanged directory, including its override file, are not
+/// picked up. Use `populate()` for those. `config` should be the one `node` was populated with.
+///
+/// `node` is only updated once the whole refresh succeeded, and left untouched when it fails.
+///
+/// # Errors
+/// The errors are those of `populate()`.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, remove_file, write};
+/// use std::{thread, time::Duration};
+///
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-refresh");
+/// # let _ = remove_dir_all(&base);
+/// for file in ["Artist/Debut/1.mp3", "Artist/Live/1.mp3", "Band/Album/1.mp3", "intro.mp3"] {
+///     create_dir_all(base.join(file).parent().unwrap()).unwrap();
+///     write(base.join(file), "").unwrap();
+/// }
+///
+/// // Let a second pass, for the directories to be older than the scan
+/// thread::sleep(Duration::from_millis(1100));
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// write(base.join("Artist/Debut/2.mp3"), "").unwrap();
+/// remove_file(base.join("Band/Album/1.mp3")).unwrap();
+/// let report: RefreshReport = refresh(&mut tree, &base, &config).unwrap();
+///
+/// let mut fresh: Node = Node::new();
+/// populate(&base, &mut fresh, &config).unwrap();
+/// assert_eq!(tree, fresh);
+/// assert_eq!(tree["Artist"].child["Debut"].child.keys().collect::<Vec<_>>(), ["1", "2"]);
+/// assert!(tree["Band"].child["Album"].child.is_empty());
+///
+/// // The base directory, `Artist/Debut` and `Band/Album` were read again
+/// assert_eq!((report.rescanned_dirs, report.skipped_dirs), (3, 3));
+/// assert_eq!((report.added_files, report.removed_files), (1, 1));
+/// ```
+pub fn refresh(node: &mut Node, base: &Path, config: &Config) -> Result<RefreshReport> {
+    let start: Instant = Instant::now();
+    let base: PathBuf = scan_base(base, config);
+    check_base(&base)?;
+    let mut scan: Scan = Scan::new(&base, config);
+    scan.enter(&base);
+    scan.ancestors.extend(dir_id(&base).ok());
+    scan.report.rescanned_dirs += 1;
+
+    let mut refreshed: Node = Node::new();
+    scan.populate(
+        &base,
+        &mut refreshed,
+        Some(node.clone()),
+        &mut Progress::new(None),
+    )?;
+
+    *node = refreshed;
+    scan.stats.duration = start.elapsed();
+    Ok(RefreshReport {
+        stats: scan.stats,
+        ..scan.report
+    })
+}
+
+/// Helper function that behaves like `populate()` on `scan.root`, starting from the state `scan`
+/// and reporting to `progress`. `scan.keys` leads to the entry of `node` when it is itself nested
+/// in a Node tree.
+fn populate_under(scan: Scan, node: &mut Node, progress: &mut Progress) -> Result<ScanStats> {
+    let (scanned, stats): (Node, ScanStats) = scan_tree(scan, progress)?;
+    merge(node, scanned);
+    Ok(stats)
+}
+
+/// Helper function that builds the Node tree of `scan.root` like `populate_from()` does, starting
+/// from the state `scan` and reporting to `progress`.
+fn scan_tree(mut scan: Scan, progress: &mut Progress) -> Result<(Node, ScanStats)> {
+    let start: Instant = Instant::now();
+    let dir: &Path = scan.root;
+    check_base(dir)?;
+    scan.enter(dir);
+    scan.ancestors.extend(dir_id(dir).ok());
+
+    let mut scanned: Node = Node::new();
+    scan.populate(dir, &mut scanned, None, progress)?;
+    scan.stats.duration = start.elapsed();
+    Ok((scanned, scan.stats))
+}
+
+/// Helper function that builds the Node tree of `dir` like `populate()` does, reading
+/// directories in parallel.
+///
+/// Every subdirectory is read, and the cover art of its entries looked up, in its own rayon
+/// task. Entries are inserted in file name order once the tasks of a directory are done, so keys
+/// are resolved exactly as in `populate()` and the returned tree is identical to the one it
+/// builds. File system errors are handled as in `populate()`, flagging the directories that
+/// cannot be listed, and recorded in the `ScanStats` `populate_parallel_with_stats()` returns.
+///
+/// When `Config.max_entries` is set, the subdirectories of a directory are read one after the
+/// other in file name order instead, so that the scan stops at the same entries as `populate()`
+/// and sets `ScanStats.limit_reached` the same way.
+///
+/// At most `Config.max_open_dirs` directories are listed at the same time, the other tasks
+/// waiting for one of them to be done, so a wide tree does not exhaust the file descriptors of
+/// the process. When opening a directory still fails because the process, or the system, ran
+/// out of file descriptors, it is retried up to `OPEN_RETRIES` times, waiting longer every time,
+/// before the error is returned.
+///
+/// A directory that takes longer than `Config.read_timeout_secs` to read is kept without its
+/// contents and flagged with `ScanIssue::TimedOut` in `Entry.error`, as in `populate()`, rather
+/// than failing the scan, and the other tasks go on. `Config.prune_empty_dirs` keeps it. When
+/// `dir` itself times out, the error is returned.
+///
+/// Every task checks `cancel` before every entry it reads, and the scan returns an error of
+/// kind `ErrorKind::Interrupted` once it is set, as in `populate_with_progress()`.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::sync::atomic::AtomicBool;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-populate-parallel");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Album")).unwrap();
+/// write(base.join("Album").join("song.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let tree: Node = populate_parallel(&base, &config, &AtomicBool::new(false)).unwrap();
+/// assert_eq!(tree, populate_from(&base, &config).unwrap());
+/// ```
+#[cfg(feature = "rayon")]
+pub fn populate_parallel(dir: &Path, config: &Config, cancel: &AtomicBool) -> Result<Node> {
+    Ok(populate_parallel_with_stats(dir, config, cancel)?.0)
+}
+
+/// Helper function that returns the Node tree of `dir` like `populate_parallel()` does, along
+/// with the `ScanStats` of its scan, which are the ones `populate_from_with_stats()` returns.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::sync::atomic::AtomicBool;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-populate-parallel-with-stats");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Album")).unwrap();
+/// write(base.join("Album").join("song.mp3"), "").unwrap();
+/// write(base.join("Album").join("notes.txt"), "").unwrap();
+///
+/// let mut config: Config = toml::from_str("base = ''").unwrap();
+/// config.max_entries = Some(1);
+/// let cancel: AtomicBool = AtomicBool::new(false);
+/// let (tree, stats): (Node, ScanStats) =
+///     populate_parallel_with_stats(&base, &config, &cancel).unwrap();
+/// assert!(tree["Album"].child.is_empty() && stats.limit_reached);
+/// assert_eq!((stats.dirs, stats.files, stats.skipped_extension), (2, 0, 1));
+/// ```
+#[cfg(feature = "rayon")]
+pub fn populate_parallel_with_stats(
+    dir: &Path,
+    config: &Config,
+    cancel: &AtomicBool,
+) -> Result<(Node, ScanStats)> {
+    let start: Instant = Instant::now();
+    let dir: PathBuf = scan_base(dir, config);
+    check_base(&dir)?;
+    let mut scan: Scan = Scan {
+        cancel: Some(cancel),
+        ..Scan::new(&dir, config)
+    };
+    scan.enter(&dir);
+    scan.ancestors.extend(dir_id(&dir).ok());
+
+    // The directory the scan starts from must be readable, as `check_base()` found it to be
+    let reads: ReadLimit = ReadLimit::new(config.max_open_dirs);
+    let read: Vec<Result<DirEntry>> = scan.list_parallel(&dir, &reads)?;
+    let mut root: Entry = Entry::new(dir.clone(), EntryKind::Directory, None, PathBuf::new());
+    scan.populate_parallel(&mut root, read, &reads)?;
+    assign_keys(&mut root.child, &mut PathBuf::new());
+    scan.stats.duration = start.elapsed();
+    Ok((root.child, scan.stats))
+}
+
+/// Helper function that sets the `parent` and `id` fields of every entry of `node` and its
+/// descendants, where `keys` leads from the root of the Node tree to `node`.
+#[cfg(any(feature = "rayon", feature = "tokio"))]
+fn assign_keys(node: &mut Node, keys: &mut PathBuf) {
+    for (key, entry) in node.iter_mut() {
+        entry.parent = keys.clone();
+        keys.push(key);
+        entry.id = entry_id(keys);
+        assign_keys(&mut entry.child, keys);
+        keys.pop();
+    }
+}
+
+/// Helper function that builds the Node tree of `dir` like `populate()` does, without blocking
+/// the async runtime it is awaited on.
+///
+/// Directories are listed with `tokio::fs`, up to `Config.max_open_dirs` at the same time, and
+/// opening one is retried when the process runs out of file descriptors, as in
+/// `populate_parallel()`. The other blocking calls, such as looking at the entries of a
+/// directory, reading override files and looking up cover art, run on tokio's blocking thread
+/// pool. Entries are inserted in file name order, so the returned tree is identical to the one
+/// `populate()` builds. File system errors are handled as in `populate()`, flagging the
+/// directories that cannot be listed, and recorded in the `ScanStats` `populate_async_with_stats()`
+/// returns. When `Config.max_entries` is set, subdirectories are read one after the other, as in
+/// `populate_parallel()`.
+///
+/// A directory that takes longer than `Config.read_timeout_secs` to read is kept without its
+/// contents and flagged with `ScanIssue::TimedOut` in `Entry.error`, as in `populate()`, rather
+/// than failing the scan. `Config.prune_empty_dirs` keeps it. When `dir` itself times out, the
+/// error is returned.
+///
+/// Subdirectories are read concurrently within the returned future instead of in tasks of their
+/// own, so dropping the future stops the scan: the file system calls already handed to the
+/// blocking pool complete, and no other one is started. Setting `cancel` stops it as well, with
+/// an error of kind `ErrorKind::Interrupted` as in `populate_with_progress()`, for callers that
+/// do not own the future.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::sync::atomic::AtomicBool;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-populate-async");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Album")).unwrap();
+/// write(base.join("Album").join("song.mp3"), "").unwrap();
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let cancel: AtomicBool = AtomicBool::new(false);
+/// let tree: Node = runtime.block_on(populate_async(&base, &config, &cancel)).unwrap();
+/// assert_eq!(tree, populate_from(&base, &config).unwrap());
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn populate_async(dir: &Path, config: &Config, cancel: &AtomicBool) -> Result<Node> {
+    Ok(populate_async_with_stats(dir, config, cancel).await?.0)
+}
+
+/// Helper function that returns the Node tree of `dir` like `populate_async()` does, along with
+/// the `ScanStats` of its scan, which are the ones `populate_from_with_stats()` returns.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::sync::atomic::AtomicBool;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-populate-async-with-stats");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Album")).unwrap();
+/// write(base.join("Album").join("song.mp3"), "").unwrap();
+/// write(base.join("Album").join("notes.txt"), "").unwrap();
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let cancel: AtomicBool = AtomicBool::new(false);
+/// let (tree, stats): (Node, ScanStats) = runtime
+///     .block_on(populate_async_with_stats(&base, &config, &cancel))
+///     .unwrap();
+/// assert_eq!(count_songs(&tree), 1);
+/// assert_eq!((stats.dirs, stats.files, stats.skipped_extension), (2, 1, 1));
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn populate_async_with_stats(
+    dir: &Path,
+    config: &Config,
+    cancel: &AtomicBool,
+) -> Result<(Node, ScanStats)> {
+    let start: Instant = Instant::now();
+    let dir: PathBuf = scan_base_async(dir, config).await;
+    let checked: PathBuf = dir.clone();
+    tokio::task::spawn_blocking(move || check_base(&checked))
+        .await
+        .map_err(Error::other)??;
+    let scan: AsyncScan = AsyncScan {
+        root: &dir,
+        config,
+        shared: Arc::new(config.clone()),
+        reads: Semaphore::new(config.max_open_dirs.max(1)),
+        cancel,
+    };
+    let mut state: Scan = scan.state();
+    let dir_path: PathBuf = dir.to_path_buf();
+    let (overrides, stats) = scan
+        .blocking(Vec::new(), move |mut state| {
+            state.enter(&dir_path);
+            (state.overrides, state.stats)
+        })
+        .await?;
+    state.overrides = overrides;
+    state.stats.add(stats);
+    state.ancestors.extend(dir_id_async(&dir).await.ok());
+
+    // The directory the scan starts from must be readable, as `check_base()` found it to be
+    let read: Vec<Listing> = scan.list(&mut state, &dir).await?;
+    let root: Entry = Entry::new(dir.clone(), EntryKind::Directory, None, PathBuf::new());
+    let (mut state, mut root) = scan.populate(state, root, read).await?;
+    assign_keys(&mut root.child, &mut PathBuf::new());
+    state.stats.duration = start.elapsed();
+    Ok((root.child, state.stats))
+}
+
+/// Helper function that inserts the audio file at `path` into `node`, where `node` was populated
+/// from `base`, without rescanning the rest of the tree.
+///
+/// Missing directory entries between `base` and `path` are created, and both them and the new
+/// file Entry get the same keys, `parent`, art, size and modification time `populate()` would
+/// have given them, including the effect of per-directory override files. An existing Entry at
+/// the same keys is replaced. The `size` and `modified` fields of every ancestor are recomputed.
+///
+/// Unless `Config.canonicalize_base` is unset, `base` is canonicalized like `populate()` does,
+/// and `path` may start with either form of `base`.
+///
+/// # Errors
+/// - `ErrorKind::InvalidInput` if `path` is not below `base` or has `..` components.
+/// - `ErrorKind::Unsupported` if the extension of `path` is not an allowed audio extension and
+///   its contents are not sniffed as audio (see `Config.sniff_content`), or
+///   if `path` or one of its directories is junk (see `is_junk()`) and `Config.skip_junk` is set.
+/// - `ErrorKind::InvalidData` if `path` is empty and `Config.skip_empty_files` is set.
+/// - `ErrorKind::AlreadyExists` if `Config.fold_case` is set and the name of `path` or one of
+///   its directories only differs in case from an entry already in the tree. See `fold_key()`.
+/// - Any error returned when reading the metadata of `path`.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// # use std::io::ErrorKind;
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-insert-path");
+/// # let _ = remove_dir_all(&base);
+/// let album: PathBuf = base.join("Artist").join("Album");
+/// create_dir_all(&album).unwrap();
+/// write(base.join("Artist").join("Artist.png"), "").unwrap();
+/// write(base.join("intro.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// write(album.join("song.mp3"), "").unwrap();
+/// insert_path(&mut tree, &base, &album.join("song.mp3"), &config).unwrap();
+///
+/// let mut scanned: Node = Node::new();
+/// populate(&base, &mut scanned, &config).unwrap();
+/// assert_eq!(tree, scanned);
+/// assert_eq!(tree["Artist"].child["Album"].child["song"].parent, Path::new("Artist/Album"));
+///
+/// // Paths outside of base and non-audio files
+/// let outside = insert_path(&mut tree, &base, &env::temp_dir().join("song.mp3"), &config);
+/// assert_eq!(outside.unwrap_err().kind(), ErrorKind::InvalidInput);
+/// let text = insert_path(&mut tree, &base, &album.join("notes.txt"), &config);
+/// assert_eq!(text.unwrap_err().kind(), ErrorKind::Unsupported);
+/// assert_eq!(tree, scanned);
+/// ```
+pub fn insert_path(node: &mut Node, base: &Path, path: &Path, config: &Config) -> Result<()> {
+    insert_entry(node, base, path, EntryKind::Audio, None, config)
+}
+
+/// Helper function that inserts the directory at `path` into `node`, where `node` was populated
+/// from `base`, the way `insert_path()` inserts audio files, without reading it. When the
+/// directory is not in the tree yet, it is created with `error` as its `Entry.error`; an existing
+/// Entry is left as it is.
+///
+/// # Errors
+/// The errors of `insert_path()`, where `ErrorKind::Unsupported` is returned for directories
+/// that are junk, hidden or excluded, and that `populate()` would leave out.
+pub(super) fn insert_dir(
+    node: &mut Node,
+    base: &Path,
+    path: &Path,
+    error: Option<ScanIssue>,
+    config: &Config,
+) -> Result<()> {
+    insert_entry(node, base, path, EntryKind::Directory, error, config)
+}
+
+/// Helper function that inserts the entry of kind `kind` at `path`, as described in
+/// `insert_path()` for audio files and `insert_dir()` for directories.
+fn insert_entry(
+    node: &mut Node,
+    base: &Path,
+    path: &Path,
+    kind: EntryKind,
+    error: Option<ScanIssue>,
+    config: &Config,
+) -> Result<()> {
+    // Insert below the directory `populate()` scans when `base` is a symbolic link
+    let canonical: PathBuf = scan_base(base, config);
+    let rebased: PathBuf = match path.strip_prefix(base) {
+        Ok(relative) if canonical != base => canonical.join(relative),
+        _ => path.to_path_buf(),
+    };
+    let (base, path): (&Path, &Path) = (&canonical, &rebased);
+
+    let relative: &Path = path.strip_prefix(base).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} is not below {}", path.display(), base.display()),
+        )
+    })?;
+    let names: Vec<&OsStr> = relative
+        .components()
+        .map(|component| match component {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        })
+        .collect::<Option<Vec<&OsStr>>>()
+        .filter(|names| !names.is_empty())
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not a file below {}", path.display(), base.display()),
+            )
+        })?;
+
+    let mut scan: Scan = Scan::new(base, config);
+    scan.enter(base);
+
+    // Build every entry first, so nothing is inserted if the file turns out to be rejected
+    let chain: &[&OsStr] = match kind {
+        EntryKind::Directory => &names,
+        _ => &names[..names.len() - 1],
+    };
+    if kind == EntryKind::Directory
+        && ((!config.include_hidden && is_hidden(path)) || scan.is_excluded(path, true))
+    {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            format!("{} is left out of the tree", path.display()),
+        ));
+    }
+    let mut dir: PathBuf = base.to_path_buf();
+    let mut dirs: Vec<(String, Entry)> = Vec::with_capacity(chain.len());
+    let mut level: Option<&Node> = Some(node);
+    for name in chain {
+        dir.push(name);
+        if scan.is_junk(&dir, true) {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("{} is below a junk directory", path.display()),
+            ));
+        }
+        scan.enter(&dir);
+        let key: String = match level.and_then(|level| find_child(level, &dir)) {
+            Some((key, existing)) => {
+                level = Some(&existing.child);
+                key.clone()
+            }
+            None => {
+                check_folded(level, &dir, config)?;
+                let key: String = level.map_or_else(
+                    || {
+                        let stem: &OsStr = Path::new(name).file_stem().unwrap_or(OsStr::new(""));
+                        name_key(stem, config.normalize_keys)
+                    },
+                    |level| unique_key(level, &dir, config.normalize_keys, config.fold_case),
+                );
+                level = None;
+                key
+            }
+        };
+        let mut entry: Entry = Entry::new(
+            dir.clone(),
+            EntryKind::Directory,
+            scan.art(&dir),
+            scan.keys.clone(),
+        );
+        entry.modified = metadata(to_long_path(&dir))
+            .and_then(|m| m.modified())
+            .ok()
+            .map(whole_seconds);
+        scan.keys.push(&key);
+        entry.id = entry_id(&scan.keys);
+        dirs.push((key, entry));
+    }
+    if kind == EntryKind::Directory {
+        // Only a directory that is new gets the error
+        if let Some((_, entry)) = dirs.last_mut().filter(|_| level.is_none()) {
+            entry.error = error;
+        }
+        insert_under(node, dirs.into_iter(), None);
+        return Ok(());
+    }
+
+    let mime: &str = match scan.is_junk(path, false) {
+        true => None,
+        false => scan.audio_mime(path),
+    }
+    .ok_or_else(|| {
+        Error::new(
+            ErrorKind::Unsupported,
+            format!("{} is not an allowed audio file", path.display()),
+        )
+    })?;
+
+    check_folded(level, path, config)?;
+    let key: String = level.map_or_else(
+        || {
+            name_key(
+                path.file_stem().unwrap_or(OsStr::new("")),
+                config.normalize_keys,
+            )
+        },
+        |level| unique_key(level, path, config.normalize_keys, config.fold_case),
+    );
+    let mut leaf: Entry = Entry::new(
+        path.to_path_buf(),
+        EntryKind::Audio,
+        scan.art(path),
+        scan.keys.clone(),
+    );
+    leaf.id = entry_id(&scan.keys.join(&key));
+    leaf.mime = Some(String::from(mime));
+    let metadata: Metadata = metadata(to_long_path(path))?;
+    if config.skip_empty_files && metadata.len() == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("{} is empty", path.display()),
+        ));
+    }
+    leaf.size = metadata.len();
+    leaf.modified = metadata.modified().ok().map(whole_seconds);
+
+    insert_under(node, dirs.into_iter(), Some((key, leaf)));
+    Ok(())
+}
+
+/// Helper function that returns an `ErrorKind::AlreadyExists` error if `Config.fold_case` is set
+/// and `node` has a child whose file name only differs in case from that of `path`.
+fn check_folded(node: Option<&Node>, path: &Path, config: &Config) -> Result<()> {
+    match node
+        .filter(|_| config.fold_case)
+        .and_then(|node| folded_sibling(node, path))
+    {
+        Some(entry) => Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!(
+                "{} only differs in case from {}",
+                path.display(),
+                entry.src.display()
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Helper function that reads the directory of `entry` alone, for `populate_resumable()`, where
+/// `keys` lead to `entry` from the root of a Node tree populated from `base`. Its audio files are
+/// inserted into it as `populate()` would insert them, and its subdirectories without being
+/// read. `entries` counts the entries of the whole tree against `Config.max_entries`.
+///
+/// Returns the keys of the subdirectories inserted, in file name order, and whether
+/// `Config.max_entries` was reached. On error, `entry` is left without children, to be read
+/// again from scratch.
+pub(super) fn read_one_dir(
+    base: &Path,
+    keys: &[String],
+    entry: &mut Entry,
+    config: &Config,
+    entries: &Arc<AtomicUsize>,
+    cancel: &AtomicBool,
+) -> Result<(Vec<String>, bool)> {
+    let mut scan: Scan = Scan {
+        cancel: Some(cancel),
+        entries: Arc::clone(entries),
+        unread: Some(Vec::new()),
+        ..Scan::new(base, config)
+    };
+    scan.enter(base);
+    scan.ancestors.extend(dir_id(base).ok());
+
+    // Enter the ancestors of the directory, and the directory itself, as `descend()` did
+    let relative: &Path = entry.src.strip_prefix(base).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} is not below {}", entry.src.display(), base.display()),
+        )
+    })?;
+    let mut dir: PathBuf = base.to_path_buf();
+    for component in relative.components() {
+        dir.push(component);
+        scan.enter(&dir);
+        scan.ancestors.extend(dir_id(&dir).ok());
+    }
+    scan.depth = keys.len();
+    scan.keys = keys.iter().collect();
+
+    let mut progress: Progress = Progress::new(None);
+    entry.scanned_at = Some(whole_seconds(SystemTime::now()));
+    let mut visited: Result<()> = Ok(());
+    match scan.read(entry, &mut progress) {
+        Ok(read) => {
+            let mut frame: Frame = Frame {
+                key: None,
+                entry: std::mem::take(entry),
+                entered: false,
+                read,
+                kept: Node::new().into_iter(),
+                previous: None,
+            };
+            while let Some(next) = frame.read.next() {
+                if scan.stats.limit_reached {
+                    break;
+                }
+                if let Err(error) = scan.visit(&mut frame, next, &mut progress) {
+                    visited = Err(error);
+                    break;
+                }
+            }
+            *entry = frame.entry;
+        }
+        Err(error) => visited = Err(error),
+    }
+
+    if let Err(error) = visited {
+        entry.child.clear();
+        entry.error = None;
+        entry.scanned_at = None;
+        return Err(error);
+    }
+    Ok((scan.unread.unwrap_or_default(), scan.stats.limit_reached))
+}
+
+/// Helper function that looks up the art of the directory at `dir` and of its children again, the
+/// way `populate()` does, where `node` was populated from `base`. These are the entries whose art
+/// may be an image directly inside `dir`. When `dir` is `base`, the top-level entries of `node`
+/// are updated. Nothing is updated when `dir` is not in the tree.
+pub(super) fn refresh_art(node: &mut Node, base: &Path, dir: &Path, config: &Config) {
+    let mut scan: Scan = Scan::new(base, config);
+    scan.enter(base);
+
+    let Ok(relative) = dir.strip_prefix(base) else {
+        return;
+    };
+    let mut path: PathBuf = base.to_path_buf();
+    let mut children: &mut Node = node;
+    for component in relative.components() {
+        let Component::Normal(name) = component else {
+            return;
+        };
+        path.push(name);
+        scan.enter(&path);
+        let Some((key, _)) = find_child(children, &path) else {
+            return;
+        };
+        let key: String = key.clone();
+        let Some(entry) = children.get_mut(&key) else {
+            return;
+        };
+        if path == dir {
+            entry.art = scan.art(&path);
+        }
+        children = &mut entry.child;
+    }
+
+    for child in children.values_mut() {
+        // Directories get art after their own override file is read, as in `descend()`
+        let entered: bool = child.is_dir() && scan.enter(&child.src);
+        child.art = scan.art(&child.src);
+        if entered {
+            scan.overrides.pop();
+        }
+    }
+}
+
+/// Helper function that inserts `leaf` into `node` below the chain of directory entries `dirs`,
+/// creating the ones that are missing and recomputing their `size` and `modified` fields. Without
+/// a `leaf`, only the missing directories are created.
+fn insert_under(
+    node: &mut Node,
+    mut dirs: std::vec::IntoIter<(String, Entry)>,
+    leaf: Option<(String, Entry)>,
+) {
+    let Some((key, dir)) = dirs.next() else {
+        if let Some((key, leaf)) = leaf {
+            node.insert(key, leaf);
+        }
+        return;
+    };
+
+    let entry: &mut Entry = node.entry(key).or_insert(dir);
+    insert_under(&mut entry.child, dirs, leaf);
+    entry.size = entry.child.values().map(|child| child.size).sum();
+    entry.modified = latest_modified(&entry.child).or(entry.modified);
+}
+
+/// Helper function that populates a single Node tree from several entry points.
+///
+/// Every root is inserted as a top-level directory Entry keyed by its final path component, with
+/// the contents of the root as its children. When two roots share a final path component, the
+/// later ones are suffixed with their position among the colliding roots, starting at 2, so
+/// `/mnt/ssd/Music` and `/mnt/external/Music` become `Music` and `Music (2)`.
+///
+/// Roots may be glob patterns (see `is_glob_path()`), expanded with `expand_dirs()` when the
+/// scan starts: the directories a pattern matches take its place among `roots`, sorted by path,
+/// so their keys are the same from one scan to the next. A directory matched more than once is
+/// only scanned once.
+///
+/// # Errors
+/// - An error of kind `ErrorKind::NotFound` wrapping `ConfigError::NoMatch` if a glob pattern
+///   matches no directory, rather than an empty tree.
+/// - The errors of `populate()` for every root.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let dir: PathBuf = env::temp_dir().join("vfh-doc-populate-all");
+/// # let _ = remove_dir_all(&dir);
+/// let ssd: PathBuf = dir.join("ssd").join("Music");
+/// let external: PathBuf = dir.join("external").join("Music");
+/// let books: PathBuf = dir.join("external").join("Books");
+/// for root in [&ssd, &external, &books] {
+///     create_dir_all(root).unwrap();
+/// }
+/// write(ssd.join("a.mp3"), "").unwrap();
+/// write(external.join("b.mp3"), "").unwrap();
+/// write(books.join("c.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let tree: Node = populate_all(&[ssd, external, books], &config).unwrap();
+/// assert_eq!(tree.keys().collect::<Vec<_>>(), ["Books", "Music", "Music (2)"]);
+/// assert_eq!(tree["Music"].child.keys().collect::<Vec<_>>(), ["a"]);
+/// assert_eq!(tree["Music (2)"].child.keys().collect::<Vec<_>>(), ["b"]);
+///
+/// let b: &Entry = &tree["Music (2)"].child["b"];
+/// assert_eq!(tree.resolve_parent(b), Some(&tree["Music (2)"]));
+/// ```
+///
+/// Glob patterns:
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// use std::io::ErrorKind;
+///
+/// let dir: PathBuf = env::temp_dir().join("vfh-doc-populate-all-glob");
+/// # let _ = remove_dir_all(&dir);
+/// for file in ["Artist/FLAC/a.flac", "Band/FLAC/b.flac", "Band/MP3/b.mp3"] {
+///     create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+///     write(dir.join(file), "").unwrap();
+/// }
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let (tree, stats): (Node, ScanStats) =
+///     populate_all_with_stats(&[dir.join("*").join("FLAC")], &config).unwrap();
+/// assert_eq!(tree.keys().collect::<Vec<_>>(), ["FLAC", "FLAC (2)"]);
+/// assert_eq!(tree["FLAC"].src, dir.join("Artist").join("FLAC"));
+/// assert_eq!(tree["FLAC (2)"].child.keys().collect::<Vec<_>>(), ["b"]);
+/// assert_eq!(stats.files, 2);
+///
+/// let error = populate_all(&[dir.join("*").join("OGG")], &config).unwrap_err();
+/// assert_eq!(error.kind(), ErrorKind::NotFound);
+/// assert_eq!(
+///     error.to_string(),
+///     format!("no directory matches the base {}", dir.join("*").join("OGG").display())
+/// );
+/// ```
+pub fn populate_all(roots: &[PathBuf], config: &Config) -> Result<Node> {
+    Ok(populate_all_with_stats(roots, config)?.0)
+}
+
+/// Helper function that populates a single Node tree from several entry points like
+/// `populate_all()` does, also returning the `ScanStats` of all the roots together.
+pub fn populate_all_with_stats(roots: &[PathBuf], config: &Config) -> Result<(Node, ScanStats)> {
+    let mut tree: Node = Node::new();
+    let mut stats: ScanStats = ScanStats::default();
+    let entries: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+
+    for root in &expand_roots(roots)? {
+        let name: String = name_key(
+            root.file_name().unwrap_or(root.as_os_str()),
+            config.normalize_keys,
+        );
+        let mut key: String = name.clone();
+        let mut index: usize = 1;
+        while tree.contains_key(&key) {
+            index += 1;
+            key = format!("{} ({})", name, index);
+        }
+
+        let root: PathBuf = scan_base(root, config);
+        let mut entry: Entry = Entry::new(
+            root.clone(),
+            EntryKind::Directory,
+            update_art(&root, config),
+            PathBuf::new(),
+        );
+        entry.id = entry_id(Path::new(&key));
+        let scan: Scan = Scan {
+            keys: PathBuf::from(&key),
+            entries: Arc::clone(&entries),
+            ..Scan::new(&root, config)
+        };
+        let (child, root_stats): (Node, ScanStats) = scan_tree(scan, &mut Progress::new(None))?;
+        entry.child = child;
+        stats.add(root_stats);
+        entry.size = entry.child.values().map(|child| child.size).sum();
+        entry.modified = latest_modified(&entry.child);
+        tree.insert(key, entry);
+    }
+
+    Ok((tree, stats))
+}
+
+/// Helper function that builds one Node tree per library declared in `Config.libraries`, keyed
+/// by library name.
+///
+/// Every library is scanned with `populate()` using the rest of the configuration, with its own
+/// `LibraryConfig.extensions` replacing `AUDIO_EXTENSIONS` and `Config.extensions` when set.
+/// When no library is declared, a single anonymous library is returned under `DEFAULT_LIBRARY`:
+/// the tree of `Config.base`, or the tree built by `populate_all()` when `Config.bases` is set.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let dir: PathBuf = env::temp_dir().join("vfh-doc-populate-libraries");
+/// # let _ = remove_dir_all(&dir);
+/// create_dir_all(dir.join("music")).unwrap();
+/// create_dir_all(dir.join("books")).unwrap();
+/// write(dir.join("music").join("song.mp3"), "").unwrap();
+/// write(dir.join("books").join("novel.m4b"), "").unwrap();
+/// write(dir.join("books").join("intro.mp3"), "").unwrap();
+///
+/// write(
+///     dir.join("config.toml"),
+///     "[libraries]
+///      music = 'music'
+///      books = { base = 'books', extensions = ['m4b'] }",
+/// )
+/// .unwrap();
+/// let config: Config = Config::from_path(&dir.join("config.toml")).unwrap();
+/// let libraries: BTreeMap<String, Node> = populate_libraries(&config).unwrap();
+/// assert_eq!(libraries.keys().collect::<Vec<_>>(), ["books", "music"]);
+/// assert_eq!(libraries["books"].keys().collect::<Vec<_>>(), ["novel"]);
+/// assert_eq!(libraries["music"].keys().collect::<Vec<_>>(), ["song"]);
+///
+/// // Only `base`
+/// write(dir.join("config.toml"), "base = 'music'").unwrap();
+/// let config: Config = Config::from_path(&dir.join("config.toml")).unwrap();
+/// let libraries: BTreeMap<String, Node> = populate_libraries(&config).unwrap();
+/// assert_eq!(libraries.keys().collect::<Vec<_>>(), [DEFAULT_LIBRARY]);
+/// assert_eq!(libraries[DEFAULT_LIBRARY].keys().collect::<Vec<_>>(), ["song"]);
+/// ```
+pub fn populate_libraries(config: &Config) -> Result<BTreeMap<String, Node>> {
+    let libraries: BTreeMap<String, (Node, ScanStats)> = populate_libraries_with_stats(config)?;
+    Ok(libraries
+        .into_iter()
+        .map(|(name, (tree, _))| (name, tree))
+        .collect())
+}
+
+/// Helper function that builds one Node tree per library like `populate_libraries()` does,
+/// along with the `ScanStats` of its scan.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let dir: PathBuf = env::temp_dir().join("vfh-doc-populate-libraries-with-stats");
+/// # let _ = remove_dir_all(&dir);
+/// create_dir_all(dir.join("music").join("Album")).unwrap();
+/// write(dir.join("music").join("Album").join("song.mp3"), "").unwrap();
+/// write(dir.join("music").join("cover.txt"), "").unwrap();
+///
+/// let mut config: Config = toml::from_str("base = ''").unwrap();
+/// config.base = dir.join("music");
+/// let libraries: BTreeMap<String, (Node, ScanStats)> =
+///     populate_libraries_with_stats(&config).unwrap();
+/// let (tree, stats): &(Node, ScanStats) = &libraries[DEFAULT_LIBRARY];
+/// assert_eq!(count_songs(tree), 1);
+/// assert_eq!((stats.dirs, stats.files, stats.skipped_extension), (2, 1, 1));
+/// ```
+pub fn populate_libraries_with_stats(
+    config: &Config,
+) -> Result<BTreeMap<String, (Node, ScanStats)>> {
+    let mut libraries: BTreeMap<String, (Node, ScanStats)> = BTreeMap::new();
+
+    if config.libraries.is_empty() {
+        let library: (Node, ScanStats) =
+            match config.bases.is_empty() && !is_glob_path(&config.base) {
+                true => populate_from_with_stats(&config.base, config)?,
+                false => populate_all_with_stats(&config.roots(), config)?,
+            };
+        libraries.insert(String::from(DEFAULT_LIBRARY), library);
+        return Ok(libraries);
+    }
+
+    for (name, library) in &config.libraries {
+        let mut library_config: Config = config.clone();
+        library_config.base = scan_base(&library.base, config);
+        library_config.bases.clear();
+        library_config.libraries.clear();
+
+        // Restrict the extensions like an override file in the base directory would
+        let mut scan: Scan = Scan::new(&library_config.base, &library_config);
+        if let Some(extensions) = &library.extensions {
+            let overrides: DirOverrides = DirOverrides {
+                extensions: Some(extensions.clone()),
+                ..DirOverrides::default()
+            };
+            scan.overrides
+                .push((library_config.base.clone(), overrides));
+        }
+
+        let library: (Node, ScanStats) = scan_tree(scan, &mut Progress::new(None))?;
+        libraries.insert(name.clone(), library);
+    }
+
+    Ok(libraries)
+}
+
+/// Helper function that merges the entries of `src` into `dest`, for instance to combine the trees
+/// of several scans.
+///
+/// When both trees have an entry under the same key:
+/// - Two directories are merged into one: their children are merged recursively, and the other
+///   fields come from the most recently modified of the two.
+/// - Two audio files are not merged: the most recently modified one is kept.
+/// - A directory always wins over an audio file, whatever their modification times, so no
+///   subtree is ever lost.
+///
+/// When the modification times are equal or either one is unknown, the entry from `src` wins.
+/// The `size` and `modified` fields of merged directories are recomputed from their children.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::time::{Duration, UNIX_EPOCH};
+/// let entry = |kind: EntryKind, seconds: u64, src: &str| -> Entry {
+///     let mut entry: Entry = Entry::default();
+///     entry.kind = kind;
+///     entry.src = PathBuf::from(src);
+///     entry.modified = Some(UNIX_EPOCH + Duration::from_secs(seconds));
+///     entry
+/// };
+///
+/// let mut dest: Node = Node::new();
+/// let mut album: Entry = entry(EntryKind::Directory, 10, "/old/Album");
+/// album.child.insert(String::from("1"), entry(EntryKind::Audio, 10, "/old/Album/1.mp3"));
+/// album.child.insert(String::from("2"), entry(EntryKind::Audio, 30, "/old/Album/2.mp3"));
+/// dest.insert(String::from("Album"), album);
+/// dest.insert(String::from("Live"), entry(EntryKind::Audio, 50, "/old/Live.mp3"));
+///
+/// let mut src: Node = Node::new();
+/// let mut album: Entry = entry(EntryKind::Directory, 20, "/new/Album");
+/// album.child.insert(String::from("2"), entry(EntryKind::Audio, 20, "/new/Album/2.mp3"));
+/// album.child.insert(String::from("3"), entry(EntryKind::Audio, 20, "/new/Album/3.mp3"));
+/// src.insert(String::from("Album"), album);
+/// src.insert(String::from("Live"), entry(EntryKind::Directory, 0, "/new/Live"));
+///
+/// merge(&mut dest, src);
+/// let album: &Entry = &dest["Album"];
+/// assert_eq!(album.src, PathBuf::from("/new/Album"));
+/// assert_eq!(album.child.keys().collect::<Vec<_>>(), ["1", "2", "3"]);
+/// assert_eq!(album.child["2"].src, PathBuf::from("/old/Album/2.mp3"));
+/// assert_eq!(album.modified, Some(UNIX_EPOCH + Duration::from_secs(30)));
+///
+/// // Directories win over audio files
+/// assert_eq!(dest["Live"].kind, EntryKind::Directory);
+/// ```
+pub fn merge(dest: &mut Node, src: Node) {
+    for (key, entry) in src {
+        let merged: Entry = match dest.remove(&key) {
+            Some(existing) => merge_entries(existing, entry),
+            None => entry,
+        };
+        dest.insert(key, merged);
+    }
+}
+
+/// Helper function that merges two entries stored under the same key, as described in `merge()`.
+fn merge_entries(old: Entry, new: Entry) -> Entry {
+    let old_is_newer: bool = matches!((old.modified, new.modified), (Some(a), Some(b)) if a > b);
+
+    match (old.kind, new.kind) {
+        (EntryKind::Directory, EntryKind::Audio) => old,
+        (EntryKind::Audio, EntryKind::Directory) => new,
+        (EntryKind::Audio, EntryKind::Audio) => match old_is_newer {
+            true => old,
+            false => new,
+        },
+        (EntryKind::Directory, EntryKind::Directory) => {
+            let mut child: Node = old.child;
+            merge(&mut child, new.child);
+            let modified: Option<SystemTime> =
+                latest_modified(&child).max(old.modified).max(new.modified);
+
+            let mut winner: Entry = match old_is_newer {
+                true => Entry { child, ..old },
+                false => Entry { child, ..new },
+            };
+            winner.size = winner.child.values().map(|entry| entry.size).sum();
+            winner.modified = modified;
+            winner
+        }
+    }
+}
+
+/// Helper function that removes every directory entry of `node` and its descendants whose
+/// subtree contains no `EntryKind::Audio` file, returning how many directories were removed.
+/// Directories flagged with an `Entry.error` are kept, as their contents are unknown.
+///
+/// The `modified` field of directories that lost children is recomputed from the remaining ones.
+/// Setting `Config.prune_empty_dirs` makes `populate()` do the same while scanning, without ever
+/// inserting the directories left out, and gives the same tree. Art is looked up on the file
+/// system rather than in the parent entries, so it is the same either way. A Node tree without
+/// any audio file is left empty.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-prune-empty-dirs");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Album").join("Scans").join("Booklet")).unwrap();
+/// create_dir_all(base.join("Album").join("Disc 1")).unwrap();
+/// create_dir_all(base.join("Empty").join("Empty")).unwrap();
+/// write(base.join("Album").join("Scans").join("Booklet").join("notes.pdf"), "").unwrap();
+/// write(base.join("Album").join("Disc 1").join("cover.cue"), "").unwrap();
+/// write(base.join("Album").join("Disc 1").join("song.mp3"), "").unwrap();
+/// write(base.join("Album").join("Album.png"), "").unwrap();
+///
+/// let mut config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+/// assert_eq!(count_dirs(&tree), 6);
+///
+/// assert_eq!(prune_empty_dirs(&mut tree), 4);
+/// assert_eq!(tree.keys().collect::<Vec<_>>(), ["Album"]);
+/// assert_eq!(tree["Album"].child.keys().collect::<Vec<_>>(), ["Disc 1"]);
+/// assert_eq!(prune_empty_dirs(&mut tree), 0);
+///
+/// // Inline while scanning
+/// config.prune_empty_dirs = true;
+/// let mut scanned: Node = Node::new();
+/// let stats: ScanStats = populate(&base, &mut scanned, &config).unwrap();
+/// assert_eq!(scanned, tree);
+/// assert_eq!(stats.pruned_dirs, 4);
+/// assert_eq!(scanned["Album"].child["Disc 1"].art, Some(base.join("Album").join("Album.png")));
+///
+/// // No audio at all
+/// let mut empty: Node = Node::new();
+/// populate(&base.join("Empty"), &mut empty, &config).unwrap();
+/// assert!(empty.is_empty());
+/// ```
+pub fn prune_empty_dirs(node: &mut Node) -> usize {
+    let mut removed: usize = 0;
+
+    node.retain(|_, entry| match entry.kind {
+        EntryKind::Audio => true,
+        EntryKind::Directory => {
+            let pruned: usize = prune_empty_dirs(&mut entry.child);
+            if pruned > 0 {
+                entry.modified = latest_modified(&entry.child);
+            }
+            removed += pruned;
+            let kept: bool = !entry.child.is_empty() || entry.error.is_some();
+            if !kept {
+                removed += 1;
+            }
+            kept
+        }
+    });
+
+    removed
+}
+
+/// Helper function that removes every entry of `node` and its descendants for which `pred`
+/// returns false, then drops the directories left without children as a result.
+///
+/// `pred` receives the keys leading from the root of `node` to the entry, the entry's own key
+/// last, together with the entry. A directory is kept as long as any of its descendants is, so
+/// a predicate only written for audio files keeps the directories leading to them. A directory
+/// that was already empty is kept if it satisfies `pred`. The `size` and `modified` fields of
+/// directories that lost children are recomputed from the remaining ones.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::ffi::OsStr;
+/// # use std::fs::{create_dir_all, remove_dir_all, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-retain-entries");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Artist").join("Album")).unwrap();
+/// create_dir_all(base.join("Other").join("Album")).unwrap();
+/// write(base.join("Artist").join("Album").join("1.flac"), "").unwrap();
+/// write(base.join("Artist").join("Album").join("2.mp3"), "").unwrap();
+/// write(base.join("Other").join("Album").join("3.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// // Only FLAC files
+/// let mut flac: Node = tree.clone();
+/// retain_entries(&mut flac, &mut |_, entry| {
+///     entry.src.extension() == Some(OsStr::new("flac"))
+/// });
+/// assert_eq!(flac.keys().collect::<Vec<_>>(), ["Artist"]);
+/// let album: &Entry = &flac["Artist"].child["Album"];
+/// assert_eq!(album.child.keys().collect::<Vec<_>>(), ["1"]);
+///
+/// // Only entries under a given artist
+/// let mut artist: Node = tree.clone();
+/// retain_entries(&mut artist, &mut |keys, _| keys[0] == "Other");
+/// assert_eq!(artist.keys().collect::<Vec<_>>(), ["Other"]);
+/// assert_eq!(artist, Node::from([(String::from("Other"), tree["Other"].clone())]));
+/// ```
+pub fn retain_entries(node: &mut Node, pred: &mut impl FnMut(&[String], &Entry) -> bool) {
+    retain_under(node, pred, &mut Vec::new());
+}
+
+/// Helper function that performs `retain_entries()` on the children `node` of the entry reached
+/// through `keys`. Returns whether any entry was removed from `node` or its descendants.
+fn retain_under(
+    node: &mut Node,
+    pred: &mut impl FnMut(&[String], &Entry) -> bool,
+    keys: &mut Vec<String>,
+) -> bool {
+    let mut removed: bool = false;
+
+    node.retain(|key, entry| {
+        keys.push(key.clone());
+        let keep: bool = match entry.kind {
+            EntryKind::Audio => pred(keys, entry),
+            EntryKind::Directory if entry.child.is_empty() => pred(keys, entry),
+            EntryKind::Directory => {
+                if retain_under(&mut entry.child, pred, keys) {
+                    removed = true;
+                    entry.size = entry.child.values().map(|child| child.size).sum();
+                    entry.modified = latest_modified(&entry.child);
+                }
+                !entry.child.is_empty()
+            }
+        };
+        keys.pop();
+        removed |= !keep;
+        keep
+    });
+
+    removed
+}
+
+/// Helper function that returns the latest `Entry.modified` among the entries directly in `node`.
+/// Since directories already carry the latest time of their descendants, this covers the whole
+/// subtree.
+pub(super) fn latest_modified(node: &Node) -> Option<SystemTime> {
+    node.values().filter_map(|entry| entry.modified).max()
+}
+
+/// Identity of a directory, telling whether two paths lead to the same directory: its device and
+/// inode numbers.
+#[cfg(unix)]
+type DirId = (u64, u64);
+
+/// Identity of a directory, telling whether two paths lead to the same directory: its canonical
+/// path.
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+/// Helper function that returns the directory a scan of `dir` starts from: `dir` canonicalized
+/// when `Config.canonicalize_base` is set, or `dir` itself when it is unset or `dir` cannot be
+/// canonicalized, in which case reading it reports the error.
+pub(super) fn scan_base(dir: &Path, config: &Config) -> PathBuf {
+    match config.canonicalize_base {
+        true => dir
+            .canonicalize()
+            .map_or_else(|_| dir.to_path_buf(), |dir| strip_long_path(&dir)),
+        false => dir.to_path_buf(),
+    }
+}
+
+/// Helper function that returns `roots` with every glob pattern replaced by the directories it
+/// matches, as described in `populate_all()`, skipping duplicates.
+fn expand_roots(roots: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut expanded: Vec<PathBuf> = Vec::new();
+    for root in roots {
+        let matched: Vec<PathBuf> = match is_glob_path(root) {
+            true => expand_dirs(root),
+            false => vec![root.clone()],
+        };
+        if matched.is_empty() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                ConfigError::NoMatch(root.clone()),
+            ));
+        }
+        for dir in matched {
+            if !expanded.contains(&dir) {
+                expanded.push(dir);
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+/// Helper function that checks that `dir`, the directory a scan starts from, is an existing
+/// directory whose contents can be listed, so that an unusable base is reported instead of
+/// producing an empty tree.
+///
+/// The errors wrap the `ConfigError` describing the problem, which `Error::get_ref()` returns:
+/// - `ConfigError::NotFound`, of kind `ErrorKind::NotFound`, if `dir` does not exist.
+/// - `ConfigError::NotADirectory`, of kind `ErrorKind::NotADirectory`, if `dir` is a file.
+/// - `ConfigError::PermissionDenied`, of kind `ErrorKind::PermissionDenied`, if `dir` cannot be
+///   listed.
+///
+/// Any other error met while reading `dir` is returned as-is.
+pub(super) fn check_base(dir: &Path) -> Result<()> {
+    let base_error = |kind: ErrorKind, error: fn(PathBuf) -> ConfigError| {
+        Error::new(kind, error(dir.to_path_buf()))
+    };
+    match metadata(to_long_path(dir)) {
+        Ok(metadata) if metadata.is_dir() => {}
+        Ok(_) => {
+            return Err(base_error(
+                ErrorKind::NotADirectory,
+                ConfigError::NotADirectory,
+            ))
+        }
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            return Err(base_error(ErrorKind::NotFound, ConfigError::NotFound))
+        }
+        Err(error) if error.kind() == ErrorKind::PermissionDenied => {
+            return Err(base_error(
+                ErrorKind::PermissionDenied,
+                ConfigError::PermissionDenied,
+            ))
+        }
+        Err(error) => return Err(error),
+    }
+
+    match open_dir(dir) {
+        Ok(_) => Ok(()),
+        Err(error) if error.kind() == ErrorKind::PermissionDenied => Err(base_error(
+            ErrorKind::PermissionDenied,
+            ConfigError::PermissionDenied,
+        )),
+        Err(error) => Err(error),
+    }
+}
+
+/// Helper function that returns the directory a scan of `dir` starts from like `scan_base()`
+/// does, with `tokio::fs`.
+#[cfg(feature = "tokio")]
+async fn scan_base_async(dir: &Path, config: &Config) -> PathBuf {
+    match config.canonicalize_base {
+        true => tokio::fs::canonicalize(dir)
+            .await
+            .map_or_else(|_| dir.to_path_buf(), |dir| strip_long_path(&dir)),
+        false => dir.to_path_buf(),
+    }
+}
+
+/// Number of times opening a directory is retried when the process runs out of file
+/// descriptors, as other directories being read are likely to be closed soon.
+pub const OPEN_RETRIES: u32 = 8;
+
+/// Time waited before the first retry of opening a directory, doubled after every retry.
+const OPEN_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// Helper function that returns whether `error` means that the process, or the whole system, has
+/// too many files open (`EMFILE` and `ENFILE` on Unix).
+fn is_out_of_descriptors(error: &Error) -> bool {
+    #[cfg(unix)]
+    return matches!(error.raw_os_error(), Some(23 | 24));
+    #[cfg(windows)]
+    return error.raw_os_error() == Some(4);
+    #[cfg(not(any(unix, windows)))]
+    return false;
+}
+
+/// Helper function that opens `dir` for reading, retrying up to `OPEN_RETRIES` times while the
+/// process is out of file descriptors.
+fn open_dir(dir: &Path) -> Result<ReadDir> {
+    #[cfg(test)]
+    if let Some(kind) = testing::failure(dir) {
+        match kind {
+            ErrorKind::TimedOut => thread::sleep(testing::HANG),
+            kind => return Err(Error::from(kind)),
+        }
+    }
+    let mut delay: Duration = OPEN_RETRY_DELAY;
+    let dir: PathBuf = to_long_path(dir);
+    for _ in 0..OPEN_RETRIES {
+        match read_dir(&dir) {
+            Err(error) if is_out_of_descriptors(&error) => {
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            read => return read,
+        }
+    }
+    read_dir(&dir)
+}
+
+/// Helper function that opens `dir` for reading like `open_dir()` does, with `tokio::fs`.
+#[cfg(feature = "tokio")]
+async fn open_dir_async(dir: &Path) -> Result<tokio::fs::ReadDir> {
+    #[cfg(test)]
+    if let Some(kind) = testing::failure(dir) {
+        match kind {
+            ErrorKind::TimedOut => tokio::time::sleep(testing::HANG).await,
+            kind => return Err(Error::from(kind)),
+        }
+    }
+    let mut delay: Duration = OPEN_RETRY_DELAY;
+    let dir: PathBuf = to_long_path(dir);
+    for _ in 0..OPEN_RETRIES {
+        match tokio::fs::read_dir(&dir).await {
+            Err(error) if is_out_of_descriptors(&error) => {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            read => return read,
+        }
+    }
+    tokio::fs::read_dir(&dir).await
+}
+
+/// Counting semaphore bounding the number of directories a parallel scan lists at the same time.
+/// See `Config.max_open_dirs`.
+///
+/// # Fields
+/// `open`: `Mutex<usize>` -> Number of directories being listed.
+/// `closed`: `Condvar` -> Notified every time one of them is done.
+/// `limit`: `usize` -> Maximum value of `open`, at least 1.
+#[cfg(feature = "rayon")]
+struct ReadLimit {
+    open: Mutex<usize>,
+    closed: Condvar,
+    limit: usize,
+}
+
+#[cfg(feature = "rayon")]
+impl ReadLimit {
+    fn new(limit: usize) -> ReadLimit {
+        ReadLimit {
+            open: Mutex::new(0),
+            closed: Condvar::new(),
+            limit: limit.max(1),
+        }
+    }
+
+    /// Helper function that waits until fewer than `limit` directories are being listed, and
+    /// returns the permit to list one until it is dropped.
+    fn acquire(&self) -> ReadPermit<'_> {
+        let mut open = self.open.lock().unwrap_or_else(PoisonError::into_inner);
+        while *open >= self.limit {
+            open = self
+                .closed
+                .wait(open)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *open += 1;
+        ReadPermit(self)
+    }
+}
+
+/// Permit to list a directory, returned to its `ReadLimit` when dropped.
+#[cfg(feature = "rayon")]
+struct ReadPermit<'a>(&'a ReadLimit);
+
+#[cfg(feature = "rayon")]
+impl Drop for ReadPermit<'_> {
+    fn drop(&mut self) {
+        *self.0.open.lock().unwrap_or_else(PoisonError::into_inner) -= 1;
+        self.0.closed.notify_one();
+    }
+}
+
+/// Helper function that returns the entries of `dir`, each of which may have failed to be read.
+/// Opening `dir` is retried while the process is out of file descriptors. See `open_dir()`.
+///
+/// When `Config.read_timeout_secs` is set, `dir` is read on a thread of its own, and an error of
+/// kind `ErrorKind::TimedOut` is returned once the timeout elapses without the whole directory
+/// being read. The thread is left to finish, or hang, on its own.
+fn read_entries(dir: &Path, config: &Config) -> Result<Vec<Result<DirEntry>>> {
+    let Some(timeout) = config.read_timeout_secs.map(Duration::from_secs) else {
+        return Ok(open_dir(dir)?.collect());
+    };
+
+    let (sender, receiver) = channel::<Result<Vec<Result<DirEntry>>>>();
+    let path: PathBuf = dir.to_path_buf();
+    thread::spawn(move || {
+        // The scan may have given up on the entries already
+        let _ = sender.send(open_dir(&path).map(|read| read.collect()));
+    });
+    receiver.recv_timeout(timeout).unwrap_or_else(|_| {
+        Err(Error::new(
+            ErrorKind::TimedOut,
+            format!("reading {} timed out", dir.display()),
+        ))
+    })
+}
+
+/// Helper function that returns whether `path` is a symbolic link whose target does not exist, so
+/// that it can never be opened.
+fn is_dangling(path: &Path) -> bool {
+    let path: PathBuf = to_long_path(path);
+    symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+        && metadata(&path).is_err_and(|error| error.kind() == ErrorKind::NotFound)
+}
+
+/// Helper function that returns the `DirId` of the directory at `path`, following symbolic links.
+fn dir_id(path: &Path) -> Result<DirId> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let metadata: Metadata = metadata(to_long_path(path))?;
+        Ok((metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    to_long_path(path).canonicalize()
+}
+
+/// Helper function that returns the `DirId` of the directory at `path` like `dir_id()` does,
+/// with `tokio::fs`.
+#[cfg(feature = "tokio")]
+async fn dir_id_async(path: &Path) -> Result<DirId> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let metadata: Metadata = tokio::fs::metadata(to_long_path(path)).await?;
+        Ok((metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    tokio::fs::canonicalize(to_long_path(path)).await
+}
+
+/// Name, path and file type of an entry listed by `AsyncScan::list()`.
+#[cfg(feature = "tokio")]
+type Listing = (OsString, PathBuf, Result<FileType>);
+
+/// Future filling in the entry of a directory for `AsyncScan::populate()`, resolving to the state
+/// of the directory and its entry.
+#[cfg(feature = "tokio")]
+type Populating<'a> = Pin<Box<dyn Future<Output = Result<(Scan<'a>, Entry)>> + Send + 'a>>;
+
+/// State shared by every directory visited during a single call to `populate_async()`.
+///
+/// # Fields
+/// `root`: `&Path` -> The directory `populate_async()` was called on.
+/// `config`: `&Config` -> The configuration driving the scan.
+/// `shared`: `Arc<Config>` -> A copy of `config` handed to the blocking thread pool.
+/// `reads`: `Semaphore` -> Permits for the directories being listed at the same time.
+/// `cancel`: `&AtomicBool` -> Flag stopping the scan as soon as it is set.
+#[cfg(feature = "tokio")]
+struct AsyncScan<'a> {
+    root: &'a Path,
+    config: &'a Config,
+    shared: Arc<Config>,
+    reads: Semaphore,
+    cancel: &'a AtomicBool,
+}
+
+#[cfg(feature = "tokio")]
+impl<'a> AsyncScan<'a> {
+    /// Helper function that returns the `Scan` state of the directory `populate_async()` was
+    /// called on, before its override file is read.
+    fn state(&self) -> Scan<'a> {
+        Scan {
+            cancel: Some(self.cancel),
+            ..Scan::new(self.root, self.config)
+        }
+    }
+
+    /// Helper function that runs `f` on tokio's blocking thread pool, with a `Scan` state holding
+    /// `overrides` as the overrides in effect.
+    async fn blocking<T: Send + 'static>(
+        &self,
+        overrides: Vec<(PathBuf, DirOverrides)>,
+        f: impl FnOnce(Scan) -> T + Send + 'static,
+    ) -> Result<T> {
+        let root: PathBuf = self.root.to_path_buf();
+        let config: Arc<Config> = Arc::clone(&self.shared);
+        tokio::task::spawn_blocking(move || {
+            f(Scan {
+                overrides,
+                ..Scan::new(&root, &config)
+            })
+        })
+        .await
+        .map_err(Error::other)
+    }
+
+    /// Helper function that lists the directory `dir` like `Scan::list()` does, counting it as
+    /// visited in `state`, with `tokio::fs`. Fails with an error of kind `ErrorKind::TimedOut`
+    /// when listing takes longer than `Config.read_timeout_secs`.
+    async fn list(&self, state: &mut Scan<'a>, dir: &Path) -> Result<Vec<Listing>> {
+        if !tokio::fs::metadata(to_long_path(dir))
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+        {
+            return Ok(Vec::new());
+        }
+
+        state.stats.dirs += 1;
+        let read = async {
+            let _permit = self.reads.acquire().await.map_err(Error::other)?;
+            let mut entries: Vec<Listing> = Vec::new();
+            let mut read = open_dir_async(dir).await?;
+            while let Some(entry) = read.next_entry().await? {
+                entries.push((
+                    entry.file_name(),
+                    strip_long_path(&entry.path()),
+                    entry.file_type().await,
+                ));
+            }
+            Ok::<_, Error>(entries)
+        };
+        match self.config.read_timeout_secs.map(Duration::from_secs) {
+            Some(timeout) => tokio::time::timeout(timeout, read)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(Error::new(
+                        ErrorKind::TimedOut,
+                        format!("reading {} timed out", dir.display()),
+                    ))
+                }),
+            None => read.await,
+        }
+    }
+
+    /// Helper function that reads the subdirectory whose entry is `entry` with its own state
+    /// `state`, as `Scan::descend()` does, flagging `entry` when it cannot be listed. Returns
+    /// `state` and `entry` once done.
+    async fn read(&'a self, mut state: Scan<'a>, mut entry: Entry) -> Result<(Scan<'a>, Entry)> {
+        let src: PathBuf = entry.src.clone();
+        let (overrides, stats, art) = self
+            .blocking(state.overrides.clone(), move |mut state| {
+                state.enter(&src);
+                let art: Option<PathBuf> = state.art(&src);
+                (state.overrides, state.stats, art)
+            })
+            .await?;
+        state.overrides = overrides;
+        state.stats.add(stats);
+        entry.art = art;
+        entry.scanned_at = Some(whole_seconds(SystemTime::now()));
+
+        let src: PathBuf = entry.src.clone();
+        let (state, mut entry): (Scan, Entry) = match self.list(&mut state, &src).await {
+            Ok(read) => self.populate(state, entry, read).await?,
+            Err(error) => {
+                entry.error = Some(ScanIssue::from(error.kind()));
+                state.fail(&src, error)?;
+                (state, entry)
+            }
+        };
+
+        entry.size = entry.child.values().map(|child| child.size).sum();
+        entry.modified = match latest_modified(&entry.child) {
+            Some(modified) => Some(modified),
+            None => tokio::fs::metadata(to_long_path(&entry.src))
+                .await
+                .and_then(|m| m.modified())
+                .ok(),
+        }
+        .map(whole_seconds);
+        Ok((state, entry))
+    }
+
+    /// Helper function that fills in `dir`, the entry of a directory whose entries are `read`,
+    /// like `Scan::populate_parallel()` does, where `state` is the state of `dir`. Subdirectories
+    /// are read concurrently within the returned future, or one after the other in file name
+    /// order when `Config.max_entries` is set. Returns `state` and `dir` once done.
+    fn populate(
+        &'a self,
+        mut state: Scan<'a>,
+        mut dir: Entry,
+        mut read: Vec<Listing>,
+    ) -> Populating<'a> {
+        Box::pin(async move {
+            read.sort_by(|(a, ..), (b, ..)| a.cmp(b));
+
+            // Look at every entry, and the art of every audio file up, at once
+            let (ancestors, depth): (Vec<DirId>, usize) = (state.ancestors.clone(), state.depth);
+            let listed = self
+                .blocking(state.overrides.clone(), move |mut state| {
+                    state.ancestors = ancestors;
+                    state.depth = depth;
+                    let mut listed: Vec<(ScanStats, Option<Listed>, Option<PathBuf>)> = Vec::new();
+                    for (name, path, file_type) in read {
+                        let (stats, entry) = state.classify_apart(&name, path, file_type)?;
+                        let art: Option<PathBuf> = match &entry {
+                            Some(Listed::Audio(path, ..)) => state.art(path),
+                            _ => None,
+                        };
+                        listed.push((stats, entry, art));
+                    }
+                    Ok::<_, Error>(listed)
+                })
+                .await??;
+
+            // Audio files are complete right away, directories once they are read
+            let mut children: Vec<(ScanStats, Option<Child<'a>>)> = Vec::new();
+            for (stats, listed, art) in listed {
+                state.check_cancelled()?;
+                let child: Option<Child> = listed.map(|listed| match listed {
+                    Listed::Dir(path, id) => (
+                        Entry::new(path, EntryKind::Directory, None, PathBuf::new()),
+                        Some(state.child_state(id)),
+                    ),
+                    Listed::Audio(path, mime, metadata) => {
+                        (audio_entry(path, mime, &metadata, art), None)
+                    }
+                });
+                children.push((stats, child));
+            }
+
+            let ordered: bool = self.config.max_entries.is_some();
+            if !ordered {
+                let reads = children.into_iter().map(|(stats, child)| async move {
+                    match child {
+                        Some((entry, Some(next_state))) => {
+                            let (next_state, entry) = self.read(next_state, entry).await?;
+                            Ok::<_, Error>((stats, Some((entry, Some(next_state)))))
+                        }
+                        child => Ok((stats, child)),
+                    }
+                });
+                // Every read finishes, for the error returned to be the first in file name order
+                children = join_all(reads).await.into_iter().collect::<Result<_>>()?;
+            }
+
+            // Insert in file name order, for keys to be resolved, and statistics collected, the
+            // same way as in `populate()`
+            for (stats, child) in children {
+                state.stats.add(stats);
+                let Some((entry, next_state)) = child else {
+                    continue;
+                };
+                if self.config.fold_case && folded_sibling(&dir.child, &entry.src).is_some() {
+                    state.stats.case_collisions.push(entry.src);
+                    continue;
+                }
+                if !state.admit() {
+                    break;
+                }
+                let entry: Entry = match next_state {
+                    Some(next_state) => {
+                        let (next_state, entry) = match ordered {
+                            true => self.read(next_state, entry).await?,
+                            false => (next_state, entry),
+                        };
+                        state.stats.add(next_state.stats);
+                        if self.config.prune_empty_dirs
+                            && entry.child.is_empty()
+                            && entry.error.is_none()
+                        {
+                            state.stats.pruned_dirs += 1;
+                            continue;
+                        }
+                        entry
+                    }
+                    None => {
+                        state.stats.files += 1;
+                        entry
+                    }
+                };
+                let key: String = state.key(&dir.child, &entry.src);
+                dir.child.insert(key, entry);
+            }
+            Ok((state, dir))
+        })
+    }
+}
+
+/// Minimum number of directories and audio files visited between two calls to the callback of
+/// `populate_with_progress()`.
+pub const PROGRESS_INTERVAL: usize = 100;
+
+/// Progress of a single call to `populate_with_progress()`.
+///
+/// # Fields
+/// `callback`: `Option<&mut dyn FnMut(ScanProgress)>` -> The callback given to
+///                                                      `populate_with_progress()`, if any.
+/// `reported`: `Option<usize>` -> Sum of `ScanStats.dirs` and `ScanStats.files` at the previous
+///                                call to `callback`.
+struct Progress<'a> {
+    callback: Option<&'a mut dyn FnMut(ScanProgress)>,
+    reported: Option<usize>,
+}
+
+impl<'a> Progress<'a> {
+    /// Helper function that returns the progress of a scan that has not started yet.
+    fn new(callback: Option<&'a mut dyn FnMut(ScanProgress)>) -> Progress<'a> {
+        Progress {
+            callback,
+            reported: None,
+        }
+    }
+
+    /// Helper function that reports the progress when `dir`, counted in `stats` already, is about
+    /// to be read and `PROGRESS_INTERVAL` entries were visited since the previous report.
+    fn visit(&mut self, dir: &Path, stats: &ScanStats) {
+        let due: bool = self
+            .reported
+            .is_none_or(|reported| stats.dirs + stats.files >= reported + PROGRESS_INTERVAL);
+        if due {
+            self.report(dir, stats);
+        }
+    }
+
+    /// Helper function that calls the callback with the counts of `stats` and `path`.
+    fn report(&mut self, path: &Path, stats: &ScanStats) {
+        if let Some(callback) = &mut self.callback {
+            callback(ScanProgress {
+                dirs: stats.dirs,
+                files: stats.files,
+                path,
+            });
+        }
+        self.reported = Some(stats.dirs + stats.files);
+    }
+}
+
+/// Entry read from a directory by a parallel scan, along with the state to read it with when it is
+/// a directory.
+#[cfg(any(feature = "rayon", feature = "tokio"))]
+type Child<'a> = (Entry, Option<Scan<'a>>);
+
+/// Helper function that returns the entry of the audio file at `path`, of MIME type `mime`, with
+/// the size and modification time of `metadata` and the cover art `art`. The `parent` and `id`
+/// fields are left for `assign_keys()` to set.
+#[cfg(any(feature = "rayon", feature = "tokio"))]
+fn audio_entry(path: PathBuf, mime: &str, metadata: &Metadata, art: Option<PathBuf>) -> Entry {
+    let mut entry: Entry = Entry::new(path, EntryKind::Audio, art, PathBuf::new());
+    entry.mime = Some(String::from(mime));
+    entry.size = metadata.len();
+    entry.modified = metadata.modified().ok().map(whole_seconds);
+    entry
+}
+
+/// An entry read from a directory that belongs in the Node tree, as told by `Scan::classify()`.
+///
+/// # Variants
+/// `Dir`: `(PathBuf, DirId)` -> A directory to descend into, with its `DirId`.
+/// `Audio`: `(PathBuf, &'static str, Metadata)` -> An audio file, with its MIME type and
+///                                                metadata.
+enum Listed {
+    Dir(PathBuf, DirId),
+    Audio(PathBuf, &'static str, Metadata),
+}
+
+impl Listed {
+    /// Helper function that returns the path of the entry.
+    fn path(&self) -> &Path {
+        match self {
+            Listed::Dir(path, _) | Listed::Audio(path, ..) => path,
+        }
+    }
+}
+
+/// A directory being filled in by the walk of `Scan::populate()`.
+///
+/// # Fields
+/// `key`: `Option<String>` -> Key of `entry` in the entry of the parent directory, or `None` for
+///                            the directory the walk started from.
+/// `entry`: `Entry` -> The entry of the directory, whose children are inserted as they are
+///                     visited.
+/// `entered`: `bool` -> Whether an override was pushed for the directory, to be popped once it
+///                      is done.
+/// `read`: `vec::IntoIter<DirEntry>` -> Entries read from the directory and left to visit.
+/// `kept`: `btree_map::IntoIter<String, Entry>` -> Entries of an earlier scan left to visit, when
+///                                                 the directory did not change since.
+/// `previous`: `Option<Node>` -> Entries of an earlier scan not found again so far, when the
+///                               directory is read again by `refresh()`.
+struct Frame {
+    key: Option<String>,
+    entry: Entry,
+    entered: bool,
+    read: vec::IntoIter<DirEntry>,
+    kept: btree_map::IntoIter<String, Entry>,
+    previous: Option<Node>,
+}
+
+/// State shared by every directory visited during a single call to `populate()`.
+///
+/// # Fields
+/// `root`: `&Path` -> The directory `populate()` was called on.
+/// `config`: `&Config` -> The configuration driving the scan.
+/// `stats`: `ScanStats` -> Statistics accumulated so far.
+/// `ancestors`: `Vec<DirId>` -> Identities of the directories currently being descended into.
+/// `depth`: `usize` -> Depth of the directory currently being read, where `root` is 0.
+/// `overrides`: `Vec<(PathBuf, DirOverrides)>` -> Override files in effect for the directory
+///                                                currently being read, outermost first, with
+///                                                the directory each one was read from.
+/// `keys`: `PathBuf` -> Keys leading from the root of the Node tree to the entry of the directory
+///                      currently being read, used as `Entry.parent`.
+/// `cancel`: `Option<&AtomicBool>` -> Flag stopping the scan as soon as it is set, if any.
+/// `entries`: `Arc<AtomicUsize>` -> Entries inserted so far, counted against
+///                                  `Config.max_entries` and shared by every task of a parallel
+///                                  scan.
+/// `report`: `RefreshReport` -> Directories and audio files counted so far by `refresh()`, whose
+///                              `stats` are only moved in from `stats` once the scan is done.
+/// `unread`: `Option<Vec<String>>` -> Keys of the subdirectories inserted without being read, when
+///                                    a single directory is read for `populate_resumable()`.
+///                                    `None` when subdirectories are descended into.
+/// `art_listings`: `ArtListings` -> Directories listed so far to look cover art up.
+struct Scan<'a> {
+    root: &'a Path,
+    config: &'a Config,
+    stats: ScanStats,
+    ancestors: Vec<DirId>,
+    depth: usize,
+    overrides: Vec<(PathBuf, DirOverrides)>,
+    keys: PathBuf,
+    cancel: Option<&'a AtomicBool>,
+    entries: Arc<AtomicUsize>,
+    report: RefreshReport,
+    unread: Option<Vec<String>>,
+    art_listings: ArtListings,
+}
+
+impl<'a> Scan<'a> {
+    /// Helper function that returns the state of a scan of `root`, before any directory is read.
+    fn new(root: &'a Path, config: &'a Config) -> Scan<'a> {
+        Scan {
+            root,
+            config,
+            stats: ScanStats::default(),
+            ancestors: Vec::new(),
+            depth: 0,
+            overrides: Vec::new(),
+            keys: PathBuf::new(),
+            cancel: None,
+            entries: Arc::new(AtomicUsize::new(0)),
+            report: RefreshReport::default(),
+            unread: None,
+            art_listings: ArtListings::default(),
+        }
+    }
+
+    /// Helper function that populates `node` from `dir`. When `dir` is read again by `refresh()`,
+    /// `previous` holds its entries from the earlier scan: the directories found there are
+    /// refreshed rather than read from scratch, and the audio files that are gone are counted in
+    /// `report`.
+    ///
+    /// Directories are walked depth first with an explicit stack of `Frame`s instead of
+    /// recursion, so that however deeply directories are nested the walk never overflows the
+    /// stack. Entries are visited, and keys resolved, in the same order either way.
+    fn populate(
+        &mut self,
+        dir: &Path,
+        node: &mut Node,
+        previous: Option<Node>,
+        progress: &mut Progress,
+    ) -> Result<()> {
+        let mut root: Entry = Entry::new(
+            dir.to_path_buf(),
+            EntryKind::Directory,
+            None,
+            PathBuf::new(),
+        );
+        root.child = std::mem::take(node);
+        // The directory the scan starts from must be readable, as `check_base()` found it to be
+        let listed: Vec<Result<DirEntry>> = self.list(dir, progress)?;
+        let read: vec::IntoIter<DirEntry> = self.sorted_entries(dir, listed)?;
+        let mut stack: Vec<Frame> = vec![Frame {
+            key: None,
+            entry: root,
+            entered: false,
+            read,
+            kept: Node::new().into_iter(),
+            previous,
+        }];
+
+        while let Some(frame) = stack.last_mut() {
+            // Past the limit, the directories being read are closed without reading further
+            if self.stats.limit_reached {
+                frame.read = Vec::new().into_iter();
+                frame.kept = Node::new().into_iter();
+            }
+            if let Some(entry) = frame.read.next() {
+                if let Some(next_frame) = self.visit(frame, entry, progress)? {
+                    stack.push(next_frame);
+                }
+                continue;
+            }
+            if let Some((key, entry)) = frame.kept.next() {
+                if let Some(next_frame) = self.keep(frame, key, entry, progress)? {
+                    stack.push(next_frame);
+                }
+                continue;
+            }
+
+            // Every entry of the directory was visited, so it can be inserted into its parent
+            let Some(frame) = stack.pop() else {
+                break;
+            };
+            if let Some(previous) = &frame.previous {
+                self.report.removed_files += count_songs(previous);
+            }
+            let (Some(key), Some(parent)) = (frame.key, stack.last_mut()) else {
+                *node = frame.entry.child;
+                break;
+            };
+
+            let mut entry: Entry = frame.entry;
+            entry.size = entry.child.values().map(|child| child.size).sum();
+            entry.modified = latest_modified(&entry.child)
+                .or_else(|| {
+                    metadata(to_long_path(&entry.src))
+                        .and_then(|m| m.modified())
+                        .ok()
+                })
+                .map(whole_seconds);
+            self.keys.pop();
+            self.depth -= 1;
+            self.ancestors.pop();
+            if frame.entered {
+                self.overrides.pop();
+            }
+
+            // Children were pruned already, so an empty directory has no audio below it, unless
+            // it could not be read
+            if self.config.prune_empty_dirs && entry.child.is_empty() && entry.error.is_none() {
+                self.stats.pruned_dirs += 1;
+                continue;
+            }
+            parent.entry.child.insert(key, entry);
+        }
+        Ok(()) // return () if updates ran successfully
+    }
+
+    /// Helper function that returns the entries of the directory `dir` in file name order,
+    /// counting it as visited, or no entries when its `src` is not a directory. When the
+    /// directory cannot be listed, the error is recorded in `dir.error` as well.
+    fn read(
+        &mut self,
+        dir: &mut Entry,
+        progress: &mut Progress,
+    ) -> Result<vec::IntoIter<DirEntry>> {
+        match self.list(&dir.src, progress) {
+            Ok(read) => self.sorted_entries(&dir.src, read),
+            Err(error) => {
+                dir.error = Some(ScanIssue::from(error.kind()));
+                self.fail(&dir.src, error)?;
+                Ok(Vec::new().into_iter())
+            }
+        }
+    }
+
+    /// Helper function that lists the directory `dir`, counting it as visited, or returns no
+    /// entries when it is not a directory.
+    fn list(&mut self, dir: &Path, progress: &mut Progress) -> Result<Vec<Result<DirEntry>>> {
+        if !to_long_path(dir).is_dir() {
+            return Ok(Vec::new());
+        }
+
+        self.stats.dirs += 1;
+        progress.visit(dir, &self.stats);
+        read_entries(dir, self.config)
+    }
+
+    /// Helper function that returns the entries `read` from the directory `dir` in file name
+    /// order, recording the ones that could not be read.
+    fn sorted_entries(
+        &mut self,
+        dir: &Path,
+        read: Vec<Result<DirEntry>>,
+    ) -> Result<vec::IntoIter<DirEntry>> {
+        let mut entries: Vec<DirEntry> = Vec::new();
+        for entry in read {
+            match entry {
+                Ok(entry) => entries.push(entry),
+                Err(error) => self.fail(dir, error)?,
+            }
+        }
+
+        // Sort for collisions between keys to be resolved the same way on every scan
+        entries.sort_by_key(DirEntry::file_name);
+        Ok(entries.into_iter())
+    }
+
+    /// Helper function that visits `entry`, read from the directory of `frame`, inserting it
+    /// into the entry of `frame` if it is an audio file. Returns the frame of `entry` when it is
+    /// a directory to descend into.
+    fn visit(
+        &mut self,
+        frame: &mut Frame,
+        entry: DirEntry,
+        progress: &mut Progress,
+    ) -> Result<Option<Frame>> {
+        self.check_cancelled()?;
+        let path: PathBuf = strip_long_path(&entry.path());
+        let Some(listed) = self.classify(&entry.file_name(), path, entry.file_type())? else {
+            return Ok(None);
+        };
+        let node: &mut Node = &mut frame.entry.child;
+        if self.config.fold_case && folded_sibling(node, listed.path()).is_some() {
+            self.stats.case_collisions.push(listed.path().to_path_buf());
+            return Ok(None);
+        }
+        if !self.admit() {
+            return Ok(None);
+        }
+
+        let (path, mime, metadata): (PathBuf, &str, Metadata) = match listed {
+            Listed::Dir(path, id) => {
+                let name: String = self.key(node, &path);
+                let parent: PathBuf = self.keys.clone();
+                let next_entry: Entry =
+                    Entry::new(path.clone(), EntryKind::Directory, None, parent);
+                if self.unread.is_some() {
+                    self.insert_unread(node, name, next_entry);
+                    return Ok(None);
+                }
+                let previous: Option<Entry> = frame
+                    .previous
+                    .as_mut()
+                    .and_then(|previous| take_child(previous, &path, EntryKind::Directory));
+                return self
+                    .descend(name, id, next_entry, previous, progress)
+                    .map(Some);
+            }
+            Listed::Audio(path, mime, metadata) => (path, mime, metadata),
+        };
+
+        let name: String = self.key(node, &path);
+        let parent: PathBuf = self.keys.clone();
+        let mut next_entry: Entry =
+            Entry::new(path.clone(), EntryKind::Audio, self.art(&path), parent);
+        next_entry.id = entry_id(&self.keys.join(&name));
+        next_entry.mime = Some(String::from(mime));
+        next_entry.size = metadata.len();
+        next_entry.modified = metadata.modified().ok().map(whole_seconds);
+
+        let known: bool = frame
+            .previous
+            .as_mut()
+            .is_some_and(|previous| take_child(previous, &path, EntryKind::Audio).is_some());
+        if !known {
+            self.report.added_files += 1;
+        }
+
+        frame.entry.child.insert(name, next_entry);
+        self.stats.files += 1;
+        Ok(None)
+    }
+
+    /// Helper function that looks at the entry named `name` at `path`, of type `file_type`, read
+    /// from the directory being read, counting it in the statistics when it is left out. Returns
+    /// what it is when it belongs in the Node tree, before `Config.fold_case` and
+    /// `Config.max_entries` are applied.
+    fn classify(
+        &mut self,
+        name: &OsStr,
+        path: PathBuf,
+        file_type: Result<FileType>,
+    ) -> Result<Option<Listed>> {
+        if name == OVERRIDES_FILE_NAME || name == IGNORE_FILE_NAME {
+            return Ok(None);
+        }
+
+        if !self.config.include_hidden && is_hidden(&path) {
+            self.stats.skipped_hidden += 1;
+            return Ok(None);
+        }
+
+        let file_type: FileType = match file_type {
+            Ok(file_type) => file_type,
+            Err(error) => {
+                self.fail(&path, error)?;
+                return Ok(None);
+            }
+        };
+        if file_type.is_symlink() && !self.config.follow_symlinks {
+            self.stats.skipped_symlinks += 1;
+            return Ok(None);
+        }
+        if file_type.is_symlink() && is_dangling(&path) {
+            self.stats.broken_symlinks.push(path);
+            return Ok(None);
+        }
+
+        if to_long_path(&path).is_dir() {
+            if self.is_junk(&path, true) {
+                self.stats.skipped_junk += 1;
+                return Ok(None);
+            }
+            if self.is_excluded(&path, true) {
+                self.stats.skipped_excluded += 1;
+                return Ok(None);
+            }
+
+            if self
+                .config
+                .max_depth
+                .is_some_and(|max_depth| self.depth >= max_depth)
+            {
+                self.stats.skipped_depth += 1;
+                return Ok(None);
+            }
+
+            // Never descend into a directory that is already being descended into
+            let id: DirId = match dir_id(&path) {
+                Ok(id) => id,
+                Err(error) => {
+                    self.fail(&path, error)?;
+                    return Ok(None);
+                }
+            };
+            if self.ancestors.contains(&id) {
+                self.stats.skipped_cycles += 1;
+                return Ok(None);
+            }
+            return Ok(Some(Listed::Dir(path, id)));
+        }
+
+        if self.is_junk(&path, false) {
+            self.stats.skipped_junk += 1;
+            return Ok(None);
+        }
+        if self.is_excluded(&path, false) {
+            self.stats.skipped_excluded += 1;
+            return Ok(None);
+        }
+
+        // Disallow any formats not in the configured allowlist from being inserted into Node tree
+        let Some(mime) = self.audio_mime(&path) else {
+            self.stats.skipped_extension += 1;
+            return Ok(None);
+        };
+
+        // Leave out files that cannot be played rather than inserting them broken
+        let metadata: Metadata = match metadata(to_long_path(&path)) {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                self.fail(&path, error)?;
+                return Ok(None);
+            }
+        };
+        if self.config.skip_empty_files && metadata.len() == 0 {
+            self.stats.empty_files.push(path);
+            return Ok(None);
+        }
+        Ok(Some(Listed::Audio(path, mime, metadata)))
+    }
+
+    /// Helper function that visits `entry`, kept under `key` from the earlier scan of the
+    /// directory of `frame` because the directory did not change since, inserting it into the
+    /// entry of `frame` if it is an audio file. Returns the frame of `entry` when it is a
+    /// directory, to be refreshed in turn.
+    fn keep(
+        &mut self,
+        frame: &mut Frame,
+        key: String,
+        entry: Entry,
+        progress: &mut Progress,
+    ) -> Result<Option<Frame>> {
+        self.check_cancelled()?;
+        if !self.admit() {
+            return Ok(None);
+        }
+        if !entry.is_dir() {
+            frame.entry.child.insert(key, entry);
+            return Ok(None);
+        }
+
+        let id: DirId = match dir_id(&entry.src) {
+            Ok(id) => id,
+            Err(error) => {
+                self.report.removed_files += count_songs(&entry.child);
+                self.fail(&entry.src, error)?;
+                return Ok(None);
+            }
+        };
+        let parent: PathBuf = self.keys.clone();
+        let next_entry: Entry = Entry::new(entry.src.clone(), EntryKind::Directory, None, parent);
+        self.descend(key, id, next_entry, Some(entry), progress)
+            .map(Some)
+    }
+
+    /// Helper function that starts filling in `entry`, the directory stored under `key` whose
+    /// `DirId` is `id`, returning its frame. The directory is read unless `previous`, its entry
+    /// from an earlier scan, shows it did not change since `previous.scanned_at`, in which case
+    /// the entries of `previous` are kept and only its subdirectories are looked at.
+    fn descend(
+        &mut self,
+        key: String,
+        id: DirId,
+        mut entry: Entry,
+        previous: Option<Entry>,
+        progress: &mut Progress,
+    ) -> Result<Frame> {
+        let entered: bool = self.enter(&entry.src);
+        entry.art = self.art(&entry.src);
+
+        self.ancestors.push(id);
+        self.depth += 1;
+        self.keys.push(&key);
+        entry.id = entry_id(&self.keys);
+
+        let mut frame: Frame = Frame {
+            key: Some(key),
+            entry,
+            entered,
+            read: Vec::new().into_iter(),
+            kept: Node::new().into_iter(),
+            previous: None,
+        };
+        match previous {
+            // A directory that could not be read may be readable now without having changed
+            Some(previous)
+                if previous.error.is_none()
+                    && !is_changed(&frame.entry.src, previous.scanned_at) =>
+            {
+                self.report.skipped_dirs += 1;
+                frame.entry.scanned_at = previous.scanned_at;
+                frame.kept = previous.child.into_iter();
+            }
+            previous => {
+                if previous.is_some() {
+                    self.report.rescanned_dirs += 1;
+                }
+                frame.entry.scanned_at = Some(whole_seconds(SystemTime::now()));
+                frame.read = self.read(&mut frame.entry, progress)?;
+                frame.previous = previous.map(|previous| previous.child);
+            }
+        }
+        Ok(frame)
+    }
+
+    /// Helper function that inserts `entry`, the entry of a subdirectory, into `node` under `key`
+    /// without reading it, with the `id` and art `descend()` would give it, and records `key` in
+    /// `unread`.
+    fn insert_unread(&mut self, node: &mut Node, key: String, mut entry: Entry) {
+        let entered: bool = self.enter(&entry.src);
+        entry.art = self.art(&entry.src);
+        if entered {
+            self.overrides.pop();
+        }
+        entry.id = entry_id(&self.keys.join(&key));
+        node.insert(key.clone(), entry);
+        self.unread.get_or_insert_with(Vec::new).push(key);
+    }
+
+    /// Helper function that looks at an entry like `Scan::classify()` does, returning the
+    /// statistics it was counted in apart, to be added once the entry is reached in file name
+    /// order.
+    #[cfg(any(feature = "rayon", feature = "tokio"))]
+    fn classify_apart(
+        &mut self,
+        name: &OsStr,
+        path: PathBuf,
+        file_type: Result<FileType>,
+    ) -> Result<(ScanStats, Option<Listed>)> {
+        let stats: ScanStats = std::mem::take(&mut self.stats);
+        let listed: Result<Option<Listed>> = self.classify(name, path, file_type);
+        let apart: ScanStats = std::mem::replace(&mut self.stats, stats);
+        Ok((apart, listed?))
+    }
+
+    /// Helper function that returns the state of a scan of the subdirectory whose `DirId` is `id`,
+    /// read on its own while this scan goes on, sharing the cancellation flag and entry count.
+    #[cfg(any(feature = "rayon", feature = "tokio"))]
+    fn child_state(&self, id: DirId) -> Scan<'a> {
+        Scan {
+            ancestors: self.ancestors.iter().cloned().chain([id]).collect(),
+            depth: self.depth + 1,
+            overrides: self.overrides.clone(),
+            cancel: self.cancel,
+            entries: Arc::clone(&self.entries),
+            ..Scan::new(self.root, self.config)
+        }
+    }
+
+    /// Helper function that lists the directory `dir` like `Scan::list()` does, once fewer than
+    /// `Config.max_open_dirs` directories are being listed.
+    #[cfg(feature = "rayon")]
+    fn list_parallel(&mut self, dir: &Path, reads: &ReadLimit) -> Result<Vec<Result<DirEntry>>> {
+        if !to_long_path(dir).is_dir() {
+            return Ok(Vec::new());
+        }
+
+        self.stats.dirs += 1;
+        let _permit: ReadPermit = reads.acquire();
+        read_entries(dir, self.config)
+    }
+
+    /// Helper function that reads the subdirectory whose entry is `entry` with its own state
+    /// `self`, as `Scan::descend()` does, flagging `entry` when it cannot be listed.
+    #[cfg(feature = "rayon")]
+    fn read_parallel(&mut self, entry: &mut Entry, reads: &ReadLimit) -> Result<()> {
+        self.enter(&entry.src);
+        entry.art = self.art(&entry.src);
+        entry.scanned_at = Some(whole_seconds(SystemTime::now()));
+        match self.list_parallel(&entry.src, reads) {
+            Ok(read) => self.populate_parallel(entry, read, reads)?,
+            Err(error) => {
+                entry.error = Some(ScanIssue::from(error.kind()));
+                self.fail(&entry.src, error)?;
+            }
+        }
+
+        entry.size = entry.child.values().map(|child| child.size).sum();
+        entry.modified = latest_modified(&entry.child)
+            .or_else(|| {
+                metadata(to_long_path(&entry.src))
+                    .and_then(|m| m.modified())
+                    .ok()
+            })
+            .map(whole_seconds);
+        Ok(())
+    }
+
+    /// Helper function that fills in `dir`, the entry of a directory whose entries are `read`,
+    /// like `Scan::populate()` does, reading every subdirectory with its own state in a rayon
+    /// task of its own. When `Config.max_entries` is set, subdirectories are read one after the
+    /// other in file name order instead, so that the entries within the limit are the ones
+    /// `Scan::populate()` keeps. The `parent` and `id` fields are left for `assign_keys()` to
+    /// set.
+    #[cfg(feature = "rayon")]
+    fn populate_parallel(
+        &mut self,
+        dir: &mut Entry,
+        read: Vec<Result<DirEntry>>,
+        reads: &ReadLimit,
+    ) -> Result<()> {
+        // Audio files are complete right away, directories once they are read
+        let mut children: Vec<(ScanStats, Option<Child<'a>>)> = Vec::new();
+        for entry in self.sorted_entries(&dir.src, read)? {
+            self.check_cancelled()?;
+            let path: PathBuf = strip_long_path(&entry.path());
+            let (stats, listed) =
+                self.classify_apart(&entry.file_name(), path, entry.file_type())?;
+            let child: Option<Child> = listed.map(|listed| match listed {
+                Listed::Dir(path, id) => (
+                    Entry::new(path, EntryKind::Directory, None, PathBuf::new()),
+                    Some(self.child_state(id)),
+                ),
+                Listed::Audio(path, mime, metadata) => {
+                    let art: Option<PathBuf> = self.art(&path);
+                    (audio_entry(path, mime, &metadata, art), None)
+                }
+            });
+            children.push((stats, child));
+        }
+
+        let ordered: bool = self.config.max_entries.is_some();
+        if !ordered {
+            let mut results: Vec<Result<()>> = children.iter().map(|_| Ok(())).collect();
+            rayon::scope(|scope| {
+                for ((_, child), result) in children.iter_mut().zip(&mut results) {
+                    if let Some((entry, Some(scan))) = child {
+                        scope.spawn(move |_| *result = scan.read_parallel(entry, reads));
+                    }
+                }
+            });
+            results.into_iter().collect::<Result<()>>()?;
+        }
+
+        // Insert in file name order, for keys to be resolved, and statistics collected, the same
+        // way as in `populate()`
+        for (stats, child) in children {
+            self.stats.add(stats);
+            let Some((mut entry, scan)) = child else {
+                continue;
+            };
+            if self.config.fold_case && folded_sibling(&dir.child, &entry.src).is_some() {
+                self.stats.case_collisions.push(entry.src);
+                continue;
+            }
+            if !self.admit() {
+                break;
+            }
+            match scan {
+                Some(mut scan) => {
+                    if ordered {
+                        scan.read_parallel(&mut entry, reads)?;
+                    }
+                    self.stats.add(scan.stats);
+                    if self.config.prune_empty_dirs
+                        && entry.child.is_empty()
+                        && entry.error.is_none()
+                    {
+                        self.stats.pruned_dirs += 1;
+                        continue;
+                    }
+                }
+                None => self.stats.files += 1,
+            }
+            let key: String = self.key(&dir.child, &entry.src);
+            dir.child.insert(key, entry);
+        }
+        Ok(())
+    }
+
+    /// Helper function that counts one more entry about to be inserted, returning whether it
+    /// fits within `Config.max_entries`. `ScanStats.limit_reached` is set once an entry does not.
+    fn admit(&mut self) -> bool {
+        let Some(max_entries) = self.config.max_entries else {
+            return true;
+        };
+        let fits: bool = self.entries.fetch_add(1, Ordering::Relaxed) < max_entries;
+        if !fits {
+            self.stats.limit_reached = true;
+        }
+        fits
+    }
+
+    /// Helper function that returns an `ErrorKind::Interrupted` error once `cancel` is set.
+    fn check_cancelled(&self) -> Result<()> {
+        match self
+            .cancel
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+        {
+            true => Err(Error::new(ErrorKind::Interrupted, "scan cancelled")),
+            false => Ok(()),
+        }
+    }
+
+    /// Helper function that records `error`, met while reading `path`, in the statistics, or
+    /// returns it when `Config.abort_on_error` is set.
+    fn fail(&mut self, path: &Path, error: Error) -> Result<()> {
+        if self.config.abort_on_error {
+            return Err(error);
+        }
+
+        self.stats.errors.push(ScanError {
+            path: path.to_path_buf(),
+            kind: error.kind(),
+        });
+        Ok(())
+    }
+
+    /// Helper function that returns the key `path` is inserted under in `node`, recording the
+    /// collision when it is not the file stem of `path`. See `unique_key()`.
+    fn key(&mut self, node: &Node, path: &Path) -> String {
+        let normalize: bool = self.config.normalize_keys;
+        let key: String = unique_key(node, path, normalize, self.config.fold_case);
+        if path
+            .file_stem()
+            .is_some_and(|stem| name_key(stem, normalize) != key)
+        {
+            self.stats.key_collisions.push(path.to_path_buf());
+        }
+        key
+    }
+
+    /// Helper function that reads the override and ignore files of `dir`, if any, and makes them
+    /// take effect. Returns whether an override was pushed, in which case the caller must pop it
+    /// once `dir` has been read.
+    fn enter(&mut self, dir: &Path) -> bool {
+        let mut overrides: Option<DirOverrides> = None;
+        let file: PathBuf = dir.join(OVERRIDES_FILE_NAME);
+        if to_long_path(&file).is_file() {
+            match DirOverrides::from_path(&file) {
+                Ok(read) => overrides = Some(read),
+                Err(error) => self.stats.invalid_overrides.push(error),
+            }
+        }
+
+        // Ignored patterns are excluded like the ones of the override file
+        let file: PathBuf = dir.join(IGNORE_FILE_NAME);
+        if to_long_path(&file).is_file() {
+            match read_to_string(to_long_path(&file)) {
+                Ok(contents) => overrides
+                    .get_or_insert_with(DirOverrides::default)
+                    .exclude
+                    .extend(parse_ignore_file(&contents)),
+                Err(error) => self.stats.invalid_overrides.push(ConfigError::Io {
+                    path: file,
+                    message: error.to_string(),
+                }),
+            }
+        }
+
+        match overrides {
+            Some(overrides) => {
+                self.overrides.push((dir.to_path_buf(), overrides));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Helper function that returns whether `path` is junk skipped because `Config.skip_junk` is
+    /// set. See `is_junk()`.
+    fn is_junk(&self, path: &Path, is_dir: bool) -> bool {
+        self.config.skip_junk && is_junk(path, is_dir, &self.config.junk)
+    }
+
+    /// Helper function that returns whether `path` matches one of the `Config.exclude` patterns,
+    /// or one of the exclude patterns of the overrides in effect.
+    fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        let relative: &Path = path.strip_prefix(self.root).unwrap_or(path);
+        let global: bool = self
+            .config
+            .exclude
+            .iter()
+            .any(|pattern| path_matches(pattern, relative, is_dir));
+
+        global
+            || self.overrides.iter().any(|(dir, overrides)| {
+                let relative: &Path = path.strip_prefix(dir).unwrap_or(path);
+                overrides
+                    .exclude
+                    .iter()
+                    .any(|pattern| path_matches(pattern, relative, is_dir))
+            })
+    }
+
+    /// Helper function that returns whether `path` is an audio file allowed in the directory being
+    /// read: one with an extension of `AUDIO_EXTENSIONS` or `Config.extensions`, or only of the
+    /// audio extensions of the innermost override in effect that sets them.
+    fn is_audio(&self, path: &Path) -> bool {
+        let overridden: Option<&[String]> = self
+            .overrides
+            .iter()
+            .rev()
+            .find_map(|(_, overrides)| overrides.extensions.as_deref());
+        let extension: &str = path.extension().and_then(OsStr::to_str).unwrap_or("");
+        let case_insensitive: bool = self.config.case_insensitive_extensions;
+
+        match overridden {
+            Some(allowed) => allowed.iter().any(|allowed| {
+                allowed == extension
+                    || (case_insensitive && allowed.eq_ignore_ascii_case(extension))
+            }),
+            None if case_insensitive => is_audio_path(path, &self.config.extensions),
+            None => AUDIO_EXTENSIONS
+                .iter()
+                .copied()
+                .chain(self.config.extensions.iter().map(String::as_str))
+                .any(|allowed| allowed == extension),
+        }
+    }
+
+    /// Helper function that returns the MIME type of `path` when it is an audio file allowed in the
+    /// directory being read, from its extension (see `Scan::is_audio()`) or, when `Scan::sniffs()`
+    /// it, from its contents.
+    fn audio_mime(&self, path: &Path) -> Option<&'static str> {
+        let extension: &str = path.extension().and_then(OsStr::to_str).unwrap_or("");
+        match self.is_audio(path) {
+            true => Some(mime_for_extension(extension)),
+            false if self.sniffs(path) => sniff_audio(path),
+            false => None,
+        }
+    }
+
+    /// Helper function that returns whether the contents of `path` are sniffed for audio because
+    /// `Config.sniff_content` is set and `path` has no extension, or one `mime_for_extension()`
+    /// does not know. Files with a known extension, such as images, are never read.
+    fn sniffs(&self, path: &Path) -> bool {
+        let extension: &str = path.extension().and_then(OsStr::to_str).unwrap_or("");
+        self.config.sniff_content && mime_for_extension(extension) == "application/octet-stream"
+    }
+
+    /// Helper function that returns the cover art for `path`, preferring the art forced by the
+    /// overrides in effect, ignoring excluded images and falling back to `Config.default_art`.
+    fn art(&self, path: &Path) -> Option<PathBuf> {
+        let forced: Option<&PathBuf> = self
+            .overrides
+            .iter()
+            .rev()
+            .find_map(|(_, overrides)| overrides.art.as_ref());
+
+        match forced {
+            Some(art) => Some(art.clone()),
+            None => find_art(
+                path,
+                self.config,
+                |art| !self.is_excluded(art, false),
+                &self.art_listings,
+            ),
+        }
+        .or_else(|| self.config.default_art.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::node::NodeExt;
+    use super::super::testing::{fail, fixture, recover};
+    use super::super::traverse::{find_by_path, get_nested, iter_depth_first};
+    use super::*;
+    use std::fs::{create_dir_all, write};
+
+    /// Helper function that returns `stats` without their duration, which differs between scans.
+    fn timeless(stats: ScanStats) -> ScanStats {
+        ScanStats {
+            duration: Duration::ZERO,
+            ..stats
+        }
+    }
+
+    /// Helper function that returns the tree and statistics of the scans of `dir` by
+    /// `populate_from_with_stats()` and the parallel and async scans, which are all the same.
+    fn scans(dir: &Path, config: &Config) -> Vec<Result<(Node, ScanStats)>> {
+        vec![
+            populate_from_with_stats(dir, config),
+            #[cfg(feature = "rayon")]
+            populate_parallel_with_stats(dir, config, &AtomicBool::new(false)),
+            #[cfg(feature = "tokio")]
+            tokio::runtime::Builder::new_multi_thread()
+                .enable_time()
+                .build()
+                .unwrap()
+                .block_on(populate_async_with_stats(
+                    dir,
+                    config,
+                    &AtomicBool::new(false),
+                )),
+        ]
+    }
+
+    #[test]
+    fn unreadable_entries_are_left_out() {
+        let base: PathBuf = fixture("unreadable-entries");
+        for album in ["Denied", "Live"] {
+            create_dir_all(base.join(album)).unwrap();
+            write(base.join(album).join("1.mp3"), "").unwrap();
+        }
+        fail(&base.join("Denied"), ErrorKind::PermissionDenied);
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+        #[cfg(unix)]
+        {
+            // A link to itself, whose metadata cannot be read whoever runs the tests
+            std::os::unix::fs::symlink("2.mp3", base.join("Live").join("2.mp3")).unwrap();
+            config.follow_symlinks = true;
+        }
+
+        let mut scanned = scans(&base, &config).into_iter();
+        let (tree, stats): (Node, ScanStats) = scanned.next().unwrap().unwrap();
+        assert_eq!(count_songs(&tree), 1);
+        assert_eq!(tree["Denied"].error, Some(ScanIssue::PermissionDenied));
+        let denied: ScanError = ScanError {
+            path: base.join("Denied"),
+            kind: ErrorKind::PermissionDenied,
+        };
+        assert_eq!(stats.errors[0], denied);
+        assert_eq!(stats.errors.len(), if cfg!(unix) { 2 } else { 1 });
+        for scan in scanned {
+            let (other_tree, other_stats): (Node, ScanStats) = scan.unwrap();
+            assert_eq!(other_tree, tree);
+            assert_eq!(timeless(other_stats), timeless(stats.clone()));
+        }
+
+        // Timed reads report the same errors
+        config.read_timeout_secs = Some(30);
+        for scan in scans(&base, &config) {
+            let (timed_tree, timed_stats): (Node, ScanStats) = scan.unwrap();
+            assert_eq!(timed_tree, tree);
+            assert_eq!(timed_stats.errors, stats.errors);
+        }
+
+        config.abort_on_error = true;
+        for scan in scans(&base, &config) {
+            assert_eq!(scan.unwrap_err().kind(), ErrorKind::PermissionDenied);
+        }
+    }
+
+    #[test]
+    fn limited_scans_report_the_limit() {
+        let base: PathBuf = fixture("limited-scans");
+        for artist in ["Artist", "Band"] {
+            for album in ["Debut", "Live"] {
+                create_dir_all(base.join(artist).join(album)).unwrap();
+                for song in ["1.mp3", "2.mp3"] {
+                    write(base.join(artist).join(album).join(song), "").unwrap();
+                }
+            }
+        }
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+
+        // 2 artists, 4 albums and 8 songs
+        for max_entries in [1, 3, 8, 13, 14, 15] {
+            config.max_entries = Some(max_entries);
+            let mut scanned = scans(&base, &config).into_iter();
+            let (tree, stats): (Node, ScanStats) = scanned.next().unwrap().unwrap();
+            assert_eq!(iter_depth_first(&tree).count(), max_entries.min(14));
+            assert_eq!(stats.limit_reached, max_entries < 14);
+            for scan in scanned {
+                let (other_tree, other_stats): (Node, ScanStats) = scan.unwrap();
+                assert_eq!(other_tree, tree);
+                assert_eq!(timeless(other_stats), timeless(stats.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_scans_match_populate() {
+        let base: PathBuf = fixture("parallel-scans");
+        for (artist, albums) in [
+            ("Artist", ["Debut", "Live", "Slow"]),
+            ("Band", ["Debut", "Denied", "Empty"]),
+        ] {
+            for album in albums {
+                create_dir_all(base.join(artist).join(album)).unwrap();
+            }
+        }
+        for file in [
+            "Artist/Artist.png",
+            "Artist/Debut/1.mp3",
+            "Artist/Debut/2.flac",
+            "Artist/Live.flac",
+            "Artist/Live/1.mp3",
+            "Artist/Slow/1.mp3",
+            "Band/Debut/1.mp3",
+            "Band/Debut/1.flac",
+            "Band/Debut/Debut.jpg",
+            "Band/Denied/1.mp3",
+            "Band/Empty.flac",
+            "intro.mp3",
+        ] {
+            write(base.join(file), "").unwrap();
+        }
+        fail(&base.join("Artist").join("Slow"), ErrorKind::TimedOut);
+        fail(
+            &base.join("Band").join("Denied"),
+            ErrorKind::PermissionDenied,
+        );
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+        config.read_timeout_secs = Some(1);
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("loop.mp3", base.join("Band").join("loop.mp3")).unwrap();
+            config.follow_symlinks = true;
+        }
+
+        for (prune, max_entries) in [
+            (false, None),
+            (true, None),
+            (false, Some(6)),
+            (true, Some(9)),
+        ] {
+            config.prune_empty_dirs = prune;
+            config.max_entries = max_entries;
+            let mut scanned = scans(&base, &config).into_iter();
+            let (tree, stats): (Node, ScanStats) = scanned.next().unwrap().unwrap();
+            assert_eq!(stats.limit_reached, max_entries.is_some());
+            if max_entries.is_none() {
+                let kinds: Vec<ErrorKind> = stats.errors.iter().map(|error| error.kind).collect();
+                assert!(kinds.contains(&ErrorKind::TimedOut));
+                assert!(kinds.contains(&ErrorKind::PermissionDenied));
+            }
+            for scan in scanned {
+                let (other_tree, other_stats): (Node, ScanStats) = scan.unwrap();
+                assert_eq!(other_tree, tree);
+                assert_eq!(timeless(other_stats), timeless(stats.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn flagged_dirs_are_read_again() {
+        let base: PathBuf = fixture("flagged-dirs");
+        create_dir_all(base.join("Locked")).unwrap();
+        write(base.join("Locked").join("1.mp3"), "").unwrap();
+        fail(&base.join("Locked"), ErrorKind::PermissionDenied);
+        let config: Config = toml::from_str("base = ''").unwrap();
+        let mut tree: Node = populate_from(&base, &config).unwrap();
+        assert_eq!(tree["Locked"].error, Some(ScanIssue::PermissionDenied));
+
+        // Readable again without having changed
+        recover(&base.join("Locked"));
+        refresh(&mut tree, &base, &config).unwrap();
+        assert_eq!(tree["Locked"].error, None);
+        assert_eq!(tree["Locked"].child.keys().collect::<Vec<_>>(), ["1"]);
+    }
+
+    #[test]
+    fn unlistable_bases_are_errors() {
+        let base: PathBuf = fixture("unlistable-base");
+        write(base.join("1.mp3"), "").unwrap();
+        fail(&base, ErrorKind::PermissionDenied);
+        let config: Config = toml::from_str("base = ''").unwrap();
+
+        for scan in scans(&base, &config) {
+            let error: Error = scan.unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+            let base_error = error.get_ref().unwrap().downcast_ref::<ConfigError>();
+            assert_eq!(
+                base_error,
+                Some(&ConfigError::PermissionDenied(base.clone()))
+            );
+        }
+    }
+
+    #[test]
+    fn timed_out_bases_are_errors() {
+        let base: PathBuf = fixture("timed-out-base");
+        write(base.join("1.mp3"), "").unwrap();
+        fail(&base, ErrorKind::TimedOut);
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+        config.read_timeout_secs = Some(1);
+
+        for scan in scans(&base, &config) {
+            assert_eq!(scan.unwrap_err().kind(), ErrorKind::TimedOut);
+        }
+    }
+
+    #[test]
+    fn timed_out_dirs_are_flagged_and_counted() {
+        let base: PathBuf = fixture("timed-out-dirs");
+        for album in ["Live", "Slow"] {
+            create_dir_all(base.join(album)).unwrap();
+            write(base.join(album).join("1.mp3"), "").unwrap();
+        }
+        fail(&base.join("Slow"), ErrorKind::TimedOut);
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+        config.read_timeout_secs = Some(1);
+        config.prune_empty_dirs = true;
+
+        let mut scanned = scans(&base, &config).into_iter();
+        let (tree, stats): (Node, ScanStats) = scanned.next().unwrap().unwrap();
+        assert_eq!(count_songs(&tree), 1);
+        assert_eq!(tree["Slow"].error, Some(ScanIssue::TimedOut));
+        let timed_out: ScanError = ScanError {
+            path: base.join("Slow"),
+            kind: ErrorKind::TimedOut,
+        };
+        assert_eq!(stats.errors, [timed_out]);
+        for scan in scanned {
+            let (other_tree, other_stats): (Node, ScanStats) = scan.unwrap();
+            assert_eq!(other_tree, tree);
+            assert_eq!(timeless(other_stats), timeless(stats.clone()));
+        }
+    }
+
+    #[test]
+    fn art_case_variants_are_picked_in_file_name_order() {
+        let album: PathBuf = fixture("art-case-variants").join("Album");
+        create_dir_all(&album).unwrap();
+        for file in [
+            "Album.jpg",
+            "Album.Png",
+            "Album.PNG",
+            "Song.Webp",
+            "Song.WEBP",
+            "Song.mp3",
+        ] {
+            write(album.join(file), "").unwrap();
+        }
+        let config: Config = toml::from_str("art_extensions = ['webp', 'png', 'jpg']").unwrap();
+
+        // Only on file systems that tell the names apart
+        if read_dir(&album).unwrap().count() == 6 {
+            assert_eq!(update_art(&album, &config), Some(album.join("Album.PNG")));
+            assert_eq!(
+                update_art(&album.join("Song.mp3"), &config),
+                Some(album.join("Song.WEBP"))
+            );
+        }
+
+        // An exact match wins over the other variants
+        write(album.join("Album.png"), "").unwrap();
+        assert_eq!(update_art(&album, &config), Some(album.join("Album.png")));
+    }
+
+    #[test]
+    fn art_lookups_list_each_directory_once() {
+        let album: PathBuf = fixture("art-listings").join("Album");
+        create_dir_all(&album).unwrap();
+        write(album.join("Album.JPG"), "").unwrap();
+        for song in 0..100 {
+            write(album.join(format!("{}.mp3", song)), "").unwrap();
+        }
+        let config: Config = toml::from_str("art_extensions = ['webp', 'png', 'jpg']").unwrap();
+
+        let listings: ArtListings = ArtListings::default();
+        for song in 0..100 {
+            let art = find_art(
+                &album.join(format!("{}.mp3", song)),
+                &config,
+                |_| true,
+                &listings,
+            );
+            assert_eq!(art, Some(album.join("Album.JPG")));
+        }
+        let listed = listings.0.lock().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].0, album);
+    }
+
+    #[test]
+    fn art_extensions_set_the_precedence() {
+        let album: PathBuf = fixture("art-extensions").join("Album");
+        create_dir_all(&album).unwrap();
+        write(album.join("Album.webp"), "").unwrap();
+        write(album.join("Album.jpg"), "").unwrap();
+
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+        assert_eq!(update_art(&album, &config), Some(album.join("Album.webp")));
+
+        config.art_extensions = vec![String::from("jpg"), String::from("webp")];
+        assert_eq!(update_art(&album, &config), Some(album.join("Album.jpg")));
+
+        config.art_extensions = vec![String::from("png")];
+        assert_eq!(update_art(&album, &config), None);
+    }
+
+    #[test]
+    fn art_extensions_match_regardless_of_case() {
+        let album: PathBuf = fixture("art-extensions-case").join("Album");
+        create_dir_all(&album).unwrap();
+        write(album.join("Album.PNG"), "").unwrap();
+
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+        assert_eq!(update_art(&album, &config), Some(album.join("Album.PNG")));
+
+        // Case-insensitive file systems find `Album.png` anyway
+        config.case_insensitive_extensions = false;
+        if !album.join("Album.png").exists() {
+            assert_eq!(update_art(&album, &config), None);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dangling_art_links_are_never_used() {
+        let album: PathBuf = fixture("art-dangling").join("Album");
+        create_dir_all(&album).unwrap();
+        std::os::unix::fs::symlink(album.join("missing.webp"), album.join("Album.WEBP")).unwrap();
+        write(album.join("Album.jpg"), "").unwrap();
+
+        let config: Config = toml::from_str("base = ''").unwrap();
+        assert_eq!(update_art(&album, &config), Some(album.join("Album.jpg")));
+    }
+
+    #[test]
+    fn junk_is_never_art() {
+        let album: PathBuf = fixture("art-junk").join("Album");
+        create_dir_all(&album).unwrap();
+        write(album.join("Album.jpg"), "").unwrap();
+        write(album.join("._Song.jpg"), "").unwrap();
+
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+        config.include_hidden = true;
+        let song: PathBuf = album.join("._Song.flac");
+        assert_eq!(update_art(&song, &config), Some(album.join("Album.jpg")));
+
+        config.skip_junk = false;
+        assert_eq!(update_art(&song, &config), Some(album.join("._Song.jpg")));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn names_that_are_not_utf8_keep_distinct_keys() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let base: PathBuf = fixture("invalid-utf8");
+        for name in [&b"caf\xe9.mp3"[..], b"caf\xe8.mp3", b"caf\xc3\xa9.mp3"] {
+            write(base.join(OsStr::from_bytes(name)), "").unwrap();
+        }
+
+        let config: Config = toml::from_str("base = ''").unwrap();
+        let tree: Node = populate_from(&base, &config).unwrap();
+        assert_eq!(
+            tree.keys().collect::<Vec<_>>(),
+            ["caf%E8", "caf%E9", "café"]
+        );
+        assert_eq!(
+            tree["caf%E9"].src,
+            base.join(OsStr::from_bytes(b"caf\xe9.mp3"))
+        );
+        let path: PathBuf = base.join(OsStr::from_bytes(b"caf\xe8.mp3"));
+        assert_eq!(
+            find_by_path(&tree, &base, &path, false),
+            Some(&tree["caf%E8"])
+        );
+    }
+
+    #[test]
+    fn keys_are_normalized() {
+        let base: PathBuf = fixture("normalize-keys");
+        let (mac, linux): (PathBuf, PathBuf) = (base.join("mac"), base.join("linux"));
+        let artist: PathBuf = mac.join("Bjo\u{308}rk");
+        create_dir_all(&artist).unwrap();
+        write(artist.join("Jo\u{301}ga.mp3"), "").unwrap();
+        create_dir_all(linux.join("Björk")).unwrap();
+        write(linux.join("Björk").join("Hyperballad.mp3"), "").unwrap();
+
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+        let mut tree: Node = populate_from(&mac, &config).unwrap();
+        assert_eq!(tree.keys().collect::<Vec<_>>(), ["Björk"]);
+        assert_eq!(tree["Björk"].src, artist);
+        assert_eq!(tree["Björk"].child["Jóga"].parent, Path::new("Björk"));
+
+        // Lookups in either form
+        let song: &Entry = &tree["Björk"].child["Jóga"];
+        let keys: [&str; 2] = ["Bjo\u{308}rk", "Jo\u{301}ga"];
+        assert_eq!(get_nested(&tree, &keys, false), Some(song));
+        let composed: PathBuf = mac.join("Björk").join("Jóga.mp3");
+        assert_eq!(find_by_path(&tree, &mac, &composed, false), Some(song));
+        let decomposed: PathBuf = artist.join("Jo\u{301}ga.mp3");
+        assert_eq!(find_by_path(&tree, &mac, &decomposed, false), Some(song));
+
+        // The same artist from both platforms is merged into a single entry
+        populate(&linux, &mut tree, &config).unwrap();
+        assert_eq!(tree.keys().collect::<Vec<_>>(), ["Björk"]);
+        assert_eq!(
+            tree["Björk"].child.keys().collect::<Vec<_>>(),
+            ["Hyperballad", "Jóga"]
+        );
+
+        config.normalize_keys = false;
+        let tree: Node = populate_from(&mac, &config).unwrap();
+        assert_eq!(tree.keys().collect::<Vec<_>>(), ["Bjo\u{308}rk"]);
+    }
+
+    #[test]
+    fn excluded_entries_are_left_out() {
+        let base: PathBuf = fixture("exclude");
+        create_dir_all(base.join("Recordings")).unwrap();
+        create_dir_all(base.join("Album").join("__MACOSX")).unwrap();
+        for file in [
+            "Recordings/memo.mp3",
+            "Album/__MACOSX/song.mp3",
+            "Album/song.mp3",
+            "Album/Album.png",
+            "Album/Album.jpg",
+        ] {
+            write(base.join(file), "").unwrap();
+        }
+
+        let config: Config =
+            toml::from_str("base = ''\nexclude = ['Recordings/', '__MACOSX/', '*.png']").unwrap();
+        let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+        assert_eq!(tree.keys().collect::<Vec<_>>(), ["Album"]);
+        assert_eq!(tree["Album"].child.keys().collect::<Vec<_>>(), ["song"]);
+        assert_eq!(
+            tree["Album"].art,
+            Some(base.join("Album").join("Album.jpg"))
+        );
+        assert_eq!(stats.skipped_excluded, 3);
+    }
+
+    #[test]
+    fn max_depth_limits_directories() {
+        let base: PathBuf = fixture("max-depth");
+        let album: PathBuf = base.join("Artist").join("Album");
+        create_dir_all(album.join("Samples").join("Kicks")).unwrap();
+        write(base.join("intro.mp3"), "").unwrap();
+        write(album.join("song.mp3"), "").unwrap();
+        write(album.join("Samples").join("Kicks").join("kick.wav"), "").unwrap();
+
+        let mut config: Config = toml::from_str("base = ''\nmax_depth = 0").unwrap();
+        let tree: Node = populate_from(&base, &config).unwrap();
+        assert_eq!(tree.keys().collect::<Vec<_>>(), ["intro"]);
+
+        config.max_depth = Some(2);
+        let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+        let album: &Entry = &tree["Artist"].child["Album"];
+        assert_eq!(album.child.keys().collect::<Vec<_>>(), ["song"]);
+        assert_eq!(stats.skipped_depth, 1);
+
+        config.max_depth = None;
+        let tree: Node = populate_from(&base, &config).unwrap();
+        let kicks: &Entry = &tree["Artist"].child["Album"].child["Samples"].child["Kicks"];
+        assert_eq!(kicks.child.keys().collect::<Vec<_>>(), ["kick"]);
+    }
+
+    // Paths this long are only supported by Linux
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn deep_trees_do_not_overflow_the_stack() {
+        let base: PathBuf = fixture("deep");
+        let deepest: PathBuf = (0..1800).fold(base.clone(), |dir, _| dir.join("d"));
+        create_dir_all(&deepest).unwrap();
+        write(deepest.join("song.mp3"), "").unwrap();
+
+        // Scan with a stack far too small for a recursive walk
+        let config: Config = toml::from_str("base = ''").unwrap();
+        let scan = thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(move || populate_from(&base, &config));
+        let tree: Node = scan.unwrap().join().unwrap().unwrap();
+
+        let mut level: &Node = &tree;
+        let mut depth: usize = 0;
+        while let Some(entry) = level.get("d") {
+            level = &entry.child;
+            depth += 1;
+        }
+        assert_eq!(depth, 1800);
+        assert_eq!(level["song"].parent.components().count(), 1800);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn long_paths_are_scanned_whole() {
+        use super::super::platform::MAX_PATH;
+
+        let name: String = "a".repeat(100);
+        let base: PathBuf = fixture("long-paths");
+        let album: PathBuf = base.join("Box Set").join(&name).join(&name).join("Disc 1");
+        create_dir_all(to_long_path(&album)).unwrap();
+        write(to_long_path(&album.join("1.mp3")), "").unwrap();
+        write(to_long_path(&album.join("Disc 1.jpg")), "").unwrap();
+
+        let config: Config = toml::from_str("base = ''").unwrap();
+        let tree: Node = populate_from(&base, &config).unwrap();
+        let disc: &Entry = &tree["Box Set"].child[&name].child[&name].child["Disc 1"];
+        assert!(disc.src.as_os_str().len() >= MAX_PATH - 12);
+        assert_eq!(disc.src, album);
+        assert!(!disc.src.to_str().unwrap().starts_with(r"\\?\"));
+        assert_eq!(disc.child["1"].art, Some(disc.src.join("Disc 1.jpg")));
+    }
+
+    #[test]
+    fn parallel_scans_prune_like_populate() {
+        let base: PathBuf = fixture("parallel-prune");
+        create_dir_all(base.join("Band").join("Live")).unwrap();
+        create_dir_all(base.join("Empty").join("Debut")).unwrap();
+        write(base.join("Band").join("Live.flac"), "").unwrap();
+        write(base.join("intro.mp3"), "").unwrap();
+
+        // Pruned directories free their key for the entries after them
+        let config: Config = toml::from_str("base = ''\nprune_empty_dirs = true").unwrap();
+        for scan in scans(&base, &config) {
+            let (tree, _): (Node, ScanStats) = scan.unwrap();
+            assert_eq!(tree.keys().collect::<Vec<_>>(), ["Band", "intro"]);
+            assert_eq!(tree["Band"].child["Live"].kind, EntryKind::Audio);
+        }
+    }
+
+    #[test]
+    fn wide_trees_are_scanned_one_open_directory_at_a_time() {
+        let base: PathBuf = fixture("parallel-wide");
+        for artist in 0..50 {
+            for album in 0..10 {
+                let dir: PathBuf = base
+                    .join(format!("Artist {}", artist))
+                    .join(album.to_string());
+                create_dir_all(&dir).unwrap();
+                write(dir.join("1.mp3"), "").unwrap();
+            }
+        }
+
+        let config: Config = toml::from_str("base = ''\nmax_open_dirs = 1").unwrap();
+        let mut scanned = scans(&base, &config).into_iter();
+        let (tree, _): (Node, ScanStats) = scanned.next().unwrap().unwrap();
+        assert_eq!(count_songs(&tree), 500);
+        for scan in scanned {
+            assert_eq!(scan.unwrap().0, tree);
+        }
+    }
+
+    #[cfg(any(feature = "rayon", feature = "tokio"))]
+    #[test]
+    fn cancelled_parallel_scans_are_interrupted() {
+        let base: PathBuf = fixture("parallel-cancel");
+        write(base.join("song.mp3"), "").unwrap();
+        let config: Config = toml::from_str("base = ''").unwrap();
+        let cancel: AtomicBool = AtomicBool::new(true);
+
+        #[cfg(feature = "rayon")]
+        {
+            let error: Error = populate_parallel(&base, &config, &cancel).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::Interrupted);
+        }
+        #[cfg(feature = "tokio")]
+        {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()
+                .unwrap();
+            let scan = populate_async(&base, &config, &cancel);
+            assert_eq!(
+                runtime.block_on(scan).unwrap_err().kind(),
+                ErrorKind::Interrupted
+            );
+        }
+    }
+
+    #[test]
+    fn extensions_add_to_the_builtin_ones() {
+        let base: PathBuf = fixture("populate-extensions");
+        create_dir_all(base.join("Artist")).unwrap();
+        for file in ["a.mp3", "b.M4A", "c.Flac", "notes.txt", "Artist.JPG"] {
+            write(base.join("Artist").join(file), "").unwrap();
+        }
+
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+        let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+        let artist: &Entry = &tree["Artist"];
+        assert_eq!(artist.child.keys().collect::<Vec<_>>(), ["a", "b", "c"]);
+        assert_eq!(artist.art, Some(base.join("Artist").join("Artist.JPG")));
+        assert_eq!(stats.skipped_extension, 2);
+
+        config.extensions.push(String::from("TXT"));
+        let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+        assert_eq!(
+            tree["Artist"].child.keys().collect::<Vec<_>>(),
+            ["a", "b", "c", "notes"]
+        );
+        assert_eq!(stats.skipped_extension, 1);
+
+        // Exact extension matching
+        config.extensions = vec![String::from("txt")];
+        config.case_insensitive_extensions = false;
+        let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+        assert_eq!(
+            tree["Artist"].child.keys().collect::<Vec<_>>(),
+            ["a", "notes"]
+        );
+        assert_eq!(stats.skipped_extension, 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_followed_without_cycles() {
+        use std::os::unix::fs::symlink;
+
+        let base: PathBuf = fixture("populate-symlinks");
+        create_dir_all(base.join("Album")).unwrap();
+        write(base.join("Album").join("song.mp3"), "").unwrap();
+        symlink(&base, base.join("Album").join("Loop")).unwrap();
+        symlink(".", base.join("Album").join("Again")).unwrap();
+        symlink(base.join("Album"), base.join("Linked")).unwrap();
+
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+        let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+        assert_eq!(tree.keys().collect::<Vec<_>>(), ["Album"]);
+        assert_eq!(stats.skipped_symlinks, 3);
+
+        config.follow_symlinks = true;
+        let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+        assert_eq!(tree.keys().collect::<Vec<_>>(), ["Album", "Linked"]);
+        assert_eq!(tree["Album"].child.keys().collect::<Vec<_>>(), ["song"]);
+        assert_eq!(tree["Linked"].child.keys().collect::<Vec<_>>(), ["song"]);
+        assert_eq!(stats.skipped_cycles, 4);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_bases_are_canonicalized() {
+        use std::os::unix::fs::symlink;
+
+        let dir: PathBuf = fixture("populate-symlinked-base");
+        let storage: PathBuf = dir.join("storage").join("music");
+        create_dir_all(storage.join("Album")).unwrap();
+        write(storage.join("Album").join("song.mp3"), "").unwrap();
+        let base: PathBuf = dir.join("Music");
+        symlink(&storage, &base).unwrap();
+
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+        let mut tree: Node = populate_from(&base, &config).unwrap();
+        let song: PathBuf = storage.join("Album").join("song.mp3");
+        assert_eq!(tree["Album"].src, storage.join("Album"));
+        assert_eq!(tree["Album"].child["song"].src, song);
+
+        // Paths are found through the link and through the directory it points to
+        let linked: PathBuf = base.join("Album").join("song.mp3");
+        assert_eq!(
+            find_by_path(&tree, &base, &linked, false).unwrap().src,
+            song
+        );
+        assert_eq!(find_by_path(&tree, &base, &song, false).unwrap().src, song);
+        assert_eq!(
+            find_by_path(&tree, &storage, &linked, false).unwrap().src,
+            song
+        );
+
+        // Inserted files get the same prefix
+        write(storage.join("Album").join("other.mp3"), "").unwrap();
+        let other: PathBuf = base.join("Album").join("other.mp3");
+        insert_path(&mut tree, &base, &other, &config).unwrap();
+        assert_eq!(
+            tree["Album"].child["other"].src,
+            storage.join("Album").join("other.mp3")
+        );
+
+        config.canonicalize_base = false;
+        let kept: Node = populate_from(&base, &config).unwrap();
+        assert_eq!(kept["Album"].child["song"].src, linked);
+        assert_eq!(
+            find_by_path(&kept, &base, &song, false).unwrap().src,
+            linked
+        );
+    }
+
+    #[test]
+    fn missing_bases_are_errors() {
+        let dir: PathBuf = fixture("populate-base-errors");
+        write(dir.join("song.mp3"), "").unwrap();
+        let config: Config = toml::from_str("base = ''").unwrap();
+        let base_error = |error: &Error| error.get_ref()?.downcast_ref::<ConfigError>().cloned();
+
+        let mut tree: Node = Node::new();
+        let error: Error = populate(&dir.join("Missing"), &mut tree, &config).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+        assert_eq!(
+            base_error(&error),
+            Some(ConfigError::NotFound(dir.join("Missing")))
+        );
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "base directory {} does not exist",
+                dir.join("Missing").display()
+            )
+        );
+
+        let error: Error = populate(&dir.join("song.mp3"), &mut tree, &config).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotADirectory);
+        assert_eq!(
+            base_error(&error),
+            Some(ConfigError::NotADirectory(dir.join("song.mp3")))
+        );
+        assert!(tree.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn empty_and_broken_files_are_reported() {
+        let base: PathBuf = fixture("populate-empty-files");
+        let album: PathBuf = base.join("Album");
+        create_dir_all(&album).unwrap();
+        write(album.join("1.flac"), "fLaC").unwrap();
+        write(album.join("2.flac"), "").unwrap();
+        std::os::unix::fs::symlink(album.join("missing.flac"), album.join("3.flac")).unwrap();
+
+        let mut config: Config = toml::from_str("base = ''\nfollow_symlinks = true").unwrap();
+        let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+        assert_eq!(tree["Album"].child.keys().collect::<Vec<_>>(), ["1", "2"]);
+        assert_eq!(stats.broken_symlinks, [album.join("3.flac")]);
+        assert!(stats.errors.is_empty());
+
+        config.skip_empty_files = true;
+        let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+        assert_eq!(tree["Album"].child.keys().collect::<Vec<_>>(), ["1"]);
+        assert_eq!(stats.empty_files, [album.join("2.flac")]);
+    }
+
+    #[test]
+    fn hidden_entries_are_left_out() {
+        let base: PathBuf = fixture("populate-hidden");
+        create_dir_all(base.join(".stversions")).unwrap();
+        create_dir_all(base.join("Album")).unwrap();
+        write(base.join(".stversions").join("old.mp3"), "").unwrap();
+        write(base.join("Album").join(".Album.jpg"), "").unwrap();
+        write(base.join("Album").join("song.mp3"), "").unwrap();
+
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+        let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+        assert_eq!(tree.keys().collect::<Vec<_>>(), ["Album"]);
+        assert_eq!(stats.skipped_hidden, 2);
+
+        config.include_hidden = true;
+        let tree: Node = populate_from(&base, &config).unwrap();
+        assert_eq!(tree.keys().collect::<Vec<_>>(), [".stversions", "Album"]);
+    }
+
+    #[test]
+    fn junk_is_left_out() {
+        let base: PathBuf = fixture("populate-junk");
+        create_dir_all(base.join("Album").join("@eaDir")).unwrap();
+        create_dir_all(base.join(".Trash-1000")).unwrap();
+        for file in [
+            "Album/@eaDir/song.mp3",
+            ".Trash-1000/old.mp3",
+            "Album/song.mp3",
+            "Album/._song.mp3",
+            "Album/Thumbs.db",
+        ] {
+            write(base.join(file), "").unwrap();
+        }
+
+        let mut config: Config = toml::from_str("base = ''\ninclude_hidden = true").unwrap();
+        let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+        assert_eq!(tree.keys().collect::<Vec<_>>(), ["Album"]);
+        assert_eq!(tree["Album"].child.keys().collect::<Vec<_>>(), ["song"]);
+        assert_eq!(stats.skipped_junk, 4);
+
+        config.skip_junk = false;
+        let tree: Node = populate_from(&base, &config).unwrap();
+        assert_eq!(tree.keys().collect::<Vec<_>>(), [".Trash-1000", "Album"]);
+        assert_eq!(tree["Album"].child.len(), 3);
+    }
+
+    #[test]
+    fn sniffed_files_are_inserted() {
+        let podcast: PathBuf = fixture("populate-sniff").join("Podcast");
+        create_dir_all(&podcast).unwrap();
+        write(
+            podcast.join("episode-1"),
+            b"ID3\x04\x00\x00\x00\x00\x00\x00",
+        )
+        .unwrap();
+        write(podcast.join("episode-2.download"), b"OggS\x00\x02").unwrap();
+        write(podcast.join("feed"), "<rss></rss>").unwrap();
+        // Known extensions are never sniffed
+        write(podcast.join("Podcast.jpg"), b"fLaC").unwrap();
+        let base: &Path = podcast.parent().unwrap();
+
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+        let tree: Node = populate_from(base, &config).unwrap();
+        assert!(tree["Podcast"].child.is_empty());
+
+        config.sniff_content = true;
+        let (tree, stats): (Node, ScanStats) = populate_from_with_stats(base, &config).unwrap();
+        let episodes: &Node = &tree["Podcast"].child;
+        assert_eq!(
+            episodes.keys().collect::<Vec<_>>(),
+            ["episode-1", "episode-2"]
+        );
+        assert_eq!(episodes["episode-1"].mime.as_deref(), Some("audio/mpeg"));
+        assert_eq!(episodes["episode-2"].mime.as_deref(), Some("audio/ogg"));
+        assert_eq!(stats.skipped_extension, 2);
+    }
+
+    #[test]
+    fn override_files_apply_to_their_subtree() {
+        let base: PathBuf = fixture("populate-overrides");
+        let album: PathBuf = base.join("Album");
+        create_dir_all(album.join("Bonus")).unwrap();
+        create_dir_all(base.join("Broken")).unwrap();
+        for file in ["a.mp3", "b.flac", "Bonus/c.flac"] {
+            write(album.join(file), "").unwrap();
+        }
+        write(
+            album.join(OVERRIDES_FILE_NAME),
+            "exclude = ['Bonus/']\nart = 'front.png'\nextensions = ['flac']",
+        )
+        .unwrap();
+        write(base.join("Broken").join(OVERRIDES_FILE_NAME), "exclude = ").unwrap();
+
+        let config: Config = toml::from_str("base = ''").unwrap();
+        let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+        assert_eq!(tree["Album"].child.keys().collect::<Vec<_>>(), ["b"]);
+        assert_eq!(tree["Album"].art, Some(album.join("front.png")));
+        assert_eq!(tree["Album"].child["b"].art, Some(album.join("front.png")));
+        assert!(tree.contains_key("Broken"));
+        assert_eq!(stats.invalid_overrides.len(), 1);
+    }
+
+    #[test]
+    fn ignore_files_stack() {
+        let base: PathBuf = fixture("populate-ignore");
+        let album: PathBuf = base.join("Artist").join("Album");
+        create_dir_all(album.join("scans")).unwrap();
+        create_dir_all(base.join("Demos")).unwrap();
+        for file in [
+            "Artist/Album/1.mp3",
+            "Artist/Album/2.mp3",
+            "Artist/Album/Album.jpg",
+            "Artist/Album/Album.png",
+            "Artist/Album/scans/4.mp3",
+            "Artist/3.mp3",
+            "Demos/1.mp3",
+        ] {
+            write(base.join(file), "").unwrap();
+        }
+        write(
+            base.join(IGNORE_FILE_NAME),
+            "# Work in progress\n\nDemos/\n!Demos/1.mp3\n",
+        )
+        .unwrap();
+        write(album.join(IGNORE_FILE_NAME), "scans/\n*.png\n/2.mp3\n").unwrap();
+
+        let config: Config = toml::from_str("base = ''").unwrap();
+        let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+
+        // Negations are not supported, so `Demos` stays ignored
+        assert_eq!(tree.keys().collect::<Vec<_>>(), ["Artist"]);
+        let artist: &Entry = &tree["Artist"];
+        assert_eq!(artist.child.keys().collect::<Vec<_>>(), ["3", "Album"]);
+        assert_eq!(
+            artist.child["Album"].child.keys().collect::<Vec<_>>(),
+            ["1"]
+        );
+        assert_eq!(artist.child["Album"].art, Some(album.join("Album.jpg")));
+        assert_eq!(stats.skipped_excluded, 4);
+    }
+
+    #[test]
+    fn default_art_covers_entries_without_art() {
+        let base: PathBuf = fixture("populate-default-art");
+        create_dir_all(base.join("Artist").join("Album")).unwrap();
+        write(base.join("Artist").join("Album").join("song.mp3"), "").unwrap();
+
+        let mut config: Config = toml::from_str("base = ''").unwrap();
+        let tree: Node = populate_from(&base, &config).unwrap();
+        assert_eq!(tree["Artist"].child["Album"].child["song"].art, None);
+
+        let placeholder: PathBuf = PathBuf::from("/usr/share/mpb/placeholder.png");
+        config.default_art = Some(placeholder.clone());
+        let tree: Node = populate_from(&base, &config).unwrap();
+        let album: &Entry = &tree["Artist"].child["Album"];
+        assert_eq!(album.art, Some(placeholder.clone()));
+        assert_eq!(album.child["song"].art, Some(placeholder));
+    }
+
+    #[test]
+    fn entries_sharing_a_stem_are_disambiguated() {
+        let base: PathBuf = fixture("populate-collisions");
+        create_dir_all(base.join("Live")).unwrap();
+        for file in ["song.mp3", "song.flac", "Live.flac", "Live/encore.mp3"] {
+            write(base.join(file), "").unwrap();
+        }
+
+        let config: Config = toml::from_str("base = ''").unwrap();
+        let (mut tree, stats): (Node, ScanStats) =
+            populate_from_with_stats(&base, &config).unwrap();
+        assert_eq!(
+            tree.keys().collect::<Vec<_>>(),
+            ["Live", "Live (flac)", "song", "song (mp3)"]
+        );
+        assert_eq!(tree["Live"].kind, EntryKind::Directory);
+        assert_eq!(tree["song"].src, base.join("song.flac"));
+        assert_eq!(tree["song (mp3)"].src, base.join("song.mp3"));
+        assert_eq!(
+            stats.key_collisions,
+            [base.join("Live.flac"), base.join("song.mp3")]
+        );
+
+        // Disambiguated entries are found and inserted like any other
+        let live: PathBuf = base.join("Live.flac");
+        assert_eq!(
+            find_by_path(&tree, &base, &live, false),
+            Some(&tree["Live (flac)"])
+        );
+        write(base.join("song.ogg"), "").unwrap();
+        insert_path(&mut tree, &base, &base.join("song.ogg"), &config).unwrap();
+        assert_eq!(tree["song (ogg)"].src, base.join("song.ogg"));
+    }
+
+    #[test]
+    fn directories_sharing_a_stem_keep_their_contents() {
+        let base: PathBuf = fixture("populate-dir-collisions");
+        for file in ["Disc.1/a.mp3", "Disc.1/b.mp3", "Disc.2/c.mp3", "Disc/d.mp3"] {
+            create_dir_all(base.join(file).parent().unwrap()).unwrap();
+            write(base.join(file), "").unwrap();
+        }
+
+        let config: Config = toml::from_str("base = ''").unwrap();
+        let tree: Node = populate_from(&base, &config).unwrap();
+        assert_eq!(
+            tree.keys().collect::<Vec<_>>(),
+            ["Disc", "Disc (1)", "Disc (2)"]
+        );
+        assert_eq!(tree["Disc"].src, base.join("Disc"));
+        assert_eq!(
+            tree["Disc (1)"].child.keys().collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+        assert_eq!(tree["Disc (2)"].child.keys().collect::<Vec<_>>(), ["c"]);
+        assert_eq!(count_songs(&tree), 4);
+    }
+
+    #[test]
+    fn max_entries_stops_consistently() {
+        let base: PathBuf = fixture("populate-max-entries");
+        for album in 0..10 {
+            let album: PathBuf = base.join(format!("Album {}", album));
+            create_dir_all(&album).unwrap();
+            for track in 0..10 {
+                write(album.join(format!("{}.mp3", track)), "track").unwrap();
+            }
+        }
+
+        let config: Config = toml::from_str("base = ''\nmax_entries = 25").unwrap();
+        let (tree, stats): (Node, ScanStats) = populate_from_with_stats(&base, &config).unwrap();
+        assert!(stats.limit_reached);
+        assert_eq!(iter_depth_first(&tree).count(), 25);
+
+        // Two full albums, and the first two tracks of the third
+        assert_eq!(
+            tree.keys().collect::<Vec<_>>(),
+            ["Album 0", "Album 1", "Album 2"]
+        );
+        assert_eq!(tree["Album 2"].child.keys().collect::<Vec<_>>(), ["0", "1"]);
+        assert_eq!(tree["Album 2"].size, 10);
+        for (_, entry) in iter_depth_first(&tree) {
+            let nested: bool = entry.parent.components().count() > 0;
+            assert_eq!(tree.resolve_parent(entry).is_some(), nested);
+            if entry.is_dir() {
+                let size: u64 = entry.child.values().map(|child| child.size).sum();
+                assert_eq!(entry.size, size);
             }
         }
     }
-    Ok(()) // return () if updates ran successfully
 }
@@ -0,0 +1,230 @@
+use super::node::{Entry, Node};
+use super::platform::to_long_path;
+use super::traverse::{count_dirs, count_songs, iter_depth_first};
+use super::utils::latest_modified;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Problems found in a Node tree by `validate_tree()`, serialized for the library problems
+/// screen of the frontend.
+///
+/// # Fields
+/// `missing_src`: `Vec<(Vec<String>, PathBuf)>` -> Keys and `Entry.src` of the entries whose file
+///                                                or directory no longer exists. The descendants
+///                                                of a missing directory are not listed.
+/// `missing_art`: `Vec<(Vec<String>, PathBuf)>` -> Keys and `Entry.art` of the entries whose
+///                                                cover art no longer exists.
+/// `cancelled`: `bool` -> Whether the validation was cancelled before every entry was checked,
+///                        in which case the lists above are incomplete.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ValidationReport {
+    pub missing_src: Vec<(Vec<String>, PathBuf)>,
+    pub missing_art: Vec<(Vec<String>, PathBuf)>,
+    pub cancelled: bool,
+}
+
+/// Helper function that checks that the file or directory and the cover art of every entry of
+/// `node` and its descendants still exist, in the order of `iter_depth_first()`.
+///
+/// Every art path is only checked once, however many entries share it. The validation stops as
+/// soon as `cancel` is set, from any thread, so it can run in the background and be interrupted
+/// cheaply when the user navigates away.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, remove_file, write};
+/// # use std::sync::atomic::AtomicBool;
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-validate-tree");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Album")).unwrap();
+/// create_dir_all(base.join("Gone").join("Disc 1")).unwrap();
+/// write(base.join("Album").join("Album.png"), "").unwrap();
+/// for song in ["Album/1.mp3", "Album/2.mp3", "Gone/Disc 1/3.mp3"] {
+///     write(base.join(song), "").unwrap();
+/// }
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+/// let cancel: AtomicBool = AtomicBool::new(false);
+/// assert_eq!(validate_tree(&tree, &cancel), ValidationReport::default());
+///
+/// remove_file(base.join("Album").join("2.mp3")).unwrap();
+/// remove_file(base.join("Album").join("Album.png")).unwrap();
+/// remove_dir_all(base.join("Gone")).unwrap();
+///
+/// let report: ValidationReport = validate_tree(&tree, &cancel);
+/// let keys = |missing: &[(Vec<String>, PathBuf)]| -> Vec<String> {
+///     missing.iter().map(|(keys, _)| keys.join("/")).collect()
+/// };
+/// assert_eq!(keys(&report.missing_src), ["Album/2", "Gone"]);
+/// assert_eq!(keys(&report.missing_art), ["Album", "Album/1"]);
+/// assert_eq!(report.missing_art[0].1, base.join("Album").join("Album.png"));
+/// assert!(!report.cancelled);
+///
+/// cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+/// assert!(validate_tree(&tree, &cancel).cancelled);
+/// ```
+pub fn validate_tree(node: &Node, cancel: &AtomicBool) -> ValidationReport {
+    let mut report: ValidationReport = ValidationReport::default();
+    let mut art_exists: BTreeMap<&PathBuf, bool> = BTreeMap::new();
+    let mut missing_dirs: Vec<Vec<&str>> = Vec::new();
+
+    for (keys, entry) in iter_depth_first(node) {
+        if cancel.load(Ordering::Relaxed) {
+            report.cancelled = true;
+            break;
+        }
+        if missing_dirs.iter().any(|dir| keys.starts_with(dir)) {
+            continue;
+        }
+
+        if !to_long_path(&entry.src).exists() {
+            report.missing_src.push((owned(&keys), entry.src.clone()));
+            if entry.is_dir() {
+                missing_dirs.push(keys);
+            }
+            continue;
+        }
+
+        if let Some(art) = &entry.art {
+            if !*art_exists
+                .entry(art)
+                .or_insert_with(|| to_long_path(art).exists())
+            {
+                report.missing_art.push((owned(&keys), art.clone()));
+            }
+        }
+    }
+
+    report
+}
+
+/// Summary of the changes made by `garbage_collect()`, serialized for the frontend.
+///
+/// # Fields
+/// `removed_tracks`: `usize` -> Audio files removed from the tree, including those of removed
+///                              directories.
+/// `removed_dirs`: `usize` -> Directories removed from the tree, because they no longer exist or
+///                            were left without children, including those nested in them.
+/// `cleared_art`: `usize` -> Entries whose cover art no longer existed and was replaced.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct GcReport {
+    pub removed_tracks: usize,
+    pub removed_dirs: usize,
+    pub cleared_art: usize,
+}
+
+/// Helper function that removes the entries of `node` and its descendants whose file or
+/// directory no longer exists, and repairs the rest of the tree in place:
+/// - Cover art that no longer exists is replaced by the art of the parent entry, or `None` for
+///   top-level entries.
+/// - Directories left without children by the removal are removed too.
+/// - The `size` and `modified` fields of directories that lost descendants are recomputed.
+///
+/// Every entry is visited, whatever the number of its siblings removed along with it.
+///
+/// # Examples
+/// ```
+/// # use vfh::music::*;
+/// # use std::fs::{create_dir_all, remove_dir_all, remove_file, write};
+/// let base: PathBuf = env::temp_dir().join("vfh-doc-garbage-collect");
+/// # let _ = remove_dir_all(&base);
+/// create_dir_all(base.join("Artist").join("Album")).unwrap();
+/// create_dir_all(base.join("Artist").join("Gone")).unwrap();
+/// create_dir_all(base.join("Emptied")).unwrap();
+/// write(base.join("Artist").join("Artist.png"), "").unwrap();
+/// write(base.join("Artist").join("Album").join("Album.png"), "").unwrap();
+/// for song in ["1", "2", "3", "4", "5"] {
+///     write(base.join("Artist").join("Album").join(format!("{}.mp3", song)), "").unwrap();
+/// }
+/// write(base.join("Artist").join("Gone").join("6.mp3"), "").unwrap();
+/// write(base.join("Emptied").join("7.mp3"), "").unwrap();
+///
+/// let config: Config = toml::from_str("base = ''").unwrap();
+/// let mut tree: Node = Node::new();
+/// populate(&base, &mut tree, &config).unwrap();
+///
+/// // Adjacent siblings removed together
+/// for song in ["1", "2", "4"] {
+///     remove_file(base.join("Artist").join("Album").join(format!("{}.mp3", song))).unwrap();
+/// }
+/// remove_file(base.join("Artist").join("Album").join("Album.png")).unwrap();
+/// remove_dir_all(base.join("Artist").join("Gone")).unwrap();
+/// remove_file(base.join("Emptied").join("7.mp3")).unwrap();
+///
+/// let report: GcReport = garbage_collect(&mut tree);
+/// assert_eq!(
+///     report,
+///     GcReport { removed_tracks: 5, removed_dirs: 2, cleared_art: 3 }
+/// );
+/// assert_eq!(tree.keys().collect::<Vec<_>>(), ["Artist"]);
+/// let album: &Entry = &tree["Artist"].child["Album"];
+/// assert_eq!(album.child.keys().collect::<Vec<_>>(), ["3", "5"]);
+/// assert_eq!(album.art, Some(base.join("Artist").join("Artist.png")));
+/// assert_eq!(album.child["3"].art, album.art);
+/// assert_eq!(garbage_collect(&mut tree), GcReport::default());
+/// ```
+pub fn garbage_collect(node: &mut Node) -> GcReport {
+    let mut report: GcReport = GcReport::default();
+    collect_under(node, None, &mut report, &mut BTreeMap::new());
+    report
+}
+
+/// Helper function that performs `garbage_collect()` on the children `node` of an entry whose
+/// art is `parent_art`, caching whether every art path checked exists in `art_exists`. Returns
+/// whether any descendant was removed.
+fn collect_under(
+    node: &mut Node,
+    parent_art: Option<&Path>,
+    report: &mut GcReport,
+    art_exists: &mut BTreeMap<PathBuf, bool>,
+) -> bool {
+    let mut removed: bool = false;
+
+    node.retain(|_, entry| {
+        if !to_long_path(&entry.src).exists() {
+            report.removed_tracks += count_songs(&entry.child) + usize::from(entry.is_leaf());
+            report.removed_dirs += count_dirs(&entry.child) + usize::from(entry.is_dir());
+            removed = true;
+            return false;
+        }
+
+        if let Some(art) = &entry.art {
+            let exists: bool = *art_exists
+                .entry(art.clone())
+                .or_insert_with(|| to_long_path(art).exists());
+            if !exists {
+                entry.art = parent_art.map(Path::to_path_buf);
+                report.cleared_art += 1;
+            }
+        }
+
+        if entry.child.is_empty() {
+            return true;
+        }
+        let Entry { art, child, .. } = entry;
+        if !collect_under(child, art.as_deref(), report, art_exists) {
+            return true;
+        }
+
+        removed = true;
+        if entry.child.is_empty() {
+            report.removed_dirs += 1;
+            return false;
+        }
+        entry.size = entry.child.values().map(|child| child.size).sum();
+        entry.modified = latest_modified(&entry.child);
+        true
+    });
+
+    removed
+}
+
+/// Helper function that returns owned copies of `keys`.
+fn owned(keys: &[&str]) -> Vec<String> {
+    keys.iter().copied().map(String::from).collect()
+}
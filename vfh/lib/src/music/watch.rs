@@ -0,0 +1,121 @@
+use super::config::{Config, ConfigError};
+use notify::{recommended_watcher, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// How long the configuration file must stay untouched before it is reloaded. Editors commonly
+/// write a file several times (truncate, write, rename, chmod) when saving it once.
+pub const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Handle to a running configuration file watcher returned by `Config::watch()`.
+///
+/// The watcher stops as soon as this handle is dropped.
+pub struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl Config {
+    /// Helper function that watches the configuration file this `Config` was loaded from (see
+    /// `Config::source_path()`) and invokes `callback` with a freshly parsed `Config` whenever
+    /// the file changes.
+    ///
+    /// Changes are debounced by `WATCH_DEBOUNCE`. Reloading goes through `Config::from_path()`
+    /// and `Config::with_env_overrides()`, so any failure is handed to `callback` as an error
+    /// rather than panicking; callers should keep using their previous `Config` in that case.
+    ///
+    /// The parent directory is watched instead of the file itself so editors that save by
+    /// replacing the file, and files created after the watcher started, are still noticed.
+    ///
+    /// # Examples
+    /// ```
+    /// # use vfh::music::*;
+    /// # use std::fs::{create_dir_all, remove_dir_all, write};
+    /// # use std::sync::mpsc::channel;
+    /// # use std::time::Duration;
+    /// let dir: PathBuf = env::temp_dir().join("vfh-doc-config-watch");
+    /// # let _ = remove_dir_all(&dir);
+    /// create_dir_all(&dir).unwrap();
+    /// write(dir.join("config.toml"), "base = '/srv/music'").unwrap();
+    ///
+    /// let config: Config = Config::from_path(&dir.join("config.toml")).unwrap();
+    /// let (sender, receiver) = channel();
+    /// let _watcher: ConfigWatcher = config
+    ///     .watch(move |reloaded| sender.send(reloaded).unwrap())
+    ///     .unwrap();
+    /// # std::thread::sleep(Duration::from_millis(100));
+    ///
+    /// write(dir.join("config.toml"), "base = '/mnt/music'").unwrap();
+    /// let reloaded: Config = receiver.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+    /// assert_eq!(reloaded.base, PathBuf::from("/mnt/music"));
+    ///
+    /// write(dir.join("config.toml"), "base = ").unwrap();
+    /// let error = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    /// assert!(matches!(error, Err(ConfigError::Parse { .. })));
+    /// ```
+    pub fn watch<F>(&self, callback: F) -> Result<ConfigWatcher, ConfigError>
+    where
+        F: FnMut(Result<Config, ConfigError>) + Send + 'static,
+    {
+        let source: PathBuf = self.source_path().to_path_buf();
+        let dir: PathBuf = match source.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let (sender, receiver) = channel::<()>();
+        let file: PathBuf = source.clone();
+        let mut watcher: RecommendedWatcher =
+            recommended_watcher(move |event: notify::Result<Event>| {
+                if let Ok(event) = event {
+                    if event.paths.iter().any(|path| same_file(path, &file)) {
+                        let _ = sender.send(());
+                    }
+                }
+            })
+            .map_err(|e| watch_error(&dir, e))?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(|e| watch_error(&dir, e))?;
+
+        thread::spawn(move || reload_loop(&source, &receiver, callback));
+
+        Ok(ConfigWatcher { _watcher: watcher })
+    }
+}
+
+/// Helper function that waits for change notifications and reloads the configuration once
+/// `WATCH_DEBOUNCE` has passed without further changes. Returns when the watcher is dropped.
+fn reload_loop<F>(source: &Path, receiver: &Receiver<()>, mut callback: F)
+where
+    F: FnMut(Result<Config, ConfigError>),
+{
+    while receiver.recv().is_ok() {
+        loop {
+            match receiver.recv_timeout(WATCH_DEBOUNCE) {
+                Ok(()) => continue,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        callback(Config::from_path(source).and_then(Config::with_env_overrides));
+    }
+}
+
+/// Helper function that compares an event path against the watched file, ignoring differences
+/// in how the parent directory was spelled (for instance through a symlinked temp directory).
+fn same_file(path: &Path, file: &Path) -> bool {
+    path == file
+        || (path.file_name() == file.file_name()
+            && path.parent().and_then(|p| p.canonicalize().ok())
+                == file.parent().and_then(|p| p.canonicalize().ok()))
+}
+
+fn watch_error(path: &Path, error: notify::Error) -> ConfigError {
+    ConfigError::Io {
+        path: path.to_path_buf(),
+        message: error.to_string(),
+    }
+}